@@ -9,8 +9,8 @@ import * as SCA from "./sca";
 
 import * as fs from 'fs';
 import * as path from 'path';
-import { AccountTypes, OperationAccountData, getAccountData } from "./type";
-import { download } from "./pinata"
+import { AccountTypes, ArbiterPoolData, ArbiterProfileData, ArbiterStakeData, ContentKind, MIN_ARBITER_STAKE, EvidenceLogData, OperationAccountData, contentRefToString, getAccountData, ipfsContent } from "./type";
+import { download, upload } from "./pinata"
 
 const connection = new Connection(process.env.URL,"finalized");
 var arbiter: Keypair;
@@ -18,6 +18,64 @@ var opInfo: PublicKey = new PublicKey(process.env.OPERATION);
 
 arbiter = Util.readKey("arbiter");
 
+async function saveProfile(active: boolean) {
+
+	console.log("Using arbiter " + arbiter.publicKey.toBase58());
+
+	let obj = {
+		name: "Arbiter " + arbiter.publicKey.toBase58(),
+		desc: "bla bla bla",
+	};
+	let ipfsCID = await upload(obj);
+
+	let result = await SCA.registerArbiterProfile(connection, arbiter, active, ipfsContent(ipfsCID));
+	console.log(JSON.stringify(result));
+
+	let arbiterProfile = await getAccountData(connection, SCA.arbiterProfileAddress(arbiter.publicKey), AccountTypes.ArbiterProfile) as ArbiterProfileData
+    console.log(Util.toJSONString(arbiterProfile));
+}
+
+async function stake(amount: bigint) {
+
+	console.log("Using arbiter " + arbiter.publicKey.toBase58());
+
+	let result = await SCA.stakeArbiter(connection, arbiter, amount);
+	console.log(JSON.stringify(result));
+
+	let arbiterStake = await getAccountData(connection, SCA.arbiterStakeAddress(arbiter.publicKey), AccountTypes.ArbiterStake) as ArbiterStakeData
+    console.log(Util.toJSONString(arbiterStake));
+}
+
+async function unstake(operations: PublicKey[]) {
+
+	console.log("Using arbiter " + arbiter.publicKey.toBase58());
+
+	let result = await SCA.unstakeArbiter(connection, arbiter, operations);
+	console.log(JSON.stringify(result));
+
+	let arbiterStake = await getAccountData(connection, SCA.arbiterStakeAddress(arbiter.publicKey), AccountTypes.ArbiterStake) as ArbiterStakeData
+    console.log(Util.toJSONString(arbiterStake));
+}
+
+async function joinPool() {
+
+	console.log("Using arbiter " + arbiter.publicKey.toBase58());
+
+	let result = await SCA.joinArbiterPool(connection, arbiter);
+	console.log(JSON.stringify(result));
+
+	let arbiterPool = await getAccountData(connection, SCA.arbiterPoolAddress(), AccountTypes.ArbiterPool) as ArbiterPoolData
+    console.log(Util.toJSONString(arbiterPool));
+}
+
+async function leavePool() {
+
+	console.log("Using arbiter " + arbiter.publicKey.toBase58());
+
+	let result = await SCA.leaveArbiterPool(connection, arbiter);
+	console.log(JSON.stringify(result));
+}
+
 async function assignArbiter() {
 
 	console.log("Using arbiter " + arbiter.publicKey.toBase58());
@@ -33,21 +91,25 @@ async function review() {
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
 
-	let arr1 = new Uint8Array(operationAccountInfo.ipfsCid);
-	let ipfsstr1 = Buffer.from(arr1.buffer).toString();
+	let ipfsstr1 = contentRefToString(operationAccountInfo.itemContent);
 	console.log("Info IPFS " + ipfsstr1);
 
-	let arr2 = new Uint8Array(operationAccountInfo.sellerIpfsExt);
-	let ipfsstr2 = Buffer.from(arr2.buffer).toString();
-	console.log("Seller IPFS " + ipfsstr2);
+	let reasonStr = contentRefToString(operationAccountInfo.disputeReason);
+	console.log("Dispute opened by " + operationAccountInfo.disputeInitiator.toBase58() + " at " + operationAccountInfo.disputeStartedAt.toString() + ": " + reasonStr);
+
+	// Only IPFS content can be fetched from the gateway, the rest is fetched by hand
+	if (operationAccountInfo.itemContent.kind == ContentKind.Ipfs) await download(ipfsstr1);
+	if (operationAccountInfo.disputeReason.kind == ContentKind.Ipfs) await download(reasonStr);
 
-	let arr3 = new Uint8Array(operationAccountInfo.buyerIpfsExt);
-	let ipfsstr3 = Buffer.from(arr3.buffer).toString();
-	console.log("Buyer IPFS " + ipfsstr3);
+	// Every submission made during the dispute, in order
+	let evidenceLog = await getAccountData(connection, SCA.evidenceLogAddress(opInfo), AccountTypes.EvidenceLog) as EvidenceLogData
 
-	await download(ipfsstr1);
-	await download(ipfsstr2);
-	await download(ipfsstr3);
+	for (const entry of evidenceLog.entries) {
+		let contentStr = contentRefToString(entry.content);
+		console.log("Evidence by " + entry.submitter.toBase58() + " at " + entry.submittedAt.toString() + ": " + contentStr);
+
+		if (entry.content.kind == ContentKind.Ipfs) await download(contentStr);
+	}
 }
 
 async function arbiterVotes() {
@@ -63,7 +125,12 @@ async function arbiterVotes() {
 
 async function requestInput() {
 	console.log("Available functions:");
-	console.log("register -> Register yourself as arbiter in an operation.");
+	console.log("profile [off] -> Create or update your arbiter profile, active unless off.");
+	console.log("stake [lamports] -> Add to your stake, 1 SOL by default.");
+	console.log("unstake [operation] -> Request the unstake, or withdraw once the cooldown is over, freeing your seat in a finished operation.");
+	console.log("join -> Join the pool, to be picked for operations with random arbiters.");
+	console.log("leave -> Leave the pool.");
+	console.log("register -> Register yourself as arbiter in an operation, or confirm the seat you were picked for.");
 	console.log("download -> Arbiter downloads info.");
 	console.log("vote -> Arbiter votes.");
 	console.log("[Any key] -> Quit tool");
@@ -79,7 +146,44 @@ async function requestInput() {
 		console.log(task);
 		rl.close();
 
-		switch (task) {
+		let [command, arg] = task.trim().split(/\s+/);
+
+		switch (command) {
+			case "profile": 
+				try {await saveProfile(arg != "off");}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "stake": 
+				try {await stake(arg ? BigInt(arg) : MIN_ARBITER_STAKE);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "unstake": 
+				try {await unstake(arg ? [new PublicKey(arg)] : []);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "join": 
+				try {await joinPool();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "leave": 
+				try {await leavePool();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "register": 
 				try {await assignArbiter();}
 				catch(err) {console.error(err)} 
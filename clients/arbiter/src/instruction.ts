@@ -1,6 +1,7 @@
 import { u32, u8, struct, Layout, seq } from "@solana/buffer-layout";
 import { publicKey, u64, bool } from "@solana/buffer-layout-utils";
-import { Participant, TokenVersion } from "./type";
+import { PublicKey } from "@solana/web3.js";
+import { ArbiterSelection, ContentRef, ContentRefData, LineItemInput, MilestoneInput, Participant, TokenVersion, encodeContentRef, encodeContentRefData } from "./type";
 
 export const enum SCAInstruction {
 	InitializeOperation = 0,
@@ -15,15 +16,43 @@ export const enum SCAInstruction {
 	BuyerAddInfo = 9,
 	ArbiterVote = 10,
 	ParticipantClaim = 11,
+	MigrateOperation = 12,
+	ArbiterAddInfo = 13,
+	RejectArbiter = 14,
+	ReplaceArbiters = 15,
+	InviteArbiter = 16,
+	RegisterArbiterProfile = 17,
+	StakeArbiter = 18,
+	UnstakeArbiter = 19,
+	JoinArbiterPool = 20,
+	LeaveArbiterPool = 21,
+	AppealVerdict = 22,
+	ProposeSettlement = 23,
+	AcceptSettlement = 24,
+	SellerPartialRefund = 25,
+	DefineMilestones = 26,
+	ReleaseMilestone = 27,
+	DefineLineItems = 28,
+	RefundLineItem = 29,
+	ProposePrice = 30,
+	AcceptOffer = 31,
+	ReassignBuyer = 32,
+	UpdateListing = 33,
+	RegisterBuyerAndDeposit = 34,
+	MarkShipped = 35,
+	ConfirmReceived = 36,
+	AttestDelivery = 37,
+	FinalizeRelease = 38,
 };
 
-export function createInitializeOperationInstruction(value: bigint, tokenVersion: TokenVersion, ipfsCID: string): Buffer {
+export function createInitializeOperationInstruction(value: bigint, tokenVersion: TokenVersion, itemContent: ContentRef,
+	arbiterSelection: ArbiterSelection = ArbiterSelection.Manual, intendedBuyer?: PublicKey, attestor?: PublicKey,
+	inspectionPeriod?: bigint): Buffer {
 
 	const dataLayout = struct([
 		u8('instruction') as Layout<never>, // Single Byte
 		u64('value') as Layout<never>, // 8 bytes
 		u8('tokenVersion') as Layout<never>, // Single Byte
-		(seq(u8(), 46, 'ipfsCid') as unknown) as Layout<never>, // IPFS CID hash, 46 bytes
 	]);
 
 	const data = Buffer.alloc(dataLayout.span);
@@ -33,18 +62,35 @@ export function createInitializeOperationInstruction(value: bigint, tokenVersion
 			instruction: SCAInstruction.InitializeOperation, 
 			value: value,
             tokenVersion: tokenVersion,
-			ipfsCid: Buffer.from(ipfsCID, "utf-8")
 		},
 		data,
 	);
 
-	return data;
+	// Optional fields follow, each as a 0x00 flag when absent, or a 0x01 flag followed by the value
+	// The intended buyer reserves the operation for that buyer
+	let intended = intendedBuyer ? Buffer.concat([Buffer.from([1]), intendedBuyer.toBuffer()]) : Buffer.from([0]);
+
+	// The attestor can sign the delivery instead of the buyer
+	let attesting = attestor ? Buffer.concat([Buffer.from([1]), attestor.toBuffer()]) : Buffer.from([0]);
+
+	// The inspection period in seconds lets anyone release the value once over
+	let inspection = Buffer.from([0]);
+	if (inspectionPeriod) {
+		inspection = Buffer.alloc(9);
+		inspection.writeUInt8(1);
+		inspection.writeBigInt64LE(inspectionPeriod, 1);
+	}
+
+	return Buffer.concat([data, encodeContentRef(itemContent), Buffer.from([arbiterSelection]), intended, attesting, inspection]);
 }
 
-export function createRegisterBuyerInstruction(): Buffer {
+export function createRegisterBuyerInstruction(value: bigint, tokenVersion: TokenVersion, itemContent: ContentRefData,
+	listingRevision?: number): Buffer {
 
 	const dataLayout = struct([
 		u8('instruction') as Layout<never>, // Single Byte
+		u64('value') as Layout<never>, // 8 bytes
+		u8('tokenVersion') as Layout<never>, // Single Byte
 	]);
 
 	const data = Buffer.alloc(dataLayout.span);
@@ -52,11 +98,24 @@ export function createRegisterBuyerInstruction(): Buffer {
 	dataLayout.encode(
 		{
 			instruction: SCAInstruction.RegisterBuyer, 
+			value: value,
+			tokenVersion: tokenVersion,
 		},
 		data,
 	);
 
-	return data;
+	// The terms are the ones the buyer reviewed, the registration fails if they changed
+	let terms = Buffer.concat([data, encodeContentRefData(itemContent)]);
+
+	// The listing revision is optional, and fails the registration if the listing was updated
+	if (listingRevision === undefined) {
+		return terms;
+	}
+
+	let revision = Buffer.alloc(4);
+	revision.writeUInt32LE(listingRevision);
+
+	return Buffer.concat([terms, revision]);
 }
 
 export function createRegisterArbiterInstruction(): Buffer {
@@ -151,7 +210,7 @@ export function createSellerRefundInstruction(): Buffer {
 	return data;
 }
 
-export function createStartDisputeInstruction(): Buffer {
+export function createStartDisputeInstruction(reason: ContentRef): Buffer {
 
 	const dataLayout = struct([
 		u8('instruction') as Layout<never>, // Single Byte
@@ -166,14 +225,13 @@ export function createStartDisputeInstruction(): Buffer {
 		data,
 	);
 
-	return data;
+	return Buffer.concat([data, encodeContentRef(reason)]);
 }
 
-export function createSellerAddInfoInstruction(ipfsCID: string): Buffer {
+export function createSellerAddInfoInstruction(contentExt: ContentRef): Buffer {
 
 	const dataLayout = struct([
 		u8('instruction') as Layout<never>, // Single Byte
-		(seq(u8(), 46, 'ipfsCid') as unknown) as Layout<never>, // IPFS CID hash, 46 bytes
 	]);
 
 	const data = Buffer.alloc(dataLayout.span);
@@ -181,19 +239,17 @@ export function createSellerAddInfoInstruction(ipfsCID: string): Buffer {
 	dataLayout.encode(
 		{
 			instruction: SCAInstruction.SellerAddInfo, 
-			ipfsCid: Buffer.from(ipfsCID, "utf-8")
 		},
 		data,
 	);
 
-	return data;
+	return Buffer.concat([data, encodeContentRef(contentExt)]);
 }
 
-export function createBuyerAddInfoInstruction(ipfsCID: string): Buffer {
+export function createBuyerAddInfoInstruction(contentExt: ContentRef): Buffer {
 
 	const dataLayout = struct([
 		u8('instruction') as Layout<never>, // Single Byte
-		(seq(u8(), 46, 'ipfsCid') as unknown) as Layout<never>, // IPFS CID hash, 46 bytes
 	]);
 
 	const data = Buffer.alloc(dataLayout.span);
@@ -201,12 +257,11 @@ export function createBuyerAddInfoInstruction(ipfsCID: string): Buffer {
 	dataLayout.encode(
 		{
 			instruction: SCAInstruction.BuyerAddInfo, 
-			ipfsCid: Buffer.from(ipfsCID, "utf-8")
 		},
 		data,
 	);
 
-	return data;
+	return Buffer.concat([data, encodeContentRef(contentExt)]);
 }
 
 
@@ -248,3 +303,558 @@ export function createParticipantClaimInstruction(): Buffer {
 
 	return data;
 }
+
+export function createMigrateOperationInstruction(): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.MigrateOperation, 
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createArbiterAddInfoInstruction(contentExt: ContentRef): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.ArbiterAddInfo, 
+		},
+		data,
+	);
+
+	return Buffer.concat([data, encodeContentRef(contentExt)]);
+}
+
+export function createRejectArbiterInstruction(arbiter: PublicKey): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		publicKey('arbiter') as Layout<never>, // 32 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.RejectArbiter, 
+			arbiter: arbiter,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createReplaceArbitersInstruction(): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.ReplaceArbiters, 
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createInviteArbiterInstruction(arbiter: PublicKey): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		publicKey('arbiter') as Layout<never>, // 32 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.InviteArbiter, 
+			arbiter: arbiter,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createRegisterArbiterProfileInstruction(active: boolean, bio: ContentRef): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		bool('active') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.RegisterArbiterProfile, 
+			active: active,
+		},
+		data,
+	);
+
+	return Buffer.concat([data, encodeContentRef(bio)]);
+}
+
+export function createStakeArbiterInstruction(amount: bigint): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u64('amount') as Layout<never>, // 8 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.StakeArbiter, 
+			amount: amount,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createUnstakeArbiterInstruction(): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.UnstakeArbiter, 
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createJoinArbiterPoolInstruction(): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.JoinArbiterPool, 
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createLeaveArbiterPoolInstruction(): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.LeaveArbiterPool, 
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createAppealVerdictInstruction(grounds: ContentRef): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.AppealVerdict, 
+		},
+		data,
+	);
+
+	return Buffer.concat([data, encodeContentRef(grounds)]);
+}
+
+export function createProposeSettlementInstruction(buyerAmount: bigint): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u64('buyerAmount') as Layout<never>, // 8 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.ProposeSettlement, 
+			buyerAmount: buyerAmount,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createAcceptSettlementInstruction(buyerAmount: bigint): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u64('buyerAmount') as Layout<never>, // 8 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.AcceptSettlement, 
+			buyerAmount: buyerAmount,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createSellerPartialRefundInstruction(amount: bigint): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u64('amount') as Layout<never>, // 8 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.SellerPartialRefund, 
+			amount: amount,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createDefineMilestonesInstruction(milestones: MilestoneInput[]): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u8('count') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.DefineMilestones, 
+			count: milestones.length,
+		},
+		data,
+	);
+
+	// Each milestone is its amount followed by its content reference
+	let buffers = [data];
+	for (const milestone of milestones) {
+		let amount = Buffer.alloc(8);
+		amount.writeBigUInt64LE(milestone.amount);
+		buffers.push(amount, encodeContentRef(milestone.content));
+	}
+
+	return Buffer.concat(buffers);
+}
+
+export function createReleaseMilestoneInstruction(index: number): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u8('index') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.ReleaseMilestone, 
+			index: index,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createDefineLineItemsInstruction(lineItems: LineItemInput[]): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u8('count') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.DefineLineItems, 
+			count: lineItems.length,
+		},
+		data,
+	);
+
+	// Each line item is its unit price and quantity, followed by its content reference
+	let buffers = [data];
+	for (const lineItem of lineItems) {
+		let priceAndQuantity = Buffer.alloc(12);
+		priceAndQuantity.writeBigUInt64LE(lineItem.unitPrice);
+		priceAndQuantity.writeUInt32LE(lineItem.quantity, 8);
+		buffers.push(priceAndQuantity, encodeContentRef(lineItem.content));
+	}
+
+	return Buffer.concat(buffers);
+}
+
+export function createRefundLineItemInstruction(index: number, quantity: number): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u8('index') as Layout<never>, // Single Byte
+		u32('quantity') as Layout<never>,
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.RefundLineItem, 
+			index: index,
+			quantity: quantity,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createProposePriceInstruction(amount: bigint): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u64('amount') as Layout<never>, // 8 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.ProposePrice, 
+			amount: amount,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createAcceptOfferInstruction(index: number): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u32('index') as Layout<never>, // 4 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.AcceptOffer, 
+			index: index,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createReassignBuyerInstruction(buyer: PublicKey): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		publicKey('buyer') as Layout<never>, // 32 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.ReassignBuyer, 
+			buyer: buyer,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createUpdateListingInstruction(value: bigint, itemContent: ContentRef): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u64('value') as Layout<never>, // 8 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.UpdateListing, 
+			value: value,
+		},
+		data,
+	);
+
+	return Buffer.concat([data, encodeContentRef(itemContent)]);
+}
+
+export function createRegisterBuyerAndDepositInstruction(value: bigint, tokenVersion: TokenVersion, itemContent: ContentRefData,
+	arbiters: PublicKey[], listingRevision?: number): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		u64('value') as Layout<never>, // 8 bytes
+		u8('tokenVersion') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.RegisterBuyerAndDeposit, 
+			value: value,
+			tokenVersion: tokenVersion,
+		},
+		data,
+	);
+
+	// The arbiters are the ones the buyer approves, in the order they hold the slots
+	let terms = Buffer.concat([data, encodeContentRefData(itemContent)].concat(arbiters.map((arbiter) => arbiter.toBuffer())));
+
+	// The listing revision is optional, and fails the registration if the listing was updated
+	if (listingRevision === undefined) {
+		return terms;
+	}
+
+	let revision = Buffer.alloc(4);
+	revision.writeUInt32LE(listingRevision);
+
+	return Buffer.concat([terms, revision]);
+}
+
+export function createMarkShippedInstruction(trackingContent: ContentRef): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.MarkShipped, 
+		},
+		data,
+	);
+
+	return Buffer.concat([data, encodeContentRef(trackingContent)]);
+}
+
+export function createConfirmReceivedInstruction(): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.ConfirmReceived, 
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createAttestDeliveryInstruction(delivered: boolean, deliveredAt: bigint): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+		bool('delivered') as Layout<never>, // Single Byte
+		u64('deliveredAt') as Layout<never>, // 8 bytes
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.AttestDelivery, 
+			delivered: delivered,
+			deliveredAt: deliveredAt,
+		},
+		data,
+	);
+
+	return data;
+}
+
+export function createFinalizeReleaseInstruction(): Buffer {
+
+	const dataLayout = struct([
+		u8('instruction') as Layout<never>, // Single Byte
+	]);
+
+	const data = Buffer.alloc(dataLayout.span);
+
+	dataLayout.encode(
+		{
+			instruction: SCAInstruction.FinalizeRelease, 
+		},
+		data,
+	);
+
+	return data;
+}
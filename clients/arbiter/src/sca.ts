@@ -1,15 +1,175 @@
 import {
     Connection, Keypair, PublicKey,
     Transaction, TransactionInstruction,
-    sendAndConfirmTransaction, SystemProgram,
+    sendAndConfirmTransaction, SystemProgram, SYSVAR_SLOT_HASHES_PUBKEY,
+    SYSVAR_INSTRUCTIONS_PUBKEY, Ed25519Program,
 } from "@solana/web3.js";
 
-import { createArbiterVoteInstruction, createBuyerAddInfoInstruction, createBuyerDepositInstruction, createBuyerReleaseInstruction, createInitializeOperationInstruction, createParticipantApprovesArbitersInstruction, createParticipantClaimInstruction, createRegisterArbiterInstruction, createRegisterBuyerInstruction, createSellerAddInfoInstruction, createSellerRefundInstruction, createStartDisputeInstruction } from "./instruction";
-import { AccountTypes, Participant, TokenVersion, createNewDataAccount } from "./type";
+import { createAcceptOfferInstruction, createAcceptSettlementInstruction, createAppealVerdictInstruction, createAttestDeliveryInstruction, createArbiterAddInfoInstruction, createArbiterVoteInstruction, createBuyerAddInfoInstruction, createBuyerDepositInstruction, createBuyerReleaseInstruction, createConfirmReceivedInstruction, createDefineLineItemsInstruction, createDefineMilestonesInstruction, createFinalizeReleaseInstruction, createInitializeOperationInstruction, createInviteArbiterInstruction, createJoinArbiterPoolInstruction, createLeaveArbiterPoolInstruction, createMarkShippedInstruction, createMigrateOperationInstruction, createParticipantApprovesArbitersInstruction, createParticipantClaimInstruction, createProposePriceInstruction, createProposeSettlementInstruction, createReassignBuyerInstruction, createRefundLineItemInstruction, createRegisterArbiterInstruction, createRegisterArbiterProfileInstruction, createRegisterBuyerAndDepositInstruction, createRegisterBuyerInstruction, createRejectArbiterInstruction, createReleaseMilestoneInstruction, createReplaceArbitersInstruction, createSellerAddInfoInstruction, createSellerPartialRefundInstruction, createSellerRefundInstruction, createStakeArbiterInstruction, createStartDisputeInstruction, createUnstakeArbiterInstruction, createUpdateListingInstruction } from "./instruction";
+import { ARBITER_POOL_SEED, ARBITER_PROFILE_SEED, ARBITER_STAKE_SEED, AccountTypes, ArbiterSelection, ContentRef, EVIDENCE_LOG_SEED, LINE_ITEMS_SEED, LineItemInput, MILESTONES_SEED, MilestoneInput, OFFER_LOG_SEED, OperationAccountData, OperationStatus, Participant, TokenVersion, VotingOptions, createNewDataAccount, getAccountData } from "./type";
 
 const programId = new PublicKey(process.env.PROGRAMID);
 
-export async function initializeOperation(conn: Connection, seller: Keypair, ipfsCID: string): Promise<PublicKey> {  
+// The evidence log is a PDA of the operation account, created when the dispute starts
+export function evidenceLogAddress(operationAccountPubkey: PublicKey): PublicKey {
+	const [address, _bump] = PublicKey.findProgramAddressSync(
+		[Buffer.from(EVIDENCE_LOG_SEED), operationAccountPubkey.toBuffer()], programId);
+	return address;
+}
+
+// Every arbiter has a single profile, kept across operations
+export function arbiterProfileAddress(arbiterPubkey: PublicKey): PublicKey {
+	const [address, _bump] = PublicKey.findProgramAddressSync(
+		[Buffer.from(ARBITER_PROFILE_SEED), arbiterPubkey.toBuffer()], programId);
+	return address;
+}
+
+// The stake an arbiter locks to be eligible, kept across operations
+export function arbiterStakeAddress(arbiterPubkey: PublicKey): PublicKey {
+	const [address, _bump] = PublicKey.findProgramAddressSync(
+		[Buffer.from(ARBITER_STAKE_SEED), arbiterPubkey.toBuffer()], programId);
+	return address;
+}
+
+// A single pool of arbiters for the program, used for random selection
+export function arbiterPoolAddress(): PublicKey {
+	const [address, _bump] = PublicKey.findProgramAddressSync(
+		[Buffer.from(ARBITER_POOL_SEED)], programId);
+	return address;
+}
+
+// The milestones are a PDA of the operation account, created when they are defined
+export function milestonesAddress(operationAccountPubkey: PublicKey): PublicKey {
+	const [address, _bump] = PublicKey.findProgramAddressSync(
+		[Buffer.from(MILESTONES_SEED), operationAccountPubkey.toBuffer()], programId);
+	return address;
+}
+
+// The line items are a PDA of the operation account, created when they are defined
+export function lineItemsAddress(operationAccountPubkey: PublicKey): PublicKey {
+	const [address, _bump] = PublicKey.findProgramAddressSync(
+		[Buffer.from(LINE_ITEMS_SEED), operationAccountPubkey.toBuffer()], programId);
+	return address;
+}
+
+// The offer log is a PDA of the operation account, created with the first offer
+export function offerLogAddress(operationAccountPubkey: PublicKey): PublicKey {
+	const [address, _bump] = PublicKey.findProgramAddressSync(
+		[Buffer.from(OFFER_LOG_SEED), operationAccountPubkey.toBuffer()], programId);
+	return address;
+}
+
+export async function joinArbiterPool(conn: Connection, arbiter: Keypair) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
+		  {pubkey: arbiterProfileAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERPROFILE
+		  {pubkey: arbiterStakeAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERSTAKE
+		  {pubkey: arbiterPoolAddress(), isSigner: false, isWritable: true}, // ARBITERPOOL
+		  {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		],
+	  data: createJoinArbiterPoolInstruction(),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [arbiter]);
+  
+	return sig;
+}
+
+export async function leaveArbiterPool(conn: Connection, arbiter: Keypair) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
+		  {pubkey: arbiterPoolAddress(), isSigner: false, isWritable: true}, // ARBITERPOOL
+		],
+	  data: createLeaveArbiterPoolInstruction(),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [arbiter]);
+  
+	return sig;
+}
+
+export async function stakeArbiter(conn: Connection, arbiter: Keypair, amount: bigint) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
+		  {pubkey: arbiterStakeAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERSTAKE
+		  {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		],
+	  data: createStakeArbiterInstruction(amount),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [arbiter]);
+  
+	return sig;
+}
+
+// First call requests the unstake, a second one after the cooldown withdraws
+// The withdrawal frees the seats held in the given operations, once finished or still waiting for the deposit
+export async function unstakeArbiter(conn: Connection, arbiter: Keypair,
+	operationAccountPubkeys: PublicKey[] = []) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
+		  {pubkey: arbiterStakeAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERSTAKE
+		  {pubkey: arbiterPoolAddress(), isSigner: false, isWritable: true}, // ARBITERPOOL
+		  ...operationAccountPubkeys.map((pubkey) => ({pubkey: pubkey, isSigner: false, isWritable: true})), // OPERATIONACCOUNT...
+		],
+	  data: createUnstakeArbiterInstruction(),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [arbiter]);
+  
+	return sig;
+}
+
+export async function registerArbiterProfile(conn: Connection, arbiter: Keypair, 
+	active: boolean, bio: ContentRef) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
+		  {pubkey: arbiterProfileAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERPROFILE
+		  {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		  // Only deactivating the profile leaves the pool
+		  ...(active ? [] : [{pubkey: arbiterPoolAddress(), isSigner: false, isWritable: true}]), // ARBITERPOOL
+		],
+	  data: createRegisterArbiterProfileInstruction(active, bio),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [arbiter]);
+  
+	return sig;
+}
+
+export async function initializeOperation(conn: Connection, seller: Keypair, itemContent: ContentRef,
+	arbiterSelection: ArbiterSelection = ArbiterSelection.Manual, milestones: MilestoneInput[] = [],
+	lineItems: LineItemInput[] = [], intendedBuyer?: PublicKey, attestor?: PublicKey, inspectionPeriod?: bigint): Promise<PublicKey> {  
 
  	let operationAccountPubkey = await createNewDataAccount(conn, seller, programId, AccountTypes.Operation);
 	console.log ("OPERATION ACCOUNT:" + operationAccountPubkey.toBase58());
@@ -21,17 +181,51 @@ export async function initializeOperation(conn: Connection, seller: Keypair, ipf
                 {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
                 {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
             ],
-          data: createInitializeOperationInstruction(BigInt(1000), TokenVersion.Sol, ipfsCID),
+          data: createInitializeOperationInstruction(BigInt(1000), TokenVersion.Sol, itemContent, arbiterSelection, intendedBuyer, attestor, inspectionPeriod),
           programId: programId,
         })
       );
 
+    // The total of the line items becomes the value, so they go before the milestones
+    if (lineItems.length > 0) {
+      tx.add(
+        new TransactionInstruction({
+          keys: [
+                {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+                {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+                {pubkey: lineItemsAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // LINEITEMS
+                {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM
+            ],
+          data: createDefineLineItemsInstruction(lineItems),
+          programId: programId,
+        })
+      );
+    }
+
+    // The milestones must add up to the value, and are defined in the same transaction
+    if (milestones.length > 0) {
+      tx.add(
+        new TransactionInstruction({
+          keys: [
+                {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+                {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+                {pubkey: milestonesAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // MILESTONES
+                {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM
+            ],
+          data: createDefineMilestonesInstruction(milestones),
+          programId: programId,
+        })
+      );
+    }
+
     let sig = await sendAndConfirmTransaction(conn, tx, [seller]);
 
     return operationAccountPubkey;
 }
 
-export async function registerBuyer(conn: Connection, buyer: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
+// The buyer registers on the terms reviewed, as read from the operation account
+export async function registerBuyer(conn: Connection, buyer: Keypair, operationAccountPubkey: PublicKey,
+	terms: OperationAccountData, assertRevision: boolean = true) :Promise<string> {  
 
    let tx = new Transaction();
    tx.add(
@@ -40,7 +234,8 @@ export async function registerBuyer(conn: Connection, buyer: Keypair, operationA
 			   {pubkey: buyer.publicKey, isSigner: true, isWritable: true}, // BUYER
 			   {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
 		   ],
-		 data: createRegisterBuyerInstruction(),
+		 data: createRegisterBuyerInstruction(terms.value, terms.tokenVersion, terms.itemContent,
+			assertRevision ? terms.listingRevision : undefined),
 		 programId: programId,
 	   })
 	 );
@@ -50,14 +245,153 @@ export async function registerBuyer(conn: Connection, buyer: Keypair, operationA
    return sig;
 }
 
+// Registers the buyer, approves the arbiters designated by the seller and deposits in a single instruction
+export async function registerBuyerAndDeposit(conn: Connection, buyer: Keypair, operationAccountPubkey: PublicKey,
+	terms: OperationAccountData, assertRevision: boolean = true) :Promise<string> {  
+
+   let tx = new Transaction();
+   tx.add(
+	   new TransactionInstruction({
+		 keys: [
+			   {pubkey: buyer.publicKey, isSigner: true, isWritable: true}, // BUYER
+			   {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+			   {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		   ],
+		 data: createRegisterBuyerAndDepositInstruction(terms.value, terms.tokenVersion, terms.itemContent,
+			[terms.arbiter1, terms.arbiter2, terms.arbiter3], assertRevision ? terms.listingRevision : undefined),
+		 programId: programId,
+	   })
+	 );
+
+   let sig = await sendAndConfirmTransaction(conn, tx, [buyer]);
+
+   return sig;
+}
+
+// The seller changes the price and item before a buyer registers, bumping the listing revision
+export async function updateListing(conn: Connection, seller: Keypair, 
+	value: bigint, itemContent: ContentRef, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+   let tx = new Transaction();
+   tx.add(
+     new TransactionInstruction({
+       keys: [
+           {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+           {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+       ],
+       data: createUpdateListingInstruction(value, itemContent),
+       programId: programId,
+     })
+   );
+
+   let sig = await sendAndConfirmTransaction(conn, tx, [seller]);
+
+   return sig;
+}
+
+// The seller replaces the registered buyer, dropping the arbiters picked so far
+export async function reassignBuyer(conn: Connection, seller: Keypair, 
+	buyer: PublicKey, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+   // The registered arbiters are dropped, and their stake unlocked
+   let operation: OperationAccountData = await getAccountData(conn, operationAccountPubkey, AccountTypes.Operation);
+   let registered = [operation.arbiter1, operation.arbiter2, operation.arbiter3].filter((arbiter) => !arbiter.equals(PublicKey.default));
+
+   let tx = new Transaction();
+   tx.add(
+     new TransactionInstruction({
+       keys: [
+           {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+           {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+           ...registered.map((arbiter) => (
+             {pubkey: arbiterStakeAddress(arbiter), isSigner: false, isWritable: true} // ARBITERSTAKE
+           )),
+       ],
+       data: createReassignBuyerInstruction(buyer),
+       programId: programId,
+     })
+   );
+
+   let sig = await sendAndConfirmTransaction(conn, tx, [seller]);
+
+   return sig;
+}
+
+// A prospective buyer offers a price instead of registering
+export async function proposePrice(conn: Connection, buyer: Keypair, 
+	amount: bigint, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+   let tx = new Transaction();
+   tx.add(
+     new TransactionInstruction({
+       keys: [
+           {pubkey: buyer.publicKey, isSigner: true, isWritable: true}, // BUYER
+           {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+           {pubkey: offerLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // OFFERLOG
+           {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM
+       ],
+       data: createProposePriceInstruction(amount),
+       programId: programId,
+     })
+   );
+
+   let sig = await sendAndConfirmTransaction(conn, tx, [buyer]);
+
+   return sig;
+}
+
+// The seller accepts an offer, registering its buyer at the offered price
+export async function acceptOffer(conn: Connection, seller: Keypair, 
+	index: number, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+   let tx = new Transaction();
+   tx.add(
+     new TransactionInstruction({
+       keys: [
+           {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+           {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+           {pubkey: offerLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // OFFERLOG
+       ],
+       data: createAcceptOfferInstruction(index),
+       programId: programId,
+     })
+   );
+
+   let sig = await sendAndConfirmTransaction(conn, tx, [seller]);
+
+   return sig;
+}
+
+export async function inviteArbiter(conn: Connection, participant: Keypair, 
+	arbiter: PublicKey, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+  let tx = new Transaction();
+  tx.add(
+    new TransactionInstruction({
+    keys: [
+        {pubkey: participant.publicKey, isSigner: true, isWritable: true}, // PARTICIPANT
+        {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+      ],
+    data: createInviteArbiterInstruction(arbiter),
+    programId: programId,
+    })
+  );
+
+  let sig = await sendAndConfirmTransaction(conn, tx, [participant]);
+
+  return sig;
+}
+
 export async function registerArbiter(conn: Connection, arbiter: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
 
   let tx = new Transaction();
   tx.add(
     new TransactionInstruction({
     keys: [
-        {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // BUYER
+        {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
         {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+        {pubkey: arbiterProfileAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERPROFILE
+        {pubkey: arbiterStakeAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERSTAKE
       ],
     data: createRegisterArbiterInstruction(),
     programId: programId,
@@ -107,6 +441,27 @@ export async function buyerApproves(conn: Connection, buyer: Keypair, operationA
   return sig;
 }
 
+export async function rejectArbiter(conn: Connection, participant: Keypair, 
+	arbiter: PublicKey, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+  let tx = new Transaction();
+  tx.add(
+    new TransactionInstruction({
+    keys: [
+        {pubkey: participant.publicKey, isSigner: true, isWritable: true}, // PARTICIPANT
+        {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+        {pubkey: arbiterStakeAddress(arbiter), isSigner: false, isWritable: true}, // ARBITERSTAKE
+      ],
+    data: createRejectArbiterInstruction(arbiter),
+    programId: programId,
+    })
+  );
+
+  let sig = await sendAndConfirmTransaction(conn, tx, [participant]);
+
+  return sig;
+}
+
 export async function buyerDeposit(conn: Connection, buyer: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
 
   let tx = new Transaction();
@@ -148,6 +503,125 @@ export async function buyerRelease(conn: Connection, buyer: Keypair,
 	return sig;
 }
 
+// The seller records the shipment with its tracking reference
+export async function markShipped(conn: Connection, seller: Keypair,
+	trackingContent: ContentRef, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		],
+	  data: createMarkShippedInstruction(trackingContent),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [seller]);
+  
+	return sig;
+}
+
+// The buyer confirms receiving the item, the token value stays in escrow until released
+export async function confirmReceived(conn: Connection, buyer: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: buyer.publicKey, isSigner: true, isWritable: true}, // BUYER
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		],
+	  data: createConfirmReceivedInstruction(),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [buyer]);
+  
+	return sig;
+}
+
+// Message signed by the attestor: operation address, delivered flag and time of delivery
+export function deliveryAttestationMessage(operationAccountPubkey: PublicKey, delivered: boolean, deliveredAt: bigint): Buffer {
+
+	let time = Buffer.alloc(8);
+	time.writeBigInt64LE(deliveredAt);
+
+	return Buffer.concat([operationAccountPubkey.toBuffer(), Buffer.from([delivered ? 1 : 0]), time]);
+}
+
+// Anyone submits the delivery signed by the attestor, verified by the Ed25519 program right before
+export async function attestDelivery(conn: Connection, payer: Keypair, attestor: Keypair,
+	deliveredAt: bigint, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  Ed25519Program.createInstructionWithPrivateKey({
+		privateKey: attestor.secretKey,
+		message: deliveryAttestationMessage(operationAccountPubkey, true, deliveredAt),
+	  }),
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: SYSVAR_INSTRUCTIONS_PUBKEY, isSigner: false, isWritable: false}, // INSTRUCTIONS
+		],
+	  data: createAttestDeliveryInstruction(true, deliveredAt),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [payer]);
+  
+	return sig;
+}
+
+// Anyone releases the token value to the seller once the inspection period after the delivery is over
+export async function finalizeRelease(conn: Connection, payer: Keypair,
+	seller: PublicKey, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: seller, isSigner: false, isWritable: true}, // SELLER
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		],
+	  data: createFinalizeReleaseInstruction(),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [payer]);
+  
+	return sig;
+}
+
+// Pays a single milestone to the seller, the rest stays in escrow
+export async function releaseMilestone(conn: Connection, buyer: Keypair,
+	seller: PublicKey, index: number, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: buyer.publicKey, isSigner: true, isWritable: true}, // BUYER
+		  {pubkey: seller, isSigner: false, isWritable: true}, // SELLER
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: milestonesAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // MILESTONES
+		],
+	  data: createReleaseMilestoneInstruction(index),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [buyer]);
+  
+	return sig;
+}
+
 export async function sellerRefund(conn: Connection, seller: Keypair,
 	buyer: PublicKey, operationAccountPubkey: PublicKey) :Promise<string> {  
 
@@ -169,7 +643,53 @@ export async function sellerRefund(conn: Connection, seller: Keypair,
 	return sig;
 }
 
-export async function startDispute(conn: Connection, participant: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
+// The buyer gets amount back, and the rest stays in escrow for the release
+export async function sellerPartialRefund(conn: Connection, seller: Keypair,
+	buyer: PublicKey, amount: bigint, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+		  {pubkey: buyer, isSigner: false, isWritable: true}, // BUYER
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		],
+	  data: createSellerPartialRefundInstruction(amount),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [seller]);
+  
+	return sig;
+}
+
+// The buyer gets back the units refunded of a single line item
+export async function refundLineItem(conn: Connection, seller: Keypair,
+	buyer: PublicKey, index: number, quantity: number, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
+		  {pubkey: buyer, isSigner: false, isWritable: true}, // BUYER
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: lineItemsAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // LINEITEMS
+		],
+	  data: createRefundLineItemInstruction(index, quantity),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [seller]);
+  
+	return sig;
+}
+
+export async function startDispute(conn: Connection, participant: Keypair, 
+	reason: ContentRef, operationAccountPubkey: PublicKey) :Promise<string> {  
 
 	let tx = new Transaction();
 	tx.add(
@@ -177,8 +697,12 @@ export async function startDispute(conn: Connection, participant: Keypair, opera
 	  keys: [
 		  {pubkey: participant.publicKey, isSigner: true, isWritable: true}, // BUYER
 		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: evidenceLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // EVIDENCELOG
+		  {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		  {pubkey: arbiterPoolAddress(), isSigner: false, isWritable: true}, // ARBITERPOOL
+		  {pubkey: SYSVAR_SLOT_HASHES_PUBKEY, isSigner: false, isWritable: false}, // SLOTHASHES
 		],
-	  data: createStartDisputeInstruction(),
+	  data: createStartDisputeInstruction(reason),
 	  programId: programId,
 	  })
 	);
@@ -189,7 +713,7 @@ export async function startDispute(conn: Connection, participant: Keypair, opera
 }
 
 export async function sellerAddInfo(conn: Connection, seller: Keypair, 
-	ipfsCID: string, operationAccountPubkey: PublicKey): Promise<string> {  
+	contentExt: ContentRef, operationAccountPubkey: PublicKey): Promise<string> {  
 
    let tx = new Transaction();
    tx.add(
@@ -197,8 +721,10 @@ export async function sellerAddInfo(conn: Connection, seller: Keypair,
 		 keys: [
 			   {pubkey: seller.publicKey, isSigner: true, isWritable: true}, // SELLER
 			   {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+			   {pubkey: evidenceLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // EVIDENCELOG
+			   {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
 		   ],
-		 data: createSellerAddInfoInstruction(ipfsCID),
+		 data: createSellerAddInfoInstruction(contentExt),
 		 programId: programId,
 	   })
 	 );
@@ -209,7 +735,7 @@ export async function sellerAddInfo(conn: Connection, seller: Keypair,
 }
 
 export async function buyerAddInfo(conn: Connection, buyer: Keypair, 
-	ipfsCID: string, operationAccountPubkey: PublicKey): Promise<string> {  
+	contentExt: ContentRef, operationAccountPubkey: PublicKey): Promise<string> {  
 
    let tx = new Transaction();
    tx.add(
@@ -217,8 +743,10 @@ export async function buyerAddInfo(conn: Connection, buyer: Keypair,
 		 keys: [
 			   {pubkey: buyer.publicKey, isSigner: true, isWritable: true}, // SELLER
 			   {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+			   {pubkey: evidenceLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // EVIDENCELOG
+			   {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
 		   ],
-		 data: createBuyerAddInfoInstruction(ipfsCID),
+		 data: createBuyerAddInfoInstruction(contentExt),
 		 programId: programId,
 	   })
 	 );
@@ -228,6 +756,28 @@ export async function buyerAddInfo(conn: Connection, buyer: Keypair,
    return sig;
 }
 
+export async function arbiterAddInfo(conn: Connection, arbiter: Keypair, 
+	contentExt: ContentRef, operationAccountPubkey: PublicKey): Promise<string> {  
+
+   let tx = new Transaction();
+   tx.add(
+	   new TransactionInstruction({
+		 keys: [
+			   {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
+			   {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+			   {pubkey: evidenceLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // EVIDENCELOG
+			   {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		   ],
+		 data: createArbiterAddInfoInstruction(contentExt),
+		 programId: programId,
+	   })
+	 );
+
+   let sig = await sendAndConfirmTransaction(conn, tx, [arbiter]);
+
+   return sig;
+}
+
 export async function arbiterVote(conn: Connection, arbiter: Keypair, 
 	vote: boolean, operationAccountPubkey: PublicKey): Promise<string> {  
 
@@ -235,8 +785,10 @@ export async function arbiterVote(conn: Connection, arbiter: Keypair,
    tx.add(
 	   new TransactionInstruction({
 		 keys: [
-			   {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // SELLER
+			   {pubkey: arbiter.publicKey, isSigner: true, isWritable: true}, // ARBITER
 			   {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+			   {pubkey: arbiterProfileAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERPROFILE
+			   {pubkey: arbiterStakeAddress(arbiter.publicKey), isSigner: false, isWritable: true}, // ARBITERSTAKE
 		   ],
 		 data: createArbiterVoteInstruction(vote),
 		 programId: programId,
@@ -248,6 +800,117 @@ export async function arbiterVote(conn: Connection, arbiter: Keypair,
    return sig;
 }
 
+export async function replaceArbiters(conn: Connection, participant: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	// The arbiters who did not vote get a missed vote on their profile, and their stake slashed
+	// During an appeal, only the appeal panel is replaced
+	let operation: OperationAccountData = await getAccountData(conn, operationAccountPubkey, AccountTypes.Operation);
+	let panel = operation.status == OperationStatus.InAppeal ?
+		operation.appealArbiters.map((arbiter, index) => [arbiter, operation.appealVotes[index]]) : [
+		[operation.arbiter1, operation.arbiterVote1],
+		[operation.arbiter2, operation.arbiterVote2],
+		[operation.arbiter3, operation.arbiterVote3],
+	];
+	let missing = panel.filter(([arbiter, vote]) => !(arbiter as PublicKey).equals(PublicKey.default) && vote == VotingOptions.NoVote);
+	let counterparty = participant.publicKey.equals(operation.seller) ? operation.buyer : operation.seller;
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: participant.publicKey, isSigner: true, isWritable: true}, // PARTICIPANT
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: counterparty, isSigner: false, isWritable: true}, // COUNTERPARTY
+		  ...missing.map(([arbiter, _vote]) => (
+			{pubkey: arbiterProfileAddress(arbiter as PublicKey), isSigner: false, isWritable: true} // ARBITERPROFILE
+		  )),
+		  ...missing.map(([arbiter, _vote]) => (
+			{pubkey: arbiterStakeAddress(arbiter as PublicKey), isSigner: false, isWritable: true} // ARBITERSTAKE
+		  )),
+		  {pubkey: arbiterPoolAddress(), isSigner: false, isWritable: true}, // ARBITERPOOL
+		  {pubkey: SYSVAR_SLOT_HASHES_PUBKEY, isSigner: false, isWritable: false}, // SLOTHASHES
+		],
+	  data: createReplaceArbitersInstruction(),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [participant]);
+  
+	return sig;
+}
+
+// The bond is transferred from the appellant, and the grounds are appended to the evidence log
+export async function appealVerdict(conn: Connection, appellant: Keypair, 
+	grounds: ContentRef, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: appellant.publicKey, isSigner: true, isWritable: true}, // APPELLANT
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: evidenceLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // EVIDENCELOG
+		  {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		  {pubkey: arbiterPoolAddress(), isSigner: false, isWritable: true}, // ARBITERPOOL
+		  {pubkey: SYSVAR_SLOT_HASHES_PUBKEY, isSigner: false, isWritable: false}, // SLOTHASHES
+		],
+	  data: createAppealVerdictInstruction(grounds),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [appellant]);
+  
+	return sig;
+}
+
+// The buyer gets buyerAmount of the token value, and the seller the rest
+export async function proposeSettlement(conn: Connection, participant: Keypair, 
+	buyerAmount: bigint, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: participant.publicKey, isSigner: true, isWritable: true}, // PARTICIPANT
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		],
+	  data: createProposeSettlementInstruction(buyerAmount),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [participant]);
+  
+	return sig;
+}
+
+// Accepts the proposal of the other party, which is paid out right away.
+// Fails if the proposal no longer gives the buyer buyerAmount.
+export async function acceptSettlement(conn: Connection, participant: Keypair, 
+	buyerAmount: bigint, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let operation: OperationAccountData = await getAccountData(conn, operationAccountPubkey, AccountTypes.Operation);
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: participant.publicKey, isSigner: true, isWritable: true}, // PARTICIPANT
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: operation.settlementProposer, isSigner: false, isWritable: true}, // PROPOSER
+		],
+	  data: createAcceptSettlementInstruction(buyerAmount),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [participant]);
+  
+	return sig;
+}
+
 export async function participantClaim(conn: Connection, participant: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
 
 	let tx = new Transaction();
@@ -264,5 +927,26 @@ export async function participantClaim(conn: Connection, participant: Keypair, o
   
 	let sig = await sendAndConfirmTransaction(conn, tx, [participant]);
   
+	return sig;
+}
+
+export async function migrateOperation(conn: Connection, payer: Keypair, operationAccountPubkey: PublicKey) :Promise<string> {  
+
+	let tx = new Transaction();
+	tx.add(
+	  new TransactionInstruction({
+	  keys: [
+		  {pubkey: payer.publicKey, isSigner: true, isWritable: true}, // PAYER
+		  {pubkey: operationAccountPubkey, isSigner: false, isWritable: true}, // OPERATIONACCOUNT
+		  {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}, // SYSTEM PROGRAM
+		  {pubkey: evidenceLogAddress(operationAccountPubkey), isSigner: false, isWritable: true}, // EVIDENCELOG
+		],
+	  data: createMigrateOperationInstruction(),
+	  programId: programId,
+	  })
+	);
+  
+	let sig = await sendAndConfirmTransaction(conn, tx, [payer]);
+  
 	return sig;
 }
\ No newline at end of file
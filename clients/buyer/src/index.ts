@@ -9,7 +9,7 @@ import * as SCA from "./sca";
 
 import * as fs from 'fs';
 import * as path from 'path';
-import { AccountTypes, OperationAccountData, getAccountData } from "./type";
+import { AccountTypes, EvidenceLogData, MilestonesData, OfferLogData, OperationAccountData, contentRefToString, getAccountData, ipfsContent } from "./type";
 import { download, upload } from "./pinata"
 
 const connection = new Connection(process.env.URL,"finalized");
@@ -20,15 +20,15 @@ buyer = Util.readKey("buyer");
 async function assignBuyer() {
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
-	let arr = new Uint8Array(operationAccountInfo.ipfsCid);
-	let ipfsStr = Buffer.from(arr.buffer).toString();
+	let ipfsStr = contentRefToString(operationAccountInfo.itemContent);
 
 	// Prints to console the IPFS stored data for verification
 	await download(ipfsStr);
 
 	console.log("Using buyer " + buyer.publicKey.toBase58());
 
-	let result = await SCA.registerBuyer(connection, buyer, opInfo);
+	// Registration fails if the seller changes the terms after they were reviewed here
+	let result = await SCA.registerBuyer(connection, buyer, opInfo, operationAccountInfo);
 	console.log(JSON.stringify(result));
 
 	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
@@ -36,6 +36,65 @@ async function assignBuyer() {
 
 }
 
+// Registers, approves the arbiters designated by the seller and deposits at once, for pre-agreed deals
+async function preAgreedDeal() {
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+	let ipfsStr = contentRefToString(operationAccountInfo.itemContent);
+
+	// Prints to console the IPFS stored data for verification
+	await download(ipfsStr);
+
+	console.log("Using buyer " + buyer.publicKey.toBase58());
+
+	// Fails if the seller changes the terms or the arbiters after they were reviewed here
+	let result = await SCA.registerBuyerAndDeposit(connection, buyer, opInfo, operationAccountInfo);
+	console.log(JSON.stringify(result));
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function proposePrice(amount: string) {
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+
+	// Prints to console the IPFS stored data for verification
+	await download(contentRefToString(operationAccountInfo.itemContent));
+
+	console.log("Using buyer " + buyer.publicKey.toBase58());
+
+	let result = await SCA.proposePrice(connection, buyer, BigInt(amount), opInfo);
+	console.log("Buyer offers " + JSON.stringify(result));
+
+	let offerLogInfo = await getAccountData(connection, SCA.offerLogAddress(opInfo), AccountTypes.OfferLog) as OfferLogData
+    console.log(Util.toJSONString(offerLogInfo));
+}
+
+async function inviteArbiter(arbiter: string) {
+	let result = await SCA.inviteArbiter(connection, buyer, new PublicKey(arbiter), opInfo);
+	console.log("Buyer invites arbiter " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function rejectArbiter(arbiter: string) {
+	let result = await SCA.rejectArbiter(connection, buyer, new PublicKey(arbiter), opInfo);
+	console.log("Buyer rejects arbiter " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function replaceArbiters() {
+	let result = await SCA.replaceArbiters(connection, buyer, opInfo);
+	console.log("Buyer replaces arbiters " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
 async function approveArbiters() {	
     let result = await SCA.buyerApproves(connection, buyer, opInfo);
 	console.log("Buyer approves " + JSON.stringify(result));
@@ -55,6 +114,30 @@ async function buyerPays() {
 	console.log("Buyer balance " + lamports.toString());
 }
 
+async function releaseMilestone(index: string) {
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+
+	let result = await SCA.releaseMilestone(connection, buyer, operationAccountInfo.seller, parseInt(index), opInfo);
+	console.log("Buyer releases milestone " + JSON.stringify(result));
+
+	let milestonesInfo = await getAccountData(connection, SCA.milestonesAddress(opInfo), AccountTypes.Milestones) as MilestonesData
+    console.log(Util.toJSONString(milestonesInfo));
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+
+	let lamports = await connection.getBalance(buyer.publicKey);
+	console.log("Buyer balance " + lamports.toString());
+}
+
+async function confirmReceived() {
+	let result = await SCA.confirmReceived(connection, buyer, opInfo);
+	console.log("Buyer received " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
 async function buyerClose() {
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
@@ -70,7 +153,13 @@ async function buyerClose() {
 }
 
 async function buyerDispute() {
-	let result = await SCA.startDispute(connection, buyer, opInfo);
+    let obj = {
+        complaint: "Item not as described",
+        desc: "bla bla bla",
+    };
+    let reasonCID = await upload(obj);
+
+	let result = await SCA.startDispute(connection, buyer, ipfsContent(reasonCID), opInfo);
 	console.log("Buyer dispute " + JSON.stringify(result));
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
@@ -86,11 +175,47 @@ async function buyerInfo() {
     };
     let ipfsCID = await upload(obj);
 
-	let result = await SCA.buyerAddInfo(connection, buyer, ipfsCID, opInfo);
+	let result = await SCA.buyerAddInfo(connection, buyer, ipfsContent(ipfsCID), opInfo);
 	console.log("Buyer added info " + JSON.stringify(result));
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
+
+	let evidenceLog = await getAccountData(connection, SCA.evidenceLogAddress(opInfo), AccountTypes.EvidenceLog) as EvidenceLogData
+    console.log(Util.toJSONString(evidenceLog));
+}
+
+async function buyerAppeal() {
+    let obj = {
+        grounds: "The arbiters ignored the evidence",
+        desc: "bla bla bla",
+    };
+    let groundsCID = await upload(obj);
+
+	let result = await SCA.appealVerdict(connection, buyer, ipfsContent(groundsCID), opInfo);
+	console.log("Buyer appeal " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function proposeSettlement(buyerAmount: string) {
+	let result = await SCA.proposeSettlement(connection, buyer, BigInt(buyerAmount), opInfo);
+	console.log("Buyer proposes settlement " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function acceptSettlement(buyerAmount: string) {
+	let result = await SCA.acceptSettlement(connection, buyer, BigInt(buyerAmount), opInfo);
+	console.log("Buyer accepts settlement " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+
+	let lamports = await connection.getBalance(buyer.publicKey);
+	console.log("Buyer balance " + lamports.toString());
 }
 
 async function claim() {
@@ -107,11 +232,21 @@ async function claim() {
 async function requestInput() {
 	console.log("Available functions:");
 	console.log("register -> Register yourself as buyer in an operation.");
+	console.log("offer <lamports> -> Offer a price instead of registering, the seller registers you if accepted.");
+	console.log("deal -> Register, approve the arbiters designated by the seller and deposit at once.");
 	console.log("approve -> Seller/Buyer approves of registered arbiters.");
+	console.log("invite <arbiter pubkey> -> Seller/Buyer invites an arbiter, only invited arbiters can register.");
+	console.log("reject <arbiter pubkey> -> Seller/Buyer rejects an invited or registered arbiter before approving.");
+	console.log("replace -> Seller/Buyer replaces the arbiters who did not vote in time.");
 	console.log("deposit -> Buyer deposits token amount.");
+	console.log("received -> Buyer confirms receiving the item, the token amount stays in escrow.");
 	console.log("release -> Buyer releases the token amount.");
+	console.log("milestone <index> -> Buyer releases a single milestone, starting at 0.");
 	console.log("dispute -> Seller/Buyer initiates a dispute.");
 	console.log("info -> Upload additional info for a dispute.");
+	console.log("appeal -> The losing Seller/Buyer appeals the verdict, posting a bond.");
+	console.log("settle <buyer lamports> -> Seller/Buyer proposes a settlement, the buyer gets the given lamports and the seller the rest.");
+	console.log("accept <buyer lamports> -> Seller/Buyer accepts the settlement proposed by the other party.");
 	console.log("claim -> Seller/Buyer claims the tokens.");
 	console.log("[Any key] -> Quit tool");
 
@@ -126,7 +261,9 @@ async function requestInput() {
 		console.log(task);
 		rl.close();
 
-		switch (task) {
+		let [command, arg] = task.trim().split(/\s+/);
+
+		switch (command) {
 			case "register": 
 				try {await assignBuyer();}
 				catch(err) {console.error(err)} 
@@ -134,6 +271,41 @@ async function requestInput() {
 				await requestInput();
 				break;
 
+			case "deal": 
+				try {await preAgreedDeal();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "offer": 
+				try {await proposePrice(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "invite": 
+				try {await inviteArbiter(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "reject": 
+				try {await rejectArbiter(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "replace": 
+				try {await replaceArbiters();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "approve": 
 				try {await approveArbiters();}
 				catch(err) {console.error(err)} 
@@ -148,6 +320,13 @@ async function requestInput() {
 				await requestInput();
 				break;
 
+			case "received": 
+				try {await confirmReceived();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "release": 
 				try {await buyerClose();}
 				catch(err) {console.error(err)} 
@@ -155,6 +334,13 @@ async function requestInput() {
 				await requestInput();
 				break;
 
+			case "milestone": 
+				try {await releaseMilestone(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "dispute": 
 				try {await buyerDispute();}
 				catch(err) {console.error(err)} 
@@ -169,6 +355,27 @@ async function requestInput() {
 				await requestInput();
 				break;
 
+			case "appeal": 
+				try {await buyerAppeal();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "settle": 
+				try {await proposeSettlement(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "accept": 
+				try {await acceptSettlement(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "claim": 
 				try {await claim();}
 				catch(err) {console.error(err)} 
@@ -18,10 +18,29 @@ export const enum TokenVersion {
 export const enum OperationStatus {
 	Closed = 0,
 	Opened = 1,
+	SellerClaim = 9,
+	BuyerClaim = 10,
+	DisputeResolved = 11,
+	InAppeal = 12,
+	Settled = 13,
+	Shipped = 14,
+	Delivered = 15,
 };
 
 export const enum AccountTypes {
 	Operation = 0,
+	EvidenceLog = 1,
+	ArbiterProfile = 2,
+	ArbiterStake = 3,
+	ArbiterPool = 4,
+	Milestones = 5,
+	LineItems = 6,
+	OfferLog = 7,
+}
+
+export const enum ArbiterSelection {
+	Manual = 0,
+	Random = 1,
 }
 
 export const enum Participant {
@@ -35,48 +54,516 @@ export const enum VotingOptions {
 	Seller = 2,
 }
 
+export const enum EvidenceRole {
+	Seller = 0,
+	Buyer = 1,
+	Arbiter = 2,
+}
+
+export const EVIDENCE_LOG_SEED = "evidence";
+
+export const ARBITER_PROFILE_SEED = "arbiter";
+
+export const ARBITER_STAKE_SEED = "stake";
+
+export const ARBITER_POOL_SEED = "pool";
+
+export const MILESTONES_SEED = "milestones";
+
+export const MAX_MILESTONES = 10;
+
+export const LINE_ITEMS_SEED = "items";
+
+export const MAX_LINE_ITEMS = 10;
+
+export const OFFER_LOG_SEED = "offers";
+
+// Lamports an arbiter needs staked to register, 1 SOL
+export const MIN_ARBITER_STAKE = BigInt(1000000000);
+
 export const PREFIX = "saftsca";
 
+export const MAX_CONTENT_REF_LEN = 128;
+
+export const MAX_ARBITER_INVITES = 6;
+
+export const APPEAL_PANEL_SIZE = 5;
+
+const BASE58_ALPHABET = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BASE32_ALPHABET = "abcdefghijklmnopqrstuvwxyz234567";
+
+export const enum ContentKind {
+	None = 0,
+	Ipfs = 1,
+	Arweave = 2,
+	Sha256 = 3,
+};
+
+// Reference to off-chain content: IPFS CID string, Arweave transaction id, or SHA-256 digest of the content at a URI.
+export type ContentRef =
+	{ kind: ContentKind.Ipfs, cid: string } |
+	{ kind: ContentKind.Arweave, txId: string } |
+	{ kind: ContentKind.Sha256, digest: Buffer, uri: string };
+
+export interface ContentRefData {
+	kind: ContentKind;
+	len: number;
+	data: number[];
+}
+
+export const ContentRefLayout = (property: string) => struct<ContentRefData>([
+	u8('kind'),
+	u8('len'),
+	seq(u8(), MAX_CONTENT_REF_LEN, 'data'),
+], property);
+
+export function ipfsContent(cid: string): ContentRef {
+	return { kind: ContentKind.Ipfs, cid: cid };
+}
+
+// Content reference as the program expects it: kind, length and payload
+export function encodeContentRef(content: ContentRef): Buffer {
+	let payload: Buffer;
+
+	switch (content.kind) {
+		case ContentKind.Ipfs:
+			payload = cidToBytes(content.cid);
+			break;
+		case ContentKind.Arweave:
+			payload = Buffer.from(content.txId, "base64url");
+			break;
+		case ContentKind.Sha256:
+			payload = Buffer.concat([content.digest, Buffer.from(content.uri, "ascii")]);
+			break;
+	}
+
+	return Buffer.concat([Buffer.from([content.kind, payload.length]), payload]);
+}
+
+// Content reference read from an account, encoded back as the program expects it
+export function encodeContentRefData(content: ContentRefData): Buffer {
+	return Buffer.concat([Buffer.from([content.kind, content.len]), Buffer.from(content.data.slice(0, content.len))]);
+}
+
+// Content reference stored by the program, as a CID, Arweave transaction id, or "uri#sha256:digest"
+export function contentRefToString(content: ContentRefData): string {
+	let payload = Buffer.from(content.data.slice(0, content.len));
+
+	switch (content.kind) {
+		case ContentKind.Ipfs:
+			return cidToString([...payload]);
+		case ContentKind.Arweave:
+			return payload.toString("base64url");
+		case ContentKind.Sha256:
+			return payload.subarray(32).toString("ascii") + "#sha256:" + payload.subarray(0, 32).toString("hex");
+		default:
+			return "";
+	}
+}
+
+// Converts a "Qm..." (CIDv0, base58btc) or "b..." (CIDv1, base32) string to the binary CID the program expects.
+export function cidToBytes(cid: string): Buffer {
+	if (cid.startsWith("Qm")) {
+		let value = BigInt(0);
+		for (const char of cid) {
+			value = value * BigInt(58) + BigInt(BASE58_ALPHABET.indexOf(char));
+		}
+		let hex = value.toString(16);
+		return Buffer.from(hex.length % 2 ? "0" + hex : hex, "hex");
+	}
+
+	if (cid.startsWith("b")) {
+		let bits = 0, buffer = 0, out: number[] = [];
+		for (const char of cid.slice(1)) {
+			buffer = (buffer << 5) | BASE32_ALPHABET.indexOf(char);
+			bits += 5;
+			if (bits >= 8) {
+				bits -= 8;
+				out.push((buffer >> bits) & 0xff);
+			}
+		}
+		return Buffer.from(out);
+	}
+
+	throw 'Error: unsupported CID encoding ' + cid;
+}
+
+// Converts a binary CID stored by the program back to its usual string form.
+export function cidToString(bytes: number[]): string {
+
+	if (bytes.length == 34 && bytes[0] == 0x12) {
+		let value = BigInt("0x" + Buffer.from(bytes).toString("hex"));
+		let str = "";
+		while (value > 0) {
+			str = BASE58_ALPHABET[Number(value % BigInt(58))] + str;
+			value = value / BigInt(58);
+		}
+		return str;
+	}
+
+	let bits = 0, buffer = 0, str = "b";
+	for (const byte of bytes) {
+		buffer = (buffer << 8) | byte;
+		bits += 8;
+		while (bits >= 5) {
+			bits -= 5;
+			str += BASE32_ALPHABET[(buffer >> bits) & 0x1f];
+		}
+		buffer &= (1 << bits) - 1;
+	}
+	if (bits > 0) {
+		str += BASE32_ALPHABET[(buffer << (5 - bits)) & 0x1f];
+	}
+	return str;
+}
+
 export interface OperationAccountData {
+	version: number;
 	status: OperationStatus;
 	createdAt: bigint;
 	tokenVersion: TokenVersion;
 	value: bigint;
 	seller: PublicKey;
 	buyer: PublicKey;
-	ipfsCid: number[];
+	itemContent: ContentRefData;
 	arbiter1: PublicKey;
 	arbiter2: PublicKey;
 	arbiter3: PublicKey;
 	sellerApproved: boolean;
 	buyerApproved: boolean;
-	sellerIpfsExt: number[];
-	buyerIpfsExt: number[];
 	arbiterVote1: VotingOptions;
 	arbiterVote2: VotingOptions;
 	arbiterVote3: VotingOptions;
+	arbiterLocks: number;
+	evidenceDeadline: bigint;
+	disputeInitiator: PublicKey;
+	disputeStartedAt: bigint;
+	disputeReason: ContentRefData;
+	voteDeadline: bigint;
+	arbiterInvites: PublicKey[];
+	arbiterSelection: ArbiterSelection;
+	appealDeadline: bigint;
+	appellant: PublicKey;
+	appealBond: bigint;
+	appealArbiters: PublicKey[];
+	appealVotes: VotingOptions[];
+	settlementProposer: PublicKey;
+	settlementBuyerAmount: bigint;
+	milestoneCount: number;
+	lineItemCount: number;
+	intendedBuyer: PublicKey;
+	listingRevision: number;
+	listingUpdatedAt: bigint;
+	shippedAt: bigint;
+	trackingContent: ContentRefData;
+	deliveredAt: bigint;
+	attestor: PublicKey;
+	inspectionPeriod: bigint;
+	inspectionDeadline: bigint;
 }
 
 export const OperationAccountDataLayout = struct<OperationAccountData>([
+	u8('version'),
 	u8('status'),
 	u64('createdAt'),
 	u8('tokenVersion'),
 	u64('value'),
     publicKey('seller'),
     publicKey('buyer'),
-	seq(u8(), 46, 'ipfsCid'),
+	ContentRefLayout('itemContent'),
 	publicKey('arbiter1'),
 	publicKey('arbiter2'),
 	publicKey('arbiter3'),
 	bool('sellerApproved'),
 	bool('buyerApproved'),
-	seq(u8(), 46, 'sellerIpfsExt'),
-	seq(u8(), 46, 'buyerIpfsExt'),
 	u8('arbiterVote1'),
 	u8('arbiterVote2'),
 	u8('arbiterVote3'),
+	u8('arbiterLocks'),
+	u64('evidenceDeadline'),
+	publicKey('disputeInitiator'),
+	u64('disputeStartedAt'),
+	ContentRefLayout('disputeReason'),
+	u64('voteDeadline'),
+	seq(publicKey(), MAX_ARBITER_INVITES, 'arbiterInvites'),
+	u8('arbiterSelection'),
+	u64('appealDeadline'),
+	publicKey('appellant'),
+	u64('appealBond'),
+	seq(publicKey(), APPEAL_PANEL_SIZE, 'appealArbiters'),
+	seq(u8(), APPEAL_PANEL_SIZE, 'appealVotes'),
+	publicKey('settlementProposer'),
+	u64('settlementBuyerAmount'),
+	u8('milestoneCount'),
+	u8('lineItemCount'),
+	publicKey('intendedBuyer'),
+	u32('listingRevision'),
+	u64('listingUpdatedAt'),
+	u64('shippedAt'),
+	ContentRefLayout('trackingContent'),
+	u64('deliveredAt'),
+	publicKey('attestor'),
+	u64('inspectionPeriod'),
+	u64('inspectionDeadline'),
+]);
+
+export interface EvidenceEntryData {
+	submitter: PublicKey;
+	role: EvidenceRole;
+	submittedAt: bigint;
+	content: ContentRefData;
+}
+
+export const EvidenceEntryLayout = struct<EvidenceEntryData>([
+	publicKey('submitter'),
+	u8('role'),
+	u64('submittedAt'),
+	ContentRefLayout('content'),
+]);
+
+export interface EvidenceLogHeaderData {
+	version: number;
+	bump: number;
+	operation: PublicKey;
+	count: number;
+}
+
+export const EvidenceLogHeaderLayout = struct<EvidenceLogHeaderData>([
+	u8('version'),
+	u8('bump'),
+	publicKey('operation'),
+	u32('count'),
+]);
+
+// The log header, followed by every entry appended so far
+export interface EvidenceLogData extends EvidenceLogHeaderData {
+	entries: EvidenceEntryData[];
+}
+
+export function decodeEvidenceLog(data: Buffer): EvidenceLogData {
+	let header = EvidenceLogHeaderLayout.decode(data);
+	let entries: EvidenceEntryData[] = [];
+
+	for (let i = 0; i < header.count; i++) {
+		entries.push(EvidenceEntryLayout.decode(data, EvidenceLogHeaderLayout.span + i * EvidenceEntryLayout.span));
+	}
+
+	return { ...header, entries: entries };
+}
+
+export interface ArbiterProfileData {
+	version: number;
+	bump: number;
+	arbiter: PublicKey;
+	active: boolean;
+	bio: ContentRefData;
+	casesAccepted: number;
+	votesCast: number;
+	votesMissed: number;
+}
+
+export const ArbiterProfileLayout = struct<ArbiterProfileData>([
+	u8('version'),
+	u8('bump'),
+	publicKey('arbiter'),
+	bool('active'),
+	ContentRefLayout('bio'),
+	u32('casesAccepted'),
+	u32('votesCast'),
+	u32('votesMissed'),
 ]);
 
+export interface ArbiterStakeData {
+	version: number;
+	bump: number;
+	arbiter: PublicKey;
+	amount: bigint;
+	unstakeAt: bigint;
+	slashed: bigint;
+	activeCases: number;
+}
+
+export const ArbiterStakeLayout = struct<ArbiterStakeData>([
+	u8('version'),
+	u8('bump'),
+	publicKey('arbiter'),
+	u64('amount'),
+	u64('unstakeAt'),
+	u64('slashed'),
+	u32('activeCases'),
+]);
+
+export interface ArbiterPoolHeaderData {
+	version: number;
+	bump: number;
+	count: number;
+}
+
+export const ArbiterPoolHeaderLayout = struct<ArbiterPoolHeaderData>([
+	u8('version'),
+	u8('bump'),
+	u32('count'),
+]);
+
+// The pool header, followed by the arbiters currently in it
+export interface ArbiterPoolData extends ArbiterPoolHeaderData {
+	arbiters: PublicKey[];
+}
+
+export function decodeArbiterPool(data: Buffer): ArbiterPoolData {
+	let header = ArbiterPoolHeaderLayout.decode(data);
+	let arbiters: PublicKey[] = [];
+
+	for (let i = 0; i < header.count; i++) {
+		arbiters.push(publicKey().decode(data, ArbiterPoolHeaderLayout.span + i * 32));
+	}
+
+	return { ...header, arbiters: arbiters };
+}
+
+// A stage of the payment, as given to DefineMilestones
+export interface MilestoneInput {
+	amount: bigint;
+	content: ContentRef;
+}
+
+export interface MilestoneData {
+	amount: bigint;
+	released: boolean;
+	content: ContentRefData;
+}
+
+export const MilestoneLayout = struct<MilestoneData>([
+	u64('amount'),
+	bool('released'),
+	ContentRefLayout('content'),
+]);
+
+export interface MilestonesHeaderData {
+	version: number;
+	bump: number;
+	operation: PublicKey;
+	count: number;
+}
+
+export const MilestonesHeaderLayout = struct<MilestonesHeaderData>([
+	u8('version'),
+	u8('bump'),
+	publicKey('operation'),
+	u8('count'),
+]);
+
+// The milestones header, followed by every milestone
+export interface MilestonesData extends MilestonesHeaderData {
+	milestones: MilestoneData[];
+}
+
+export function decodeMilestones(data: Buffer): MilestonesData {
+	let header = MilestonesHeaderLayout.decode(data);
+	let milestones: MilestoneData[] = [];
+
+	for (let i = 0; i < header.count; i++) {
+		milestones.push(MilestoneLayout.decode(data, MilestonesHeaderLayout.span + i * MilestoneLayout.span));
+	}
+
+	return { ...header, milestones: milestones };
+}
+
+// A line of an order, as given to DefineLineItems
+export interface LineItemInput {
+	unitPrice: bigint;
+	quantity: number;
+	content: ContentRef;
+}
+
+export interface LineItemData {
+	unitPrice: bigint;
+	quantity: number;
+	refunded: number;
+	content: ContentRefData;
+}
+
+export const LineItemLayout = struct<LineItemData>([
+	u64('unitPrice'),
+	u32('quantity'),
+	u32('refunded'),
+	ContentRefLayout('content'),
+]);
+
+export interface LineItemsHeaderData {
+	version: number;
+	bump: number;
+	operation: PublicKey;
+	count: number;
+}
+
+export const LineItemsHeaderLayout = struct<LineItemsHeaderData>([
+	u8('version'),
+	u8('bump'),
+	publicKey('operation'),
+	u8('count'),
+]);
+
+// The line items header, followed by every line item
+export interface LineItemsData extends LineItemsHeaderData {
+	lineItems: LineItemData[];
+}
+
+export function decodeLineItems(data: Buffer): LineItemsData {
+	let header = LineItemsHeaderLayout.decode(data);
+	let lineItems: LineItemData[] = [];
+
+	for (let i = 0; i < header.count; i++) {
+		lineItems.push(LineItemLayout.decode(data, LineItemsHeaderLayout.span + i * LineItemLayout.span));
+	}
+
+	return { ...header, lineItems: lineItems };
+}
+
+export interface OfferData {
+	buyer: PublicKey;
+	amount: bigint;
+	offeredAt: bigint;
+	accepted: boolean;
+}
+
+export const OfferLayout = struct<OfferData>([
+	publicKey('buyer'),
+	u64('amount'),
+	u64('offeredAt'),
+	bool('accepted'),
+]);
+
+export interface OfferLogHeaderData {
+	version: number;
+	bump: number;
+	operation: PublicKey;
+	count: number;
+}
+
+export const OfferLogHeaderLayout = struct<OfferLogHeaderData>([
+	u8('version'),
+	u8('bump'),
+	publicKey('operation'),
+	u32('count'),
+]);
+
+// The offer log header, followed by every offer made so far
+export interface OfferLogData extends OfferLogHeaderData {
+	offers: OfferData[];
+}
+
+export function decodeOfferLog(data: Buffer): OfferLogData {
+	let header = OfferLogHeaderLayout.decode(data);
+	let offers: OfferData[] = [];
+
+	for (let i = 0; i < header.count; i++) {
+		offers.push(OfferLayout.decode(data, OfferLogHeaderLayout.span + i * OfferLayout.span));
+	}
+
+	return { ...header, offers: offers };
+}
+
 export async function getAccountData(connection: Connection, accountPubkey: PublicKey,
 	accountType: AccountTypes): Promise<any> {
 	const accountInfo = await connection.getAccountInfo(accountPubkey);
@@ -93,6 +580,27 @@ export async function getAccountData(connection: Connection, accountPubkey: Publ
 		case AccountTypes.Operation:
 			deserializedRes = OperationAccountDataLayout.decode(accountInfo.data);
 			break;
+		case AccountTypes.EvidenceLog:
+			deserializedRes = decodeEvidenceLog(accountInfo.data);
+			break;
+		case AccountTypes.ArbiterProfile:
+			deserializedRes = ArbiterProfileLayout.decode(accountInfo.data);
+			break;
+		case AccountTypes.ArbiterStake:
+			deserializedRes = ArbiterStakeLayout.decode(accountInfo.data);
+			break;
+		case AccountTypes.ArbiterPool:
+			deserializedRes = decodeArbiterPool(accountInfo.data);
+			break;
+		case AccountTypes.Milestones:
+			deserializedRes = decodeMilestones(accountInfo.data);
+			break;
+		case AccountTypes.LineItems:
+			deserializedRes = decodeLineItems(accountInfo.data);
+			break;
+		case AccountTypes.OfferLog:
+			deserializedRes = decodeOfferLog(accountInfo.data);
+			break;
 	}
 
 	console.log("Balance: " + lamports.toString());
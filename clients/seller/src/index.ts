@@ -9,7 +9,7 @@ import * as SCA from "./sca";
 
 import * as fs from 'fs';
 import * as path from 'path';
-import { AccountTypes, OperationAccountData, getAccountData } from "./type";
+import { AccountTypes, ArbiterSelection, EvidenceLogData, LineItemInput, LineItemsData, MilestoneInput, OfferLogData, OperationAccountData, contentRefToString, getAccountData, ipfsContent } from "./type";
 import { download, upload } from "./pinata"
 
 const connection = new Connection(process.env.URL,"finalized");
@@ -17,22 +17,22 @@ var seller: Keypair;
 var opInfo: PublicKey = new PublicKey(process.env.OPERATION);
 seller = Util.readKey("seller");
 
-async function initOp(ipfsCID:string):Promise<PublicKey> {
+async function initOp(ipfsCID:string, arbiterSelection: ArbiterSelection, milestones: MilestoneInput[],
+	lineItems: LineItemInput[] = [], intendedBuyer?: PublicKey, attestor?: PublicKey, inspectionPeriod?: bigint):Promise<PublicKey> {
     console.log("Using seller " + seller.publicKey.toBase58());
 
-    let result = await SCA.initializeOperation(connection, seller, ipfsCID);
+    let result = await SCA.initializeOperation(connection, seller, ipfsContent(ipfsCID), arbiterSelection, milestones, lineItems, intendedBuyer, attestor, inspectionPeriod);
     console.log(JSON.stringify(result));
 
     let operationAccountInfo = await getAccountData(connection, result, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
-	let arr = new Uint8Array(operationAccountInfo.ipfsCid);
-	let str = Buffer.from(arr.buffer).toString();
-	console.log("IPFS: " + str)
+	console.log("IPFS: " + contentRefToString(operationAccountInfo.itemContent))
 
 	return result;
 }
 
-async function recordItemInfo(): Promise<PublicKey> {
+async function recordItemInfo(arbiterSelection: ArbiterSelection, milestoneCount: number,
+	intendedBuyer?: PublicKey, attestor?: PublicKey, inspectionPeriod?: bigint): Promise<PublicKey> {
 
     let obj = {
         name: "Used Macbook Air 13",
@@ -41,10 +41,111 @@ async function recordItemInfo(): Promise<PublicKey> {
         token: "Sol",
     };
     let ipfsCID = await upload(obj);
-	let opInfo = await initOp(ipfsCID);
+
+	// Split the price in equal milestones, the last one takes the remainder
+	let milestones: MilestoneInput[] = [];
+	let price = BigInt(obj.price);
+	for (let i = 0; i < milestoneCount; i++) {
+		let amount = i < milestoneCount - 1 ? price / BigInt(milestoneCount) : price - price / BigInt(milestoneCount) * BigInt(milestoneCount - 1);
+		milestones.push({ amount: amount, content: ipfsContent(ipfsCID) });
+	}
+
+	let opInfo = await initOp(ipfsCID, arbiterSelection, milestones, [], intendedBuyer, attestor, inspectionPeriod);
+	return opInfo;
+}
+
+async function recordOrderInfo(arbiterSelection: ArbiterSelection): Promise<PublicKey> {
+
+    let items = [
+        { name: "Used Macbook Air 13", desc: "bla bla bla", price: "1000", quantity: 1 },
+        { name: "USB-C charger", desc: "bla bla bla", price: "100", quantity: 2 },
+    ];
+
+    let obj = {
+        name: "Macbook bundle",
+        desc: "bla bla bla",
+        token: "Sol",
+    };
+    let ipfsCID = await upload(obj);
+
+	// Every line item has its own description, the order total becomes the price
+	let lineItems: LineItemInput[] = [];
+	for (const item of items) {
+		let itemCID = await upload(item);
+		lineItems.push({ unitPrice: BigInt(item.price), quantity: item.quantity, content: ipfsContent(itemCID) });
+	}
+
+	let opInfo = await initOp(ipfsCID, arbiterSelection, [], lineItems);
+
+	let lineItemsInfo = await getAccountData(connection, SCA.lineItemsAddress(opInfo), AccountTypes.LineItems) as LineItemsData
+    console.log(Util.toJSONString(lineItemsInfo));
+
 	return opInfo;
 }
 
+async function updateListing(price: string) {
+
+    let obj = {
+        name: "Used Macbook Air 13",
+        desc: "bla bla bla",
+        price: price,
+        token: "Sol",
+    };
+    let ipfsCID = await upload(obj);
+
+	let result = await SCA.updateListing(connection, seller, BigInt(obj.price), ipfsContent(ipfsCID), opInfo);
+	console.log("Seller updates listing " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+	console.log("IPFS: " + contentRefToString(operationAccountInfo.itemContent))
+}
+
+async function reassignBuyer(buyer: string) {
+	let result = await SCA.reassignBuyer(connection, seller, new PublicKey(buyer), opInfo);
+	console.log("Seller reassigns buyer " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function listOffers() {
+	let offerLogInfo = await getAccountData(connection, SCA.offerLogAddress(opInfo), AccountTypes.OfferLog) as OfferLogData
+    console.log(Util.toJSONString(offerLogInfo));
+}
+
+async function acceptOffer(index: string) {
+	let result = await SCA.acceptOffer(connection, seller, parseInt(index), opInfo);
+	console.log("Seller accepts offer " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function inviteArbiter(arbiter: string) {
+	let result = await SCA.inviteArbiter(connection, seller, new PublicKey(arbiter), opInfo);
+	console.log("Seller invites arbiter " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function rejectArbiter(arbiter: string) {
+	let result = await SCA.rejectArbiter(connection, seller, new PublicKey(arbiter), opInfo);
+	console.log("Seller rejects arbiter " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function replaceArbiters() {
+	let result = await SCA.replaceArbiters(connection, seller, opInfo);
+	console.log("Seller replaces arbiters " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
 async function approveArbiters() {	
     let result = await SCA.sellerApproves(connection, seller, opInfo);
 	console.log("Seller approves " + JSON.stringify(result));
@@ -53,6 +154,29 @@ async function approveArbiters() {
     console.log(Util.toJSONString(operationAccountInfo));
 }
 
+async function markShipped() {
+    let obj = {
+        carrier: "Fedex",
+        tracking: "123456789012",
+    };
+    let ipfsCID = await upload(obj);
+
+	let result = await SCA.markShipped(connection, seller, ipfsContent(ipfsCID), opInfo);
+	console.log("Seller shipped " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+// Anyone can release the value once the inspection period is over, the seller pays for it here
+async function finalizeRelease() {
+	let result = await SCA.finalizeRelease(connection, seller, seller.publicKey, opInfo);
+	console.log("Seller finalizes release " + JSON.stringify(result));
+
+	let lamports = await connection.getBalance(seller.publicKey);
+	console.log("Seller balance " + lamports.toString());
+}
+
 async function sellerRefund() {
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
@@ -64,8 +188,37 @@ async function sellerRefund() {
     console.log(Util.toJSONString(operationAccountInfo));
 }
 
+async function refundLineItem(index: string, quantity: string) {
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+
+	let result = await SCA.refundLineItem(connection, seller, operationAccountInfo.buyer, parseInt(index), parseInt(quantity), opInfo);
+	console.log("Seller refunds line item " + JSON.stringify(result));
+
+	let lineItemsInfo = await getAccountData(connection, SCA.lineItemsAddress(opInfo), AccountTypes.LineItems) as LineItemsData
+    console.log(Util.toJSONString(lineItemsInfo));
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function sellerPartialRefund(amount: string) {
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+
+	let result = await SCA.sellerPartialRefund(connection, seller, operationAccountInfo.buyer, BigInt(amount), opInfo);
+	console.log("Seller partial refund " + JSON.stringify(result));
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
 async function sellerDispute() {
-	let result = await SCA.startDispute(connection, seller, opInfo);
+    let obj = {
+        complaint: "Buyer received the item but did not release the deposit",
+        desc: "bla bla bla",
+    };
+    let reasonCID = await upload(obj);
+
+	let result = await SCA.startDispute(connection, seller, ipfsContent(reasonCID), opInfo);
 	console.log("Seller dispute " + JSON.stringify(result));
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
@@ -81,11 +234,47 @@ async function sellerInfo() {
     };
     let ipfsCID = await upload(obj);
 
-	let result = await SCA.sellerAddInfo(connection, seller, ipfsCID, opInfo);
+	let result = await SCA.sellerAddInfo(connection, seller, ipfsContent(ipfsCID), opInfo);
 	console.log("Seller added info " + JSON.stringify(result));
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
+
+	let evidenceLog = await getAccountData(connection, SCA.evidenceLogAddress(opInfo), AccountTypes.EvidenceLog) as EvidenceLogData
+    console.log(Util.toJSONString(evidenceLog));
+}
+
+async function sellerAppeal() {
+    let obj = {
+        grounds: "The arbiters ignored the evidence",
+        desc: "bla bla bla",
+    };
+    let groundsCID = await upload(obj);
+
+	let result = await SCA.appealVerdict(connection, seller, ipfsContent(groundsCID), opInfo);
+	console.log("Seller appeal " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function proposeSettlement(buyerAmount: string) {
+	let result = await SCA.proposeSettlement(connection, seller, BigInt(buyerAmount), opInfo);
+	console.log("Seller proposes settlement " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function acceptSettlement(buyerAmount: string) {
+	let result = await SCA.acceptSettlement(connection, seller, BigInt(buyerAmount), opInfo);
+	console.log("Seller accepts settlement " + JSON.stringify(result));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+
+	let lamports = await connection.getBalance(seller.publicKey);
+	console.log("Seller balance " + lamports.toString());
 }
 
 async function claim() {
@@ -101,11 +290,29 @@ async function claim() {
 
 async function requestInput() {
 	console.log("Available functions:");
-	console.log("init -> Upload JSON to IPFS and initializes an operation.");
+	console.log("init [random] [milestones] -> Upload JSON to IPFS and initializes an operation. With random, arbiters are picked from the pool if a dispute starts. With a number of milestones, the price is released in that many stages.");
+	console.log("reserve <buyer pubkey> -> Upload JSON to IPFS and initializes an operation only the given buyer can register to.");
+	console.log("attested <attestor pubkey> -> Upload JSON to IPFS and initializes an operation whose delivery the given courier or oracle can attest.");
+	console.log("inspected <seconds> [attestor pubkey] -> Upload JSON to IPFS and initializes an operation whose value anyone can release once the buyer had the given seconds to dispute the delivery.");
+	console.log("update <lamports> -> Upload JSON to IPFS and updates the price and item before a buyer registers.");
+	console.log("reassign <buyer pubkey> -> Seller reopens the operation for another buyer to register before the deposit, dropping the arbiters invited so far.");
+	console.log("order [random] -> Upload the JSON of each item to IPFS and initializes an operation for the whole order. With random, arbiters are picked from the pool if a dispute starts.");
+	console.log("offers -> List the price offers of prospective buyers.");
+	console.log("acceptoffer <index> -> Seller accepts an offer, registering its buyer at the offered price.");
 	console.log("approve -> Seller/Buyer approves of registered arbiters.");
+	console.log("invite <arbiter pubkey> -> Seller/Buyer invites an arbiter, only invited arbiters can register.");
+	console.log("reject <arbiter pubkey> -> Seller/Buyer rejects an invited or registered arbiter before approving.");
+	console.log("replace -> Seller/Buyer replaces the arbiters who did not vote in time.");
+	console.log("ship -> Upload the tracking JSON to IPFS and records the shipment of the item.");
+	console.log("finalize -> Releases the deposit to the seller once the inspection period after the delivery is over.");
 	console.log("refund -> Seller cancels the op and issues buyer a refund.");
+	console.log("partial <lamports> -> Seller refunds part of the deposit, the rest stays in escrow for the release.");
+	console.log("refunditem <index> <units> -> Seller refunds units of a line item of the order, starting at 0.");
 	console.log("dispute -> Seller/Buyer initiates a dispute.");
 	console.log("info -> Upload additional info for a dispute.");
+	console.log("appeal -> The losing Seller/Buyer appeals the verdict, posting a bond.");
+	console.log("settle <buyer lamports> -> Seller/Buyer proposes a settlement, the buyer gets the given lamports and the seller the rest.");
+	console.log("accept <buyer lamports> -> Seller/Buyer accepts the settlement proposed by the other party.");
 	console.log("claim -> Seller/Buyer claims the tokens.");
 	console.log("[Any key] -> Quit tool");
 
@@ -120,9 +327,89 @@ async function requestInput() {
 		console.log(task);
 		rl.close();
 
-		switch (task) {
+		let [command, arg, arg2] = task.trim().split(/\s+/);
+
+		switch (command) {
 			case "init": 
-				try {opInfo = await recordItemInfo();}
+				try {opInfo = await recordItemInfo(arg == "random" ? ArbiterSelection.Random : ArbiterSelection.Manual,
+					parseInt(arg == "random" ? arg2 : arg) || 0);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "reserve": 
+				try {opInfo = await recordItemInfo(ArbiterSelection.Manual, 0, new PublicKey(arg));}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "attested": 
+				try {opInfo = await recordItemInfo(ArbiterSelection.Manual, 0, undefined, new PublicKey(arg));}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "inspected": 
+				try {opInfo = await recordItemInfo(ArbiterSelection.Manual, 0, undefined, arg2 ? new PublicKey(arg2) : undefined, BigInt(arg));}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "update": 
+				try {await updateListing(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "reassign": 
+				try {await reassignBuyer(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "order": 
+				try {opInfo = await recordOrderInfo(arg == "random" ? ArbiterSelection.Random : ArbiterSelection.Manual);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "offers": 
+				try {await listOffers();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "acceptoffer": 
+				try {await acceptOffer(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "invite": 
+				try {await inviteArbiter(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "reject": 
+				try {await rejectArbiter(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "replace": 
+				try {await replaceArbiters();}
 				catch(err) {console.error(err)} 
 
 				await requestInput();
@@ -135,6 +422,20 @@ async function requestInput() {
 				await requestInput();
 				break;
 
+			case "ship": 
+				try {await markShipped();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "finalize": 
+				try {await finalizeRelease();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "refund": 
 				try {await sellerRefund();}
 				catch(err) {console.error(err)} 
@@ -142,6 +443,20 @@ async function requestInput() {
 				await requestInput();
 				break;
 
+			case "partial": 
+				try {await sellerPartialRefund(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "refunditem": 
+				try {await refundLineItem(arg, arg2);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "dispute": 
 				try {await sellerDispute();}
 				catch(err) {console.error(err)} 
@@ -156,6 +471,27 @@ async function requestInput() {
 				await requestInput();
 				break;
 
+			case "appeal": 
+				try {await sellerAppeal();}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "settle": 
+				try {await proposeSettlement(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
+			case "accept": 
+				try {await acceptSettlement(arg);}
+				catch(err) {console.error(err)} 
+
+				await requestInput();
+				break;
+
 			case "claim": 
 				try {await claim();}
 				catch(err) {console.error(err)} 
@@ -1,4 +1,4 @@
 cd program/sca
-cargo build-bpf
+cargo build-bpf --features short-periods
 cd ../..
 solana program deploy --keypair ./keys/programowner.json --url localhost ./program/sca/target/deploy/sca.so
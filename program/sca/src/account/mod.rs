@@ -1,17 +1,25 @@
-//!	# account
-//!	This crate is tasked with verifying the validity of accounts.
+//! # account
+//! This crate is tasked with verifying the validity of accounts.
 //!
 //!  
 
 use solana_program::{
 	pubkey::Pubkey,
 	account_info::AccountInfo,
+	entrypoint::ProgramResult,
     msg,
     program_error::ProgramError,
+	system_instruction::{allocate, assign, transfer},
+	system_program::check_id,
+	program::{invoke, invoke_signed},
+	sysvar::{
+		Sysvar,
+		rent::Rent,
+	}
 };
 
 use crate::scatype::{
-	ZERO_ACCOUNT
+	ZERO_ACCOUNT, SCAError,
 };
 
 /// Checks that the given account is both writable and owned by the program. 
@@ -67,3 +75,47 @@ pub fn is_signed_by_writable_account(account: &AccountInfo, message: &str) -> Re
 	}
 
 }
+
+/// Creates a rent exempt PDA of the program, of the given size and zeroed.
+///
+/// The address might have been funded beforehand by anyone, so only the missing rent is transferred
+/// and the account is allocated and assigned, instead of using create_account.
+pub fn create_pda_account<'a>(program_id: &Pubkey, payer: &AccountInfo<'a>, pda: &AccountInfo<'a>,
+	system_program: &AccountInfo<'a>, len: usize, signer_seeds: &[&[u8]]) -> ProgramResult {
+
+	if !check_id(system_program.key) {
+		msg!("Invalid System program");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if *pda.owner == *program_id || !pda.data_is_empty() {
+		msg!("Account already exists.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	let rent_exemption_balance = match Rent::get() {
+		Err(_e) => return Err(ProgramError::Custom(SCAError::RentError as u32)),
+		Ok(rent) => rent.minimum_balance(len),
+	};
+
+	let rent_shortfall = rent_exemption_balance.saturating_sub(pda.lamports());
+
+	if rent_shortfall > 0 {
+		invoke(
+			&transfer(payer.key, pda.key, rent_shortfall),
+			&[payer.clone(), pda.clone(), system_program.clone()],
+		)?;
+	}
+
+	invoke_signed(
+		&allocate(pda.key, len as u64),
+		&[pda.clone(), system_program.clone()],
+		&[signer_seeds],
+	)?;
+
+	invoke_signed(
+		&assign(pda.key, program_id),
+		&[pda.clone(), system_program.clone()],
+		&[signer_seeds],
+	)
+}
@@ -0,0 +1,416 @@
+//! # appeal
+//! Functionality related to appealing the verdict of a dispute
+//!
+//! List of supported instructions
+//!
+//! 1. Appeal Verdict -> [appeal_verdict]
+//!
+//! The losing party can appeal during the [crate::scatype::APPEAL_PERIOD] that follows the verdict, posting a bond.
+//! A larger panel, picked from the arbiter pool, votes again and its verdict overrides the first one.
+//! Appeal arbiters vote with [crate::dispute::arbiter_vote] and are replaced with [crate::dispute::replace_arbiters].
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg, system_instruction::transfer,
+    program_error::ProgramError,
+    pubkey::Pubkey, system_program::check_id,
+	program::invoke,
+	sysvar,
+};
+
+use crate::content::{ContentRef, ContentRefData};
+
+use crate::scatype::{
+	OperationAccount, OperationStatus, VotingOptions,
+	EvidenceEntry, EvidenceRole, SCAError,
+	APPEAL_BOND_PERCENT, APPEAL_PANEL_SIZE, APPEAL_SEAT, VOTING_PERIOD, ZERO_ACCOUNT,
+};
+
+use crate::evidence::append_evidence;
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+};
+
+use crate::arbiter::{
+	record_missed_vote,
+	release_seat,
+	slash_missed_vote,
+};
+
+use crate::pool::{
+	arbiter_pool_address,
+	linked_parties,
+	select_arbiters,
+};
+
+use crate::dispute::current_timestamp;
+
+/// Returns the bond the appellant posts for an operation of the given value.
+pub fn appeal_bond(value: u64) -> u64 {
+	(value as u128 * APPEAL_BOND_PERCENT as u128 / 100) as u64
+}
+
+/// Allows the losing Buyer/Seller to appeal the verdict, during the appeal period.
+/// The bond is transferred to the operation account, see [appeal_bond], and the appeal panel is picked
+/// from the pool, excluding the parties and the first panel. The grounds are appended to the evidence log.
+/// The appeal arbiters confirm their seat with [crate::operation::register_arbiter] before voting, which locks their stake.
+/// Note that this function expects a SellerClaim/BuyerClaim [OperationAccount] that was not appealed yet.
+///
+/// grounds: Content reference of the grounds for the appeal, conformant to [crate::instruction::OperationInstruction::AppealVerdict]
+///
+/// Accounts:
+/// 1. APPELLANT - Account of the losing seller/buyer, who pays the bond and the log growth.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. EVIDENCELOG - Evidence log of the operation, created by [crate::dispute::start_dispute].
+/// 4. SYSTEM PROGRAM - Used to transfer the bond and the additional rent.
+/// 5. ARBITERPOOL - Used to pick the appeal panel. Comformant to [crate::scatype::ArbiterPoolHeader]
+/// 6. SLOTHASHES - The SlotHashes sysvar.
+#[inline(never)]
+pub fn appeal_verdict(program_id: &Pubkey, accounts: &[AccountInfo],
+	grounds: ContentRef) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get APPELLANT account
+	let appellant_account_info = next_account_info(accounts_iter)?;
+
+	// Check APPELLANT account validity
+	is_signed_by_writable_account(appellant_account_info, "APPELLANT account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the EVIDENCELOG account, validated when appending
+	let evidence_log_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+	if !check_id(system_program_account_info.key) {
+		msg!("Invalid System program");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// Get the ARBITERPOOL and SLOTHASHES accounts, validated when selecting
+	let arbiter_pool_account_info = next_account_info(accounts_iter)?;
+	let slot_hashes_account_info = next_account_info(accounts_iter)?;
+
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	// The loser is the party who cannot claim.
+	let (loser, role) = match operation_account_data.status()? {
+		OperationStatus::SellerClaim => (operation_account_data.buyer, EvidenceRole::Buyer),
+		OperationStatus::BuyerClaim => (operation_account_data.seller, EvidenceRole::Seller),
+		_ => {
+			msg!("Operation account incorrect state.");
+			return Err(ProgramError::InvalidAccountData)
+		}
+	};
+
+	// CHECK: Was the verdict already appealed??? -> Reject
+	if operation_account_data.appellant.to_bytes() != ZERO_ACCOUNT {
+		msg!("Verdict already appealed.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// CHECK: Is the appeal window closed??? -> Reject
+	if unix_timestamp >= operation_account_data.appeal_deadline() {
+		msg!("Appeal period is over.");
+		return Err(ProgramError::Custom(SCAError::AppealPeriodOver as u32))
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if *appellant_account_info.key != loser {
+		msg!("Only the losing party can appeal.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// The content reference was already validated when the instruction was unpacked
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	let bond = appeal_bond(operation_account_data.value());
+
+	// Nobody linked to the operation can be picked
+	let selected = select_arbiters(program_id, arbiter_pool_account_info, slot_hashes_account_info,
+		operation_account_info.key, &linked_parties(operation_account_data), APPEAL_PANEL_SIZE)?;
+
+	operation_account_data.appeal_arbiters.copy_from_slice(&selected);
+
+	for index in 0..APPEAL_PANEL_SIZE {
+		operation_account_data.set_appeal_vote(index, VotingOptions::NoVote);
+	}
+
+	operation_account_data.set_status(OperationStatus::InAppeal);
+	operation_account_data.set_appeal_deadline(0);
+	operation_account_data.set_appeal_bond(bond);
+	operation_account_data.set_vote_deadline(unix_timestamp.saturating_add(VOTING_PERIOD));
+
+	// Settlements proposed before the verdict no longer reflect the positions of the parties
+	operation_account_data.settlement_proposer = Default::default();
+	operation_account_data.set_settlement_buyer_amount(0);
+
+	// Set externally
+	operation_account_data.appellant = *appellant_account_info.key;
+
+	msg!("Appeal arbiters selected: {} {} {} {} {}", selected[0], selected[1], selected[2], selected[3], selected[4]);
+
+	// Release the account data before calling into the System program
+	drop(operation_account_bytes);
+
+	invoke(
+		&transfer(appellant_account_info.key, operation_account_info.key, bond),
+		&[appellant_account_info.clone(), operation_account_info.clone(), system_program_account_info.clone()],
+	)?;
+
+	let entry = EvidenceEntry::new(*appellant_account_info.key, role,
+		unix_timestamp, ContentRefData::from(&grounds));
+
+	append_evidence(program_id, operation_account_info.key, appellant_account_info,
+		evidence_log_account_info, system_program_account_info, &entry)?;
+
+	msg!("Verdict appealed.");
+
+	Ok(())
+}
+
+/// Records the vote of an appeal arbiter. Once the whole panel voted, its majority
+/// decides the claim, overriding the first verdict. There is no further appeal.
+pub(crate) fn record_appeal_vote(operation_account_data: &mut OperationAccount,
+	arbiter: &Pubkey, vote: bool) -> ProgramResult {
+
+	let index = match operation_account_data.appeal_arbiters.iter().position(|appeal_arbiter| appeal_arbiter == arbiter) {
+		Some(index) if arbiter.to_bytes() != ZERO_ACCOUNT => index,
+		_ => {
+			msg!("Invalid Arbiter account.");
+			return Err(ProgramError::InvalidAccountData)
+		}
+	};
+
+	if operation_account_data.appeal_vote(index)? != VotingOptions::NoVote {
+		msg!("Already voted.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	if !vote {
+		operation_account_data.set_appeal_vote(index, VotingOptions::Buyer);
+	} else {
+		operation_account_data.set_appeal_vote(index, VotingOptions::Seller);
+	}
+
+	let mut buyer_claim = 0;
+	let mut seller_claim = 0;
+
+	// Vote count
+	for index in 0..APPEAL_PANEL_SIZE {
+		match operation_account_data.appeal_vote(index)? {
+			VotingOptions::Buyer => buyer_claim += 1,
+			VotingOptions::Seller => seller_claim += 1,
+			VotingOptions::NoVote => {},
+		}
+	}
+
+	// All votes are mandatory
+	if buyer_claim + seller_claim == APPEAL_PANEL_SIZE {
+		if buyer_claim > seller_claim {
+			operation_account_data.set_status(OperationStatus::BuyerClaim);
+			msg!("Appeal decided, Buyer claim enabled.");
+		} else {
+			operation_account_data.set_status(OperationStatus::SellerClaim);
+			msg!("Appeal decided, Seller claims enabled.");
+		}
+	}
+
+	Ok(())
+}
+
+/// Frees the seats of the appeal arbiters who did not vote, with the same penalties as
+/// [crate::dispute::replace_arbiters], and fills them from the pool right away.
+/// Returns the number of arbiters replaced.
+pub(crate) fn replace_appeal_arbiters<'a>(program_id: &Pubkey, operation: &Pubkey,
+	operation_account_data: &mut OperationAccount, remaining_account_infos: &[AccountInfo<'a>],
+	participant_account_info: &AccountInfo<'a>, counterparty_account_info: &AccountInfo<'a>) -> Result<usize, ProgramError> {
+
+	let mut replaced_arbiters: Vec<Pubkey> = Vec::with_capacity(APPEAL_PANEL_SIZE);
+
+	for index in 0..APPEAL_PANEL_SIZE {
+		let arbiter = operation_account_data.appeal_arbiters[index];
+
+		if arbiter.to_bytes() != ZERO_ACCOUNT &&
+			operation_account_data.appeal_vote(index)? == VotingOptions::NoVote {
+			operation_account_data.appeal_arbiters[index] = Default::default();
+			release_seat(program_id, operation_account_data, APPEAL_SEAT + index, &arbiter, remaining_account_infos)?;
+			record_missed_vote(program_id, &arbiter, remaining_account_infos)?;
+			slash_missed_vote(program_id, &arbiter, remaining_account_infos,
+				participant_account_info, counterparty_account_info)?;
+			replaced_arbiters.push(arbiter);
+		}
+	}
+
+	if replaced_arbiters.is_empty() {
+		return Ok(0)
+	}
+
+	let (arbiter_pool_pubkey, _bump) = arbiter_pool_address(program_id);
+	let arbiter_pool_account_info = remaining_account_infos.iter()
+		.find(|account_info| *account_info.key == arbiter_pool_pubkey);
+	let slot_hashes_account_info = remaining_account_infos.iter()
+		.find(|account_info| sysvar::slot_hashes::check_id(account_info.key));
+
+	let (arbiter_pool_account_info, slot_hashes_account_info) =
+		match (arbiter_pool_account_info, slot_hashes_account_info) {
+		(Some(arbiter_pool_account_info), Some(slot_hashes_account_info)) =>
+			(arbiter_pool_account_info, slot_hashes_account_info),
+		_ => {
+			msg!("ARBITERPOOL and SLOTHASHES accounts are required to select the arbiters.");
+			return Err(ProgramError::NotEnoughAccountKeys)
+		}
+	};
+
+	// Nobody linked to the operation can be picked, including the arbiters just replaced
+	let mut excluded = linked_parties(operation_account_data);
+	excluded.extend_from_slice(&replaced_arbiters);
+
+	let mut selected = select_arbiters(program_id, arbiter_pool_account_info, slot_hashes_account_info,
+		operation, &excluded, replaced_arbiters.len())?.into_iter();
+
+	for appeal_arbiter in operation_account_data.appeal_arbiters.iter_mut() {
+		if appeal_arbiter.to_bytes() == ZERO_ACCOUNT {
+			*appeal_arbiter = selected.next().unwrap_or_default();
+		}
+	}
+
+	msg!("Appeal arbiters selected: {}", replaced_arbiters.len());
+
+	Ok(replaced_arbiters.len())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bytemuck::{bytes_of, Zeroable};
+
+	use crate::scatype::OPERATION_ACCOUNT_VERSION;
+
+	use crate::dispute::participant_claim;
+
+	const SELLER: Pubkey = Pubkey::new_from_array([1; 32]);
+	const BUYER: Pubkey = Pubkey::new_from_array([2; 32]);
+
+	fn appeal_arbiter(index: usize) -> Pubkey {
+		Pubkey::new_from_array([index as u8 + 100; 32])
+	}
+
+	fn appealed(value: u64) -> OperationAccount {
+		let mut operation_account_data = OperationAccount::zeroed();
+		operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+		operation_account_data.set_status(OperationStatus::InAppeal);
+		operation_account_data.set_value(value);
+		operation_account_data.set_appeal_bond(appeal_bond(value));
+		operation_account_data.seller = SELLER;
+		operation_account_data.buyer = BUYER;
+		operation_account_data.appellant = BUYER;
+
+		for index in 0..APPEAL_PANEL_SIZE {
+			operation_account_data.appeal_arbiters[index] = appeal_arbiter(index);
+			operation_account_data.set_appeal_vote(index, VotingOptions::NoVote);
+		}
+
+		operation_account_data
+	}
+
+	#[test]
+	fn bond_is_a_share_of_the_value_rounded_down() {
+		assert_eq!(APPEAL_BOND_PERCENT, 10);
+		assert_eq!(appeal_bond(1_000), 100);
+		assert_eq!(appeal_bond(19), 1);
+		assert_eq!(appeal_bond(9), 0);
+		assert_eq!(appeal_bond(0), 0);
+
+		// The intermediate product does not overflow
+		assert_eq!(appeal_bond(u64::MAX), u64::MAX / 10);
+	}
+
+	#[test]
+	fn panel_majority_decides_once_everyone_voted() {
+		let mut operation_account_data = appealed(1_000);
+
+		// Seller, buyer, seller, buyer, seller
+		for index in 0..APPEAL_PANEL_SIZE {
+			assert_eq!(operation_account_data.status(), Ok(OperationStatus::InAppeal));
+			assert_eq!(record_appeal_vote(&mut operation_account_data, &appeal_arbiter(index), index % 2 == 0), Ok(()));
+		}
+
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::SellerClaim));
+
+		let mut operation_account_data = appealed(1_000);
+
+		for index in 0..APPEAL_PANEL_SIZE {
+			record_appeal_vote(&mut operation_account_data, &appeal_arbiter(index), index == 0).unwrap();
+		}
+
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::BuyerClaim));
+	}
+
+	#[test]
+	fn rejects_repeated_votes_and_arbiters_off_the_panel() {
+		let mut operation_account_data = appealed(1_000);
+
+		record_appeal_vote(&mut operation_account_data, &appeal_arbiter(0), true).unwrap();
+		assert_eq!(record_appeal_vote(&mut operation_account_data, &appeal_arbiter(0), false),
+			Err(ProgramError::AccountAlreadyInitialized));
+
+		assert_eq!(record_appeal_vote(&mut operation_account_data, &SELLER, true), Err(ProgramError::InvalidAccountData));
+
+		// A freed seat does not let anyone vote
+		operation_account_data.appeal_arbiters[1] = Pubkey::default();
+		assert_eq!(record_appeal_vote(&mut operation_account_data, &Pubkey::default(), true), Err(ProgramError::InvalidAccountData));
+	}
+
+	#[test]
+	fn claimant_gets_the_value_and_the_bond() {
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let operation_key = Pubkey::new_from_array([9; 32]);
+
+		let mut operation_account_data = appealed(1_000);
+		operation_account_data.set_status(OperationStatus::SellerClaim);
+
+		let mut operation_data = bytes_of(&operation_account_data).to_vec();
+		let (mut operation_lamports, mut seller_lamports) = (5_000 + 1_000 + 100, 5_000);
+		let mut seller_data = vec![];
+
+		let seller_account_info = AccountInfo::new(&SELLER, true, true, &mut seller_lamports, &mut seller_data,
+			&system_program, false, 0);
+		let operation_account_info = AccountInfo::new(&operation_key, false, true, &mut operation_lamports, &mut operation_data,
+			&program_id, false, 0);
+
+		assert_eq!(participant_claim(&program_id, &[seller_account_info, operation_account_info]), Ok(()));
+
+		assert_eq!((operation_lamports, seller_lamports), (5_000, 5_000 + 1_000 + 100));
+		assert_eq!(OperationAccount::load(&operation_data).unwrap().status(), Ok(OperationStatus::DisputeResolved));
+	}
+}
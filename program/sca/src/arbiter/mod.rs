@@ -0,0 +1,818 @@
+//! # arbiter
+//! Functionality related to Arbiters, outside of any single operation
+//!
+//! List of supported instructions
+//!
+//! 1. Register Arbiter Profile -> [register_arbiter_profile]
+//! 2. Stake Arbiter -> [stake_arbiter]
+//! 3. Unstake Arbiter -> [unstake_arbiter]
+//!
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+	system_instruction::transfer,
+	program::invoke,
+};
+
+use crate::content::{ContentRef, ContentRefData};
+
+use crate::scatype::{
+	OperationAccount, OperationStatus, ArbiterProfile, ArbiterStake, SCAError,
+	ARBITER_PROFILE_VERSION, ARBITER_PROFILE_SEED, ARBITER_PROFILE_LEN,
+	ARBITER_STAKE_VERSION, ARBITER_STAKE_SEED, ARBITER_STAKE_LEN, UNSTAKE_COOLDOWN,
+};
+
+use crate::dispute::current_timestamp;
+
+use crate::pool::{arbiter_pool_address, remove_from_pool};
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+	create_pda_account,
+};
+
+/// Returns the address and bump seed of the profile of an arbiter.
+pub fn arbiter_profile_address(program_id: &Pubkey, arbiter: &Pubkey) -> (Pubkey, u8) {
+	Pubkey::find_program_address(&[ARBITER_PROFILE_SEED, arbiter.as_ref()], program_id)
+}
+
+/// Checks that the account is the existing profile of the arbiter, so that it can be updated.
+pub fn check_arbiter_profile(program_id: &Pubkey, arbiter: &Pubkey, arbiter_profile_account_info: &AccountInfo) -> ProgramResult {
+
+	is_owned_and_writable(program_id, arbiter_profile_account_info, "ARBITERPROFILE account is not a valid account.")?;
+
+	let mut arbiter_profile_bytes = arbiter_profile_account_info.try_borrow_mut_data()?;
+	let arbiter_profile_data = ArbiterProfile::load_mut(&mut arbiter_profile_bytes)?;
+
+	let arbiter_profile_pubkey = Pubkey::create_program_address(
+		&[ARBITER_PROFILE_SEED, arbiter.as_ref(), &[arbiter_profile_data.bump]], program_id)?;
+
+	if arbiter_profile_data.arbiter != *arbiter || arbiter_profile_pubkey != *arbiter_profile_account_info.key {
+		msg!("ARBITERPROFILE account does not belong to the arbiter.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	Ok(())
+}
+
+/// Returns the address and bump seed of the stake of an arbiter.
+pub fn arbiter_stake_address(program_id: &Pubkey, arbiter: &Pubkey) -> (Pubkey, u8) {
+	Pubkey::find_program_address(&[ARBITER_STAKE_SEED, arbiter.as_ref()], program_id)
+}
+
+/// Checks that the account is the existing stake of the arbiter.
+pub fn check_arbiter_stake(program_id: &Pubkey, arbiter: &Pubkey, arbiter_stake_account_info: &AccountInfo) -> ProgramResult {
+
+	is_owned_and_writable(program_id, arbiter_stake_account_info, "ARBITERSTAKE account is not a valid account.")?;
+
+	let mut arbiter_stake_bytes = arbiter_stake_account_info.try_borrow_mut_data()?;
+	let arbiter_stake_data = ArbiterStake::load_mut(&mut arbiter_stake_bytes)?;
+
+	let arbiter_stake_pubkey = Pubkey::create_program_address(
+		&[ARBITER_STAKE_SEED, arbiter.as_ref(), &[arbiter_stake_data.bump]], program_id)?;
+
+	if arbiter_stake_data.arbiter != *arbiter || arbiter_stake_pubkey != *arbiter_stake_account_info.key {
+		msg!("ARBITERSTAKE account does not belong to the arbiter.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	Ok(())
+}
+
+/// Slashes the stake of an arbiter, found among the given accounts, paying it half to each party.
+/// The arbiter also leaves the pool, also found among the given accounts, so that they are not picked again
+/// even if their stake is no longer eligible. Arbiters registered before stakes existed have none, so there is nothing to slash.
+pub fn slash_missed_vote(program_id: &Pubkey, arbiter: &Pubkey, account_infos: &[AccountInfo],
+	participant_account_info: &AccountInfo, counterparty_account_info: &AccountInfo) -> ProgramResult {
+
+	let (arbiter_pool_pubkey, _bump) = arbiter_pool_address(program_id);
+
+	let arbiter_pool_account_info = match account_infos.iter()
+		.find(|account_info| *account_info.key == arbiter_pool_pubkey) {
+		Some(account_info) => account_info,
+		None => {
+			msg!("Missing ARBITERPOOL account to remove a replaced arbiter.");
+			return Err(ProgramError::NotEnoughAccountKeys)
+		}
+	};
+
+	remove_from_pool(program_id, arbiter_pool_account_info, arbiter)?;
+
+	let (arbiter_stake_pubkey, _bump) = arbiter_stake_address(program_id, arbiter);
+
+	let arbiter_stake_account_info = match account_infos.iter()
+		.find(|account_info| *account_info.key == arbiter_stake_pubkey) {
+		Some(account_info) => account_info,
+		None => {
+			msg!("Missing ARBITERSTAKE account of a replaced arbiter.");
+			return Err(ProgramError::NotEnoughAccountKeys)
+		}
+	};
+
+	if *arbiter_stake_account_info.owner != *program_id {
+		return Ok(())
+	}
+
+	check_arbiter_stake(program_id, arbiter, arbiter_stake_account_info)?;
+
+	let slashed = ArbiterStake::load_mut(&mut arbiter_stake_account_info.try_borrow_mut_data()?)?.slash();
+	let participant_share = slashed / 2;
+
+	// The program owns the stake, so lamports can be moved directly
+	**arbiter_stake_account_info.try_borrow_mut_lamports()? -= slashed;
+	**participant_account_info.try_borrow_mut_lamports()? += participant_share;
+	**counterparty_account_info.try_borrow_mut_lamports()? += slashed - participant_share;
+
+	msg!("Arbiter stake slashed: {}", slashed);
+
+	Ok(())
+}
+
+/// Counts a missed vote on the profile of an arbiter, found among the given accounts.
+/// Arbiters registered before profiles existed have none, so there is nothing to count.
+pub fn record_missed_vote(program_id: &Pubkey, arbiter: &Pubkey, arbiter_profile_account_infos: &[AccountInfo]) -> ProgramResult {
+
+	let (arbiter_profile_pubkey, _bump) = arbiter_profile_address(program_id, arbiter);
+
+	let arbiter_profile_account_info = match arbiter_profile_account_infos.iter()
+		.find(|account_info| *account_info.key == arbiter_profile_pubkey) {
+		Some(account_info) => account_info,
+		None => {
+			msg!("Missing ARBITERPROFILE account of a replaced arbiter.");
+			return Err(ProgramError::NotEnoughAccountKeys)
+		}
+	};
+
+	if *arbiter_profile_account_info.owner != *program_id {
+		return Ok(())
+	}
+
+	check_arbiter_profile(program_id, arbiter, arbiter_profile_account_info)?;
+
+	ArbiterProfile::load_mut(&mut arbiter_profile_account_info.try_borrow_mut_data()?)?.add_vote_missed();
+
+	Ok(())
+}
+
+/// Unlocks the stake of the arbiter of a seat, found among the given accounts, if the seat was locked.
+/// See [OperationAccount::seat_locked].
+pub fn release_seat(program_id: &Pubkey, operation_account_data: &mut OperationAccount, seat: usize,
+	arbiter: &Pubkey, arbiter_stake_account_infos: &[AccountInfo]) -> ProgramResult {
+
+	if !operation_account_data.seat_locked(seat) {
+		return Ok(())
+	}
+
+	let (arbiter_stake_pubkey, _bump) = arbiter_stake_address(program_id, arbiter);
+
+	let arbiter_stake_account_info = match arbiter_stake_account_infos.iter()
+		.find(|account_info| *account_info.key == arbiter_stake_pubkey) {
+		Some(account_info) => account_info,
+		None => {
+			msg!("Missing ARBITERSTAKE account of an arbiter leaving a locked seat.");
+			return Err(ProgramError::NotEnoughAccountKeys)
+		}
+	};
+
+	check_arbiter_stake(program_id, arbiter, arbiter_stake_account_info)?;
+
+	ArbiterStake::load_mut(&mut arbiter_stake_account_info.try_borrow_mut_data()?)?.remove_active_case();
+	operation_account_data.set_seat_locked(seat, false);
+
+	Ok(())
+}
+
+/// Creates the profile of an arbiter, or updates its bio and active flag if it already exists.
+/// Deactivating the profile also removes the arbiter from the pool.
+///
+/// profile_data: A tuple conformant to [OperationInstruction::RegisterArbiterProfile]
+///
+/// Accounts:
+/// 1. ARBITER - Account of the arbiter, who also pays for this transaction and the profile.
+/// 2. ARBITERPROFILE - PDA of [ARBITER_PROFILE_SEED] and ARBITER. Comformant to [ArbiterProfile]
+/// 3. SYSTEM PROGRAM - Used to create the profile.
+/// 4. ARBITERPOOL - Only to deactivate the profile, as inactive arbiters leave the pool. See [crate::pool].
+#[inline(never)]
+pub fn register_arbiter_profile(program_id: &Pubkey, accounts: &[AccountInfo],
+	profile_data: (bool, ContentRef)) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get ARBITER account
+	let arbiter_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITER account validity
+	is_signed_by_writable_account(arbiter_account_info, "ARBITER account is not a valid account.")?;
+
+	// Get the ARBITERPROFILE account, validated below
+	let arbiter_profile_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+	// Get the optional ARBITERPOOL account, validated when leaving it
+	let arbiter_pool_account_info = next_account_info(accounts_iter).ok();
+
+	// ================ Enforce configuration rules section
+
+	let (active, bio) = profile_data;
+
+	if !active && arbiter_pool_account_info.is_none() {
+		msg!("ARBITERPOOL account is required to deactivate the profile.");
+		return Err(ProgramError::NotEnoughAccountKeys)
+	}
+
+	// ================ Enforce previous state section
+
+	if *arbiter_profile_account_info.owner == *program_id {
+		check_arbiter_profile(program_id, arbiter_account_info.key, arbiter_profile_account_info)?;
+	} else {
+		let (arbiter_profile_pubkey, bump) = arbiter_profile_address(program_id, arbiter_account_info.key);
+
+		if *arbiter_profile_account_info.key != arbiter_profile_pubkey || !arbiter_profile_account_info.is_writable {
+			msg!("ARBITERPROFILE account is not a valid account.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		create_pda_account(program_id, arbiter_account_info, arbiter_profile_account_info, system_program_account_info,
+			ARBITER_PROFILE_LEN, &[ARBITER_PROFILE_SEED, arbiter_account_info.key.as_ref(), &[bump]])?;
+
+		let mut arbiter_profile_bytes = arbiter_profile_account_info.try_borrow_mut_data()?;
+		arbiter_profile_bytes[0] = ARBITER_PROFILE_VERSION;
+
+		let arbiter_profile_data = ArbiterProfile::load_mut(&mut arbiter_profile_bytes)?;
+		arbiter_profile_data.bump = bump;
+		arbiter_profile_data.arbiter = *arbiter_account_info.key;
+
+		msg!("Arbiter profile created.");
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	// The content reference was already validated when the instruction was unpacked
+
+	// ========================= Change state section
+
+	let mut arbiter_profile_bytes = arbiter_profile_account_info.try_borrow_mut_data()?;
+	let arbiter_profile_data = ArbiterProfile::load_mut(&mut arbiter_profile_bytes)?;
+
+	// Set externally
+	arbiter_profile_data.set_active(active);
+	arbiter_profile_data.bio = ContentRefData::from(&bio);
+
+	if let (false, Some(arbiter_pool_account_info)) = (active, arbiter_pool_account_info) {
+		remove_from_pool(program_id, arbiter_pool_account_info, arbiter_account_info.key)?;
+	}
+
+	msg!("Arbiter profile saved.");
+
+	Ok(())
+}
+
+/// Adds lamports to the stake of an arbiter, creating it if needed.
+/// Staking again also cancels a pending unstake, see [unstake_arbiter].
+///
+/// amount: Lamports to stake, conformant to [OperationInstruction::StakeArbiter]
+///
+/// Accounts:
+/// 1. ARBITER - Account of the arbiter, who also pays for this transaction and the stake.
+/// 2. ARBITERSTAKE - PDA of [ARBITER_STAKE_SEED] and ARBITER. Comformant to [ArbiterStake]
+/// 3. SYSTEM PROGRAM - Used to create and fund the stake.
+#[inline(never)]
+pub fn stake_arbiter(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get ARBITER account
+	let arbiter_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITER account validity
+	is_signed_by_writable_account(arbiter_account_info, "ARBITER account is not a valid account.")?;
+
+	// Get the ARBITERSTAKE account, validated below
+	let arbiter_stake_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+	// ================ Enforce configuration rules section
+
+	if amount == 0 {
+		msg!("Nothing to stake.");
+		return Err(ProgramError::InvalidInstructionData)
+	}
+
+	// ================ Enforce previous state section
+
+	if *arbiter_stake_account_info.owner == *program_id {
+		check_arbiter_stake(program_id, arbiter_account_info.key, arbiter_stake_account_info)?;
+	} else {
+		let (arbiter_stake_pubkey, bump) = arbiter_stake_address(program_id, arbiter_account_info.key);
+
+		if *arbiter_stake_account_info.key != arbiter_stake_pubkey || !arbiter_stake_account_info.is_writable {
+			msg!("ARBITERSTAKE account is not a valid account.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		create_pda_account(program_id, arbiter_account_info, arbiter_stake_account_info, system_program_account_info,
+			ARBITER_STAKE_LEN, &[ARBITER_STAKE_SEED, arbiter_account_info.key.as_ref(), &[bump]])?;
+
+		let mut arbiter_stake_bytes = arbiter_stake_account_info.try_borrow_mut_data()?;
+		arbiter_stake_bytes[0] = ARBITER_STAKE_VERSION;
+
+		let arbiter_stake_data = ArbiterStake::load_mut(&mut arbiter_stake_bytes)?;
+		arbiter_stake_data.bump = bump;
+		arbiter_stake_data.arbiter = *arbiter_account_info.key;
+
+		msg!("Arbiter stake created.");
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if arbiter_account_info.lamports() < amount {
+		return Err(ProgramError::InsufficientFunds);
+	}
+
+	// ========================= Change state section
+
+	invoke(
+		&transfer(arbiter_account_info.key, arbiter_stake_account_info.key, amount),
+		&[arbiter_account_info.clone(), arbiter_stake_account_info.clone(), system_program_account_info.clone()],
+	)?;
+
+	let mut arbiter_stake_bytes = arbiter_stake_account_info.try_borrow_mut_data()?;
+	let arbiter_stake_data = ArbiterStake::load_mut(&mut arbiter_stake_bytes)?;
+
+	arbiter_stake_data.set_amount(arbiter_stake_data.amount().saturating_add(amount));
+	arbiter_stake_data.set_unstake_at(0);
+
+	msg!("Arbiter staked: {}", arbiter_stake_data.amount());
+
+	Ok(())
+}
+
+/// Withdraws the stake of an arbiter, in two steps.
+/// The first call requests the unstake, which makes the arbiter ineligible for new operations
+/// and removes them from the pool.
+/// Once [UNSTAKE_COOLDOWN] has passed, a second call returns the whole stake to the arbiter.
+/// The stake can still be slashed during the cooldown, and stays locked while the arbiter holds a case,
+/// see [ArbiterStake::active_cases]. Seats in finished operations, or in operations still waiting for the deposit,
+/// are freed by passing those operations to the second call.
+///
+/// Accounts:
+/// 1. ARBITER - Account of the arbiter, who also pays for this transaction.
+/// 2. ARBITERSTAKE - Stake of the arbiter. Comformant to [ArbiterStake]
+/// 3. ARBITERPOOL - The arbiter pool. See [crate::pool].
+/// 4. OPERATIONACCOUNT... - Optional, operations where the arbiter holds a locked seat. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn unstake_arbiter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get ARBITER account
+	let arbiter_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITER account validity
+	is_signed_by_writable_account(arbiter_account_info, "ARBITER account is not a valid account.")?;
+
+	// Get the ARBITERSTAKE account
+	let arbiter_stake_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITERSTAKE account validity
+	check_arbiter_stake(program_id, arbiter_account_info.key, arbiter_stake_account_info)?;
+
+	// Get the ARBITERPOOL account, validated when leaving it
+	let arbiter_pool_account_info = next_account_info(accounts_iter)?;
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut arbiter_stake_bytes = arbiter_stake_account_info.try_borrow_mut_data()?;
+	let arbiter_stake_data = ArbiterStake::load_mut(&mut arbiter_stake_bytes)?;
+
+	// ================ Enforce previous state section
+
+	if arbiter_stake_data.amount() == 0 {
+		msg!("Nothing to unstake.");
+		return Err(ProgramError::InsufficientFunds)
+	}
+
+	// ========================= Change state section
+
+	if arbiter_stake_data.unstake_at() == 0 {
+		arbiter_stake_data.set_unstake_at(unix_timestamp.saturating_add(UNSTAKE_COOLDOWN));
+		remove_from_pool(program_id, arbiter_pool_account_info, arbiter_account_info.key)?;
+
+		msg!("Unstake requested, available at {}", arbiter_stake_data.unstake_at());
+		return Ok(())
+	}
+
+	for operation_account_info in accounts_iter {
+		release_operation_seat(program_id, operation_account_info, arbiter_account_info.key, arbiter_stake_data)?;
+	}
+
+	// CHECK: Does the arbiter still hold a case??? -> Reject
+	if arbiter_stake_data.active_cases() != 0 {
+		msg!("Arbiter still holds {} cases.", arbiter_stake_data.active_cases());
+		return Err(ProgramError::Custom(SCAError::UnstakeCooldown as u32))
+	}
+
+	// CHECK: Is the cooldown still running??? -> Reject
+	if unix_timestamp < arbiter_stake_data.unstake_at() {
+		msg!("Unstake cooldown still running.");
+		return Err(ProgramError::Custom(SCAError::UnstakeCooldown as u32))
+	}
+
+	let amount = arbiter_stake_data.amount();
+
+	// The account keeps its rent, so that it can be staked again
+	arbiter_stake_data.set_amount(0);
+	arbiter_stake_data.set_unstake_at(0);
+
+	**arbiter_stake_account_info.try_borrow_mut_lamports()? -= amount;
+	**arbiter_account_info.try_borrow_mut_lamports()? += amount;
+
+	msg!("Arbiter unstaked: {}", amount);
+
+	Ok(())
+}
+
+/// Frees the locked seat of the arbiter in an operation that no longer needs them: either finished,
+/// or still waiting for the deposit, in which case the arbiter leaves the panel and the parties must approve it again.
+fn release_operation_seat(program_id: &Pubkey, operation_account_info: &AccountInfo,
+	arbiter: &Pubkey, arbiter_stake_data: &mut ArbiterStake) -> ProgramResult {
+
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	let seat = match operation_account_data.arbiter_seat(arbiter) {
+		Some(seat) if operation_account_data.seat_locked(seat) => seat,
+		_ => {
+			msg!("Arbiter holds no locked seat in the operation.");
+			return Err(ProgramError::InvalidAccountData)
+		}
+	};
+
+	let status = operation_account_data.status()?;
+
+	match status {
+		_ if status.is_finished() => {},
+		OperationStatus::Opened |
+		OperationStatus::BuyerRegistered |
+		OperationStatus::ArbitersRegistered |
+		OperationStatus::ArbitersApproved => {
+			operation_account_data.remove_invite(arbiter);
+
+			match seat {
+				0 => operation_account_data.arbiter1 = Default::default(),
+				1 => operation_account_data.arbiter2 = Default::default(),
+				_ => operation_account_data.arbiter3 = Default::default(),
+			}
+
+			// The panel changed, so it must be approved again
+			operation_account_data.set_seller_approved(false);
+			operation_account_data.set_buyer_approved(false);
+
+			if status != OperationStatus::Opened {
+				operation_account_data.set_status(OperationStatus::BuyerRegistered);
+			}
+		},
+		_ => {
+			msg!("Operation still needs the arbiter.");
+			return Err(ProgramError::InvalidAccountData)
+		}
+	}
+
+	operation_account_data.set_seat_locked(seat, false);
+	arbiter_stake_data.remove_active_case();
+
+	msg!("Arbiter seat released.");
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bytemuck::{bytes_of, Zeroable};
+
+	use crate::scatype::{ArbiterSelection, OPERATION_ACCOUNT_VERSION, MIN_ARBITER_STAKE, APPEAL_SEAT};
+
+	use crate::operation::register_arbiter;
+
+	use crate::content::ARWEAVE_TX_ID_LEN;
+
+	const ARBITER: Pubkey = Pubkey::new_from_array([7; 32]);
+	const OPERATION: Pubkey = Pubkey::new_from_array([9; 32]);
+	const RENT: u64 = 1_000;
+
+	fn stake(active_cases: u32) -> ArbiterStake {
+		let (_pubkey, bump) = arbiter_stake_address(&crate::id(), &ARBITER);
+
+		let mut arbiter_stake_data = ArbiterStake::zeroed();
+		arbiter_stake_data.version = ARBITER_STAKE_VERSION;
+		arbiter_stake_data.bump = bump;
+		arbiter_stake_data.arbiter = ARBITER;
+		arbiter_stake_data.set_amount(MIN_ARBITER_STAKE);
+
+		// Requested long ago, so the cooldown is over
+		arbiter_stake_data.set_unstake_at(-1);
+
+		for _case in 0..active_cases {
+			arbiter_stake_data.add_active_case();
+		}
+
+		arbiter_stake_data
+	}
+
+	fn operation(status: OperationStatus) -> OperationAccount {
+		let mut operation_account_data = OperationAccount::zeroed();
+		operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+		operation_account_data.set_status(status);
+		operation_account_data.set_seller_approved(true);
+		operation_account_data.set_buyer_approved(true);
+		operation_account_data.arbiter2 = ARBITER;
+		operation_account_data.set_seat_locked(1, true);
+		operation_account_data
+	}
+
+	/// Runs the withdrawal step of [unstake_arbiter], returning the result, the stake and the operation afterwards.
+	fn withdraw(arbiter_stake_data: ArbiterStake, operation_account_data: Option<OperationAccount>)
+		-> (ProgramResult, ArbiterStake, Option<OperationAccount>, u64) {
+
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let (stake_key, _bump) = arbiter_stake_address(&program_id, &ARBITER);
+		let (pool_key, _bump) = arbiter_pool_address(&program_id);
+
+		let mut stake_data = bytes_of(&arbiter_stake_data).to_vec();
+		let mut operation_data = operation_account_data.map(|data| bytes_of(&data).to_vec()).unwrap_or_default();
+		let (mut arbiter_lamports, mut stake_lamports, mut pool_lamports, mut operation_lamports) =
+			(0, RENT + MIN_ARBITER_STAKE, 0, RENT);
+		let (mut arbiter_data, mut pool_data) = (vec![], vec![]);
+
+		let mut accounts = vec![
+			AccountInfo::new(&ARBITER, true, true, &mut arbiter_lamports, &mut arbiter_data, &system_program, false, 0),
+			AccountInfo::new(&stake_key, false, true, &mut stake_lamports, &mut stake_data, &program_id, false, 0),
+			AccountInfo::new(&pool_key, false, true, &mut pool_lamports, &mut pool_data, &program_id, false, 0),
+		];
+
+		if operation_account_data.is_some() {
+			accounts.push(AccountInfo::new(&OPERATION, false, true, &mut operation_lamports, &mut operation_data,
+				&program_id, false, 0));
+		}
+
+		let result = unstake_arbiter(&program_id, &accounts);
+		drop(accounts);
+
+		let operation_account_data = operation_account_data.map(|_data| *OperationAccount::load(&operation_data).unwrap());
+
+		(result, *ArbiterStake::load_mut(&mut stake_data).unwrap(), operation_account_data, arbiter_lamports)
+	}
+
+	fn profile() -> ArbiterProfile {
+		let (_pubkey, bump) = arbiter_profile_address(&crate::id(), &ARBITER);
+
+		let mut arbiter_profile_data = ArbiterProfile::zeroed();
+		arbiter_profile_data.version = ARBITER_PROFILE_VERSION;
+		arbiter_profile_data.bump = bump;
+		arbiter_profile_data.arbiter = ARBITER;
+		arbiter_profile_data.set_active(true);
+		arbiter_profile_data
+	}
+
+	/// Runs [register_arbiter], returning the result, the stake and the operation afterwards.
+	fn register(arbiter_stake_data: ArbiterStake, operation_account_data: OperationAccount)
+		-> (ProgramResult, ArbiterStake, OperationAccount) {
+
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let (profile_key, _bump) = arbiter_profile_address(&program_id, &ARBITER);
+		let (stake_key, _bump) = arbiter_stake_address(&program_id, &ARBITER);
+
+		let mut profile_data = bytes_of(&profile()).to_vec();
+		let mut stake_data = bytes_of(&arbiter_stake_data).to_vec();
+		let mut operation_data = bytes_of(&operation_account_data).to_vec();
+		let (mut arbiter_lamports, mut profile_lamports, mut stake_lamports, mut operation_lamports) = (0, RENT, RENT, RENT);
+		let mut arbiter_data = vec![];
+
+		let result = register_arbiter(&program_id, &[
+			AccountInfo::new(&ARBITER, true, true, &mut arbiter_lamports, &mut arbiter_data, &system_program, false, 0),
+			AccountInfo::new(&OPERATION, false, true, &mut operation_lamports, &mut operation_data, &program_id, false, 0),
+			AccountInfo::new(&profile_key, false, true, &mut profile_lamports, &mut profile_data, &program_id, false, 0),
+			AccountInfo::new(&stake_key, false, true, &mut stake_lamports, &mut stake_data, &program_id, false, 0),
+		]);
+
+		(result, *ArbiterStake::load_mut(&mut stake_data).unwrap(), *OperationAccount::load(&operation_data).unwrap())
+	}
+
+	#[test]
+	fn pool_is_only_needed_to_deactivate() {
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let (profile_key, _bump) = arbiter_profile_address(&program_id, &ARBITER);
+		let (pool_key, _bump) = arbiter_pool_address(&program_id);
+
+		let mut profile_data = bytes_of(&profile()).to_vec();
+		let (mut arbiter_lamports, mut profile_lamports, mut system_lamports, mut pool_lamports) = (0, RENT, 0, 0);
+		let (mut arbiter_data, mut system_data, mut pool_data) = (vec![], vec![], vec![]);
+
+		let accounts = [
+			AccountInfo::new(&ARBITER, true, true, &mut arbiter_lamports, &mut arbiter_data, &system_program, false, 0),
+			AccountInfo::new(&profile_key, false, true, &mut profile_lamports, &mut profile_data, &program_id, false, 0),
+			AccountInfo::new(&system_program, false, false, &mut system_lamports, &mut system_data, &system_program, true, 0),
+			// The pool was never created, so there is nothing to leave
+			AccountInfo::new(&pool_key, false, true, &mut pool_lamports, &mut pool_data, &system_program, false, 0),
+		];
+
+		let bio = || ContentRef::Arweave([1; ARWEAVE_TX_ID_LEN]);
+
+		assert_eq!(register_arbiter_profile(&program_id, &accounts[..3], (true, bio())), Ok(()));
+		assert_eq!(register_arbiter_profile(&program_id, &accounts[..3], (false, bio())), Err(ProgramError::NotEnoughAccountKeys));
+		assert!(ArbiterProfile::load_mut(&mut accounts[1].try_borrow_mut_data().unwrap()).unwrap().active());
+
+		assert_eq!(register_arbiter_profile(&program_id, &accounts, (false, bio())), Ok(()));
+		assert!(!ArbiterProfile::load_mut(&mut accounts[1].try_borrow_mut_data().unwrap()).unwrap().active());
+	}
+
+	#[test]
+	fn registering_locks_the_stake() {
+		let mut arbiter_stake_data = stake(0);
+		arbiter_stake_data.set_unstake_at(0);
+
+		let mut operation_account_data = operation(OperationStatus::BuyerRegistered);
+		operation_account_data.arbiter2 = Pubkey::default();
+		operation_account_data.set_seat_locked(1, false);
+		operation_account_data.arbiter1 = Pubkey::new_from_array([8; 32]);
+		operation_account_data.add_invite(ARBITER).unwrap();
+
+		let (result, arbiter_stake_data, operation_account_data) = register(arbiter_stake_data, operation_account_data);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(operation_account_data.arbiter2, ARBITER);
+		assert!(operation_account_data.seat_locked(1) && !operation_account_data.seat_locked(0));
+		assert_eq!(arbiter_stake_data.active_cases(), 1);
+	}
+
+	#[test]
+	fn picked_arbiters_confirm_their_seat() {
+		// The pick predates the unstake request, which does not stop the confirmation
+		let mut operation_account_data = operation(OperationStatus::InDispute);
+		operation_account_data.set_arbiter_selection(ArbiterSelection::Random);
+		operation_account_data.set_seat_locked(1, false);
+
+		let (result, arbiter_stake_data, operation_account_data) = register(stake(0), operation_account_data);
+
+		assert_eq!(result, Ok(()));
+		assert!(operation_account_data.seat_locked(1));
+		assert_eq!(arbiter_stake_data.active_cases(), 1);
+
+		// Only once
+		let (result, arbiter_stake_data, _operation) = register(arbiter_stake_data, operation_account_data);
+
+		assert_eq!(result, Err(ProgramError::AccountAlreadyInitialized));
+		assert_eq!(arbiter_stake_data.active_cases(), 1);
+
+		// Nor before the dispute, nor without being picked
+		let mut operation_account_data = operation(OperationStatus::BuyerDeposited);
+		operation_account_data.set_arbiter_selection(ArbiterSelection::Random);
+		operation_account_data.set_seat_locked(1, false);
+
+		assert_eq!(register(stake(0), operation_account_data).0, Err(ProgramError::InvalidAccountData));
+
+		operation_account_data.set_status(OperationStatus::InVoting);
+		operation_account_data.arbiter2 = Pubkey::default();
+
+		assert_eq!(register(stake(0), operation_account_data).0, Err(ProgramError::InvalidAccountData));
+	}
+
+	#[test]
+	fn appeal_panels_confirm_their_seat() {
+		let mut operation_account_data = operation(OperationStatus::InAppeal);
+		operation_account_data.arbiter2 = Pubkey::default();
+		operation_account_data.set_seat_locked(1, false);
+		operation_account_data.appeal_arbiters[2] = ARBITER;
+
+		let (result, arbiter_stake_data, operation_account_data) = register(stake(0), operation_account_data);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(operation_account_data.arbiter_seat(&ARBITER), Some(APPEAL_SEAT + 2));
+		assert!(operation_account_data.seat_locked(APPEAL_SEAT + 2));
+		assert_eq!(arbiter_stake_data.active_cases(), 1);
+
+		// The first panel already ruled
+		let (result, arbiter_stake_data, _operation) = register(stake(0), operation(OperationStatus::InAppeal));
+
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+		assert_eq!(arbiter_stake_data.active_cases(), 0);
+	}
+
+	#[test]
+	fn withdrawal_waits_for_every_case() {
+		let (result, arbiter_stake_data, _operation, arbiter_lamports) = withdraw(stake(1), None);
+
+		assert_eq!(result, Err(ProgramError::Custom(SCAError::UnstakeCooldown as u32)));
+		assert_eq!((arbiter_stake_data.amount(), arbiter_stake_data.active_cases(), arbiter_lamports), (MIN_ARBITER_STAKE, 1, 0));
+
+		let (result, arbiter_stake_data, _operation, arbiter_lamports) = withdraw(stake(0), None);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!((arbiter_stake_data.amount(), arbiter_lamports), (0, MIN_ARBITER_STAKE));
+	}
+
+	#[test]
+	fn finished_operations_free_the_seat() {
+		let (result, arbiter_stake_data, operation_account_data, arbiter_lamports) =
+			withdraw(stake(1), Some(operation(OperationStatus::DisputeResolved)));
+
+		assert_eq!(result, Ok(()));
+		assert_eq!((arbiter_stake_data.active_cases(), arbiter_lamports), (0, MIN_ARBITER_STAKE));
+
+		// The finished operation keeps its panel, only the lock goes
+		let operation_account_data = operation_account_data.unwrap();
+		assert!(!operation_account_data.seat_locked(1));
+		assert_eq!(operation_account_data.arbiter2, ARBITER);
+
+		// The same seat cannot be released twice
+		let (result, arbiter_stake_data, _operation, _lamports) =
+			withdraw(stake(1), Some(operation_account_data));
+
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+		assert_eq!(arbiter_stake_data.active_cases(), 1);
+
+		// Settlements end the operation without a verdict
+		let (result, arbiter_stake_data, _operation, _lamports) =
+			withdraw(stake(1), Some(operation(OperationStatus::Settled)));
+
+		assert_eq!((result, arbiter_stake_data.active_cases()), (Ok(()), 0));
+	}
+
+	#[test]
+	fn arbiters_leave_operations_waiting_for_the_deposit() {
+		let (result, arbiter_stake_data, operation_account_data, _lamports) =
+			withdraw(stake(1), Some(operation(OperationStatus::ArbitersApproved)));
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(arbiter_stake_data.active_cases(), 0);
+
+		// The panel changed, so the parties must approve it again
+		let operation_account_data = operation_account_data.unwrap();
+		assert_eq!(operation_account_data.arbiter2, Pubkey::default());
+		assert!(!operation_account_data.seat_locked(1));
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::BuyerRegistered));
+		assert!(!operation_account_data.seller_approved() && !operation_account_data.buyer_approved());
+	}
+
+	#[test]
+	fn disputes_keep_the_stake_locked() {
+		for status in [OperationStatus::BuyerDeposited, OperationStatus::InDispute, OperationStatus::InVoting] {
+			let (result, arbiter_stake_data, operation_account_data, arbiter_lamports) =
+				withdraw(stake(1), Some(operation(status)));
+
+			assert_eq!(result, Err(ProgramError::InvalidAccountData));
+			assert_eq!((arbiter_stake_data.active_cases(), arbiter_lamports), (1, 0));
+			assert!(operation_account_data.unwrap().seat_locked(1));
+		}
+	}
+
+	#[test]
+	fn freed_seats_unlock_the_stake_once() {
+		let program_id = crate::id();
+		let (stake_key, _bump) = arbiter_stake_address(&program_id, &ARBITER);
+
+		let mut stake_data = bytes_of(&stake(1)).to_vec();
+		let mut stake_lamports = RENT + MIN_ARBITER_STAKE;
+		let stake_account_info = AccountInfo::new(&stake_key, false, true, &mut stake_lamports, &mut stake_data,
+			&program_id, false, 0);
+
+		let mut operation_account_data = operation(OperationStatus::InVoting);
+
+		assert_eq!(release_seat(&program_id, &mut operation_account_data, 1, &ARBITER, std::slice::from_ref(&stake_account_info)), Ok(()));
+		assert_eq!(release_seat(&program_id, &mut operation_account_data, 1, &ARBITER, std::slice::from_ref(&stake_account_info)), Ok(()));
+
+		assert!(!operation_account_data.seat_locked(1));
+		assert_eq!(ArbiterStake::load_mut(&mut stake_account_info.try_borrow_mut_data().unwrap()).unwrap().active_cases(), 0);
+
+		// A locked seat needs the stake of its arbiter
+		operation_account_data.set_seat_locked(1, true);
+		assert_eq!(release_seat(&program_id, &mut operation_account_data, 1, &ARBITER, &[]), Err(ProgramError::NotEnoughAccountKeys));
+	}
+}
@@ -0,0 +1,469 @@
+//! # content
+//! This crate is tasked with the validation of references to off-chain content
+//!
+//! The available types are:
+//! ContentRef -> [ContentRef]
+//! ContentRefData -> [ContentRefData]
+//! IpfsCid -> [IpfsCid]
+
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+	msg,
+	program_error::ProgramError,
+};
+
+use std::convert::{TryFrom, TryInto};
+
+/// Maximum size in bytes of a binary CID.
+pub const MAX_CID_LEN: usize = 64;
+
+/// Size in bytes of an Arweave transaction id (43 characters in base64url).
+pub const ARWEAVE_TX_ID_LEN: usize = 32;
+
+/// Size in bytes of a SHA-256 digest.
+pub const SHA256_DIGEST_LEN: usize = 32;
+
+/// Maximum size in bytes of the URI that goes with a SHA-256 digest.
+pub const MAX_URI_LEN: usize = 96;
+
+/// Maximum size in bytes of the payload of any [ContentRef] variant.
+pub const MAX_CONTENT_REF_LEN: usize = SHA256_DIGEST_LEN + MAX_URI_LEN;
+
+/// Multihash code of sha2-256, the only hash supported by CIDv0.
+const SHA2_256_CODE: u64 = 0x12;
+
+/// Size in bytes of a binary CIDv0, which is a bare sha2-256 multihash.
+const CIDV0_LEN: usize = 2 + SHA256_DIGEST_LEN;
+
+/// The off-chain storage backends supported by a [ContentRef], as stored in [ContentRefData].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ContentKind {
+	/// No content was provided.
+	None,
+	/// See [ContentRef::Ipfs].
+	Ipfs,
+	/// See [ContentRef::Arweave].
+	Arweave,
+	/// See [ContentRef::Sha256].
+	Sha256,
+}
+
+impl TryFrom<u8> for ContentKind {
+	type Error = ProgramError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		match value {
+			0 => Ok(ContentKind::None),
+			1 => Ok(ContentKind::Ipfs),
+			2 => Ok(ContentKind::Arweave),
+			3 => Ok(ContentKind::Sha256),
+			_ => Err(ProgramError::InvalidInstructionData),
+		}
+	}
+}
+
+/// A reference to content stored off-chain, validated according to its storage backend.
+///
+/// In instruction data it is encoded as 1 byte of [ContentKind], 1 byte of length,
+/// and that many bytes of payload.
+#[derive(PartialEq, Debug)]
+pub enum ContentRef {
+	/// IPFS content identifier, CIDv0 or CIDv1 in binary form.
+	Ipfs(IpfsCid),
+	/// Arweave transaction id, in binary form.
+	Arweave([u8; ARWEAVE_TX_ID_LEN]),
+	/// SHA-256 digest of the content, plus the URI where it can be fetched (https://, etc).
+	Sha256 {
+		digest: [u8; SHA256_DIGEST_LEN],
+		uri: Vec<u8>,
+	},
+}
+
+impl ContentRef {
+
+	/// Reads a [ContentRef] from instruction data, returning it and the remaining data.
+	pub fn unpack(data: &[u8]) -> Result<(Self, &[u8]), ProgramError> {
+
+		if data.len() < 2 {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		let kind = ContentKind::try_from(data[0])?;
+		let len = data[1] as usize;
+
+		if data.len() < 2 + len {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		let (payload, rest) = data[2..].split_at(len);
+
+		let content_ref = match kind {
+			ContentKind::Ipfs => ContentRef::Ipfs(IpfsCid::new(payload)?),
+			ContentKind::Arweave => {
+				let tx_id: [u8; ARWEAVE_TX_ID_LEN] = match payload.try_into() {
+					Err(_e) => {
+						msg!("Invalid Arweave transaction id.");
+						return Err(ProgramError::InvalidArgument);
+					},
+					Ok(b) => b,
+				};
+
+				if tx_id.iter().all(|byte| *byte == 0) {
+					msg!("Invalid Arweave transaction id.");
+					return Err(ProgramError::InvalidArgument);
+				}
+
+				ContentRef::Arweave(tx_id)
+			},
+			ContentKind::Sha256 => {
+				if payload.len() <= SHA256_DIGEST_LEN {
+					msg!("Missing SHA-256 content URI.");
+					return Err(ProgramError::InvalidArgument);
+				}
+
+				let (digest, uri) = payload.split_at(SHA256_DIGEST_LEN);
+				validate_uri(uri)?;
+
+				let digest: [u8; SHA256_DIGEST_LEN] = match digest.try_into() {
+					Err(_e) => return Err(ProgramError::InvalidInstructionData),
+					Ok(b) => b,
+				};
+
+				if digest.iter().all(|byte| *byte == 0) {
+					msg!("Invalid SHA-256 digest.");
+					return Err(ProgramError::InvalidArgument);
+				}
+
+				ContentRef::Sha256 { digest, uri: uri.to_vec() }
+			},
+			ContentKind::None => {
+				msg!("A content reference is required.");
+				return Err(ProgramError::InvalidArgument);
+			},
+		};
+
+		Ok((content_ref, rest))
+	}
+}
+
+/// Checks that the URI is short enough and made of visible ASCII, as URIs must be.
+fn validate_uri(uri: &[u8]) -> Result<(), ProgramError> {
+
+	if uri.is_empty() || uri.len() > MAX_URI_LEN {
+		msg!("Invalid content URI length.");
+		return Err(ProgramError::InvalidArgument);
+	}
+
+	if !uri.iter().all(|byte| byte.is_ascii_graphic()) || !uri.contains(&b':') {
+		msg!("Invalid content URI.");
+		return Err(ProgramError::InvalidArgument);
+	}
+
+	Ok(())
+}
+
+/// A [ContentRef] as stored in zero-copy accounts: 1 byte of [ContentKind], 1 byte of length,
+/// and the payload, zero padded.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct ContentRefData {
+	/// See [ContentKind].
+	kind: u8,
+
+	/// Number of payload bytes in use.
+	len: u8,
+
+	/// Payload, zero padded.
+	data: [u8; MAX_CONTENT_REF_LEN],
+}
+
+impl Default for ContentRefData {
+	fn default() -> Self {
+		ContentRefData::zeroed()
+	}
+}
+
+impl From<&ContentRef> for ContentRefData {
+	fn from(content_ref: &ContentRef) -> Self {
+
+		let mut content_ref_data = ContentRefData::zeroed();
+
+		let len = match content_ref {
+			ContentRef::Ipfs(cid) => {
+				content_ref_data.kind = ContentKind::Ipfs as u8;
+				let cid_bytes = cid.as_bytes();
+				content_ref_data.data[..cid_bytes.len()].copy_from_slice(cid_bytes);
+				cid_bytes.len()
+			},
+			ContentRef::Arweave(tx_id) => {
+				content_ref_data.kind = ContentKind::Arweave as u8;
+				content_ref_data.data[..ARWEAVE_TX_ID_LEN].copy_from_slice(tx_id);
+				ARWEAVE_TX_ID_LEN
+			},
+			ContentRef::Sha256 { digest, uri } => {
+				content_ref_data.kind = ContentKind::Sha256 as u8;
+				content_ref_data.data[..SHA256_DIGEST_LEN].copy_from_slice(digest);
+				content_ref_data.data[SHA256_DIGEST_LEN..SHA256_DIGEST_LEN + uri.len()].copy_from_slice(uri);
+				SHA256_DIGEST_LEN + uri.len()
+			},
+		};
+
+		content_ref_data.len = len as u8;
+		content_ref_data
+	}
+}
+
+/// An IPFS content identifier in binary form, either CIDv0 or CIDv1.
+///
+/// CIDv0 is the bare sha2-256 multihash behind a "Qm..." string.
+/// CIDv1 is \<version\>\<codec\>\<multihash\>, all as unsigned varints, behind a "bafy..." string.
+/// Clients decode the multibase string before sending it, so the program only deals with bytes.
+/// An empty CID (len 0) means no content was provided.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct IpfsCid {
+	/// Number of bytes in use.
+	len: u8,
+
+	/// CID bytes, zero padded.
+	bytes: [u8; MAX_CID_LEN],
+}
+
+impl Default for IpfsCid {
+	fn default() -> Self {
+		IpfsCid::zeroed()
+	}
+}
+
+impl IpfsCid {
+
+	/// Builds a validated [IpfsCid] from its binary form.
+	pub fn new(cid_bytes: &[u8]) -> Result<Self, ProgramError> {
+
+		IpfsCid::validate(cid_bytes)?;
+
+		let mut cid = IpfsCid::zeroed();
+		cid.len = cid_bytes.len() as u8;
+		cid.bytes[..cid_bytes.len()].copy_from_slice(cid_bytes);
+
+		Ok(cid)
+	}
+
+	/// The CID bytes in use.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.bytes[..(self.len as usize).min(MAX_CID_LEN)]
+	}
+
+	/// Checks that the bytes are a well formed binary CIDv0 or CIDv1.
+	fn validate(cid_bytes: &[u8]) -> Result<(), ProgramError> {
+
+		if cid_bytes.is_empty() || cid_bytes.len() > MAX_CID_LEN {
+			msg!("Invalid IPFS CID length.");
+			return Err(ProgramError::InvalidArgument);
+		}
+
+		// CIDv0: a bare sha2-256 multihash
+		if cid_bytes.len() == CIDV0_LEN && cid_bytes[0] as u64 == SHA2_256_CODE {
+			return validate_multihash(cid_bytes);
+		}
+
+		// CIDv1: version, codec, multihash
+		let (version, rest) = read_varint(cid_bytes)?;
+		if version != 1 {
+			msg!("Unsupported IPFS CID version.");
+			return Err(ProgramError::InvalidArgument);
+		}
+
+		let (_codec, multihash) = read_varint(rest)?;
+
+		validate_multihash(multihash)
+	}
+}
+
+/// Checks that the bytes are exactly one multihash: \<hash code\>\<digest length\>\<digest\>.
+fn validate_multihash(multihash: &[u8]) -> Result<(), ProgramError> {
+
+	let (hash_code, rest) = read_varint(multihash)?;
+	let (digest_len, digest) = read_varint(rest)?;
+
+	if digest_len == 0 || digest.len() as u64 != digest_len {
+		msg!("Invalid IPFS CID digest.");
+		return Err(ProgramError::InvalidArgument);
+	}
+
+	if hash_code == SHA2_256_CODE && digest.len() != SHA256_DIGEST_LEN {
+		msg!("Invalid IPFS CID digest.");
+		return Err(ProgramError::InvalidArgument);
+	}
+
+	Ok(())
+}
+
+/// Reads an unsigned LEB128 varint as used by multiformats, at most 9 bytes long.
+fn read_varint(data: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+
+	let mut value: u64 = 0;
+
+	for (index, byte) in data.iter().enumerate().take(9) {
+		value |= ((byte & 0x7f) as u64) << (7 * index);
+
+		if byte & 0x80 == 0 {
+			// Multiformats requires the minimal encoding
+			if index > 0 && *byte == 0 {
+				break;
+			}
+			return Ok((value, &data[index + 1..]));
+		}
+	}
+
+	msg!("Invalid IPFS CID varint.");
+	Err(ProgramError::InvalidArgument)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DIGEST: [u8; SHA256_DIGEST_LEN] = [0xab; SHA256_DIGEST_LEN];
+
+	/// Multicodec of raw binary content, as used by CIDv1.
+	const RAW_CODEC: u8 = 0x55;
+
+	/// Multihash code of blake3, which is not limited to 32 bytes.
+	const BLAKE3_CODE: u8 = 0x1e;
+
+	fn cid_v0() -> Vec<u8> {
+		[&[SHA2_256_CODE as u8, SHA256_DIGEST_LEN as u8][..], &DIGEST].concat()
+	}
+
+	fn cid_v1(hash_code: u8, digest: &[u8]) -> Vec<u8> {
+		[&[0x01, RAW_CODEC, hash_code, digest.len() as u8][..], digest].concat()
+	}
+
+	#[test]
+	fn ipfs_cid_accepts_binary_cid_v0_and_v1() {
+		for cid_bytes in [cid_v0(), cid_v1(SHA2_256_CODE as u8, &DIGEST), cid_v1(BLAKE3_CODE, &[0xcd; 48])] {
+			let cid = IpfsCid::new(&cid_bytes).unwrap();
+			assert_eq!(cid.as_bytes(), &cid_bytes[..]);
+		}
+	}
+
+	#[test]
+	fn ipfs_cid_rejects_bad_lengths() {
+		assert_eq!(IpfsCid::new(&[]), Err(ProgramError::InvalidArgument));
+		assert_eq!(IpfsCid::new(&[0x01; MAX_CID_LEN + 1]), Err(ProgramError::InvalidArgument));
+
+		// A CIDv0 one byte short is read as an unsupported CID version
+		assert_eq!(IpfsCid::new(&cid_v0()[..CIDV0_LEN - 1]), Err(ProgramError::InvalidArgument));
+	}
+
+	#[test]
+	fn ipfs_cid_rejects_malformed_varints() {
+		let mut unsupported_version = cid_v1(SHA2_256_CODE as u8, &DIGEST);
+		unsupported_version[0] = 0x02;
+
+		// The version 1 in two bytes is not the minimal encoding
+		let non_minimal_version = [&[0x81, 0x00][..], &cid_v1(SHA2_256_CODE as u8, &DIGEST)[1..]].concat();
+
+		// The codec never ends
+		let unterminated_codec = [0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+
+		for cid_bytes in [unsupported_version, non_minimal_version, unterminated_codec.to_vec()] {
+			assert_eq!(IpfsCid::new(&cid_bytes), Err(ProgramError::InvalidArgument));
+		}
+	}
+
+	#[test]
+	fn ipfs_cid_rejects_malformed_multihashes() {
+		let malformed = [
+			// Missing digest length
+			vec![0x01, RAW_CODEC, SHA2_256_CODE as u8],
+			// Empty digest
+			cid_v1(BLAKE3_CODE, &[]),
+			// Digest shorter than its length
+			cid_v1(SHA2_256_CODE as u8, &DIGEST)[..3 + SHA256_DIGEST_LEN].to_vec(),
+			// Digest longer than its length
+			[&cid_v1(SHA2_256_CODE as u8, &DIGEST)[..], &[0x00]].concat(),
+			// sha2-256 digests are always 32 bytes
+			cid_v1(SHA2_256_CODE as u8, &DIGEST[..16]),
+		];
+
+		for cid_bytes in malformed {
+			assert_eq!(IpfsCid::new(&cid_bytes), Err(ProgramError::InvalidArgument));
+		}
+	}
+
+	fn encode(kind: ContentKind, payload: &[u8]) -> Vec<u8> {
+		[&[kind as u8, payload.len() as u8][..], payload].concat()
+	}
+
+	fn sha256_payload(uri: &[u8]) -> Vec<u8> {
+		[&DIGEST[..], uri].concat()
+	}
+
+	#[test]
+	fn content_ref_unpacks_every_kind_and_returns_the_rest() {
+		let uri = b"https://example.com/item.json";
+
+		let data = [&encode(ContentKind::Ipfs, &cid_v0())[..], &[0x07, 0x08]].concat();
+		let expected = ContentRef::Ipfs(IpfsCid::new(&cid_v0()).unwrap());
+		assert_eq!(ContentRef::unpack(&data), Ok((expected, &[0x07, 0x08][..])));
+
+		let data = encode(ContentKind::Arweave, &[0x11; ARWEAVE_TX_ID_LEN]);
+		assert_eq!(ContentRef::unpack(&data), Ok((ContentRef::Arweave([0x11; ARWEAVE_TX_ID_LEN]), &[][..])));
+
+		let data = encode(ContentKind::Sha256, &sha256_payload(uri));
+		let expected = ContentRef::Sha256 { digest: DIGEST, uri: uri.to_vec() };
+		assert_eq!(ContentRef::unpack(&data), Ok((expected, &[][..])));
+	}
+
+	#[test]
+	fn content_ref_rejects_malformed_encodings() {
+		let ipfs = encode(ContentKind::Ipfs, &cid_v0());
+
+		// Missing kind or length
+		assert_eq!(ContentRef::unpack(&[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(ContentRef::unpack(&ipfs[..1]), Err(ProgramError::InvalidInstructionData));
+
+		// Payload shorter than its length
+		assert_eq!(ContentRef::unpack(&ipfs[..ipfs.len() - 1]), Err(ProgramError::InvalidInstructionData));
+
+		// Unknown kind
+		assert_eq!(ContentRef::unpack(&[0x04, 0x00]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn content_ref_rejects_invalid_payloads() {
+		let invalid = [
+			// A content reference is required
+			encode(ContentKind::None, &[]),
+			encode(ContentKind::Ipfs, &[]),
+			// Arweave transaction ids are 32 bytes, and not zeroed
+			encode(ContentKind::Arweave, &[0x11; ARWEAVE_TX_ID_LEN - 1]),
+			encode(ContentKind::Arweave, &[0x00; ARWEAVE_TX_ID_LEN]),
+			// SHA-256 content needs a URI, and a digest that is not zeroed
+			encode(ContentKind::Sha256, &DIGEST),
+			encode(ContentKind::Sha256, &[&[0x00; SHA256_DIGEST_LEN][..], b"ar://item"].concat()),
+			// URIs are visible ASCII with a scheme, and at most MAX_URI_LEN bytes
+			encode(ContentKind::Sha256, &sha256_payload(b"https://example.com/my item.json")),
+			encode(ContentKind::Sha256, &sha256_payload(b"example.com/item.json")),
+			encode(ContentKind::Sha256, &sha256_payload(&[b'a'; MAX_URI_LEN + 1])),
+		];
+
+		for data in invalid {
+			assert_eq!(ContentRef::unpack(&data), Err(ProgramError::InvalidArgument));
+		}
+	}
+
+	#[test]
+	fn content_ref_data_keeps_the_kind_and_payload() {
+		let payload = sha256_payload(b"ar://item");
+		let (content_ref, _) = ContentRef::unpack(&encode(ContentKind::Sha256, &payload)).unwrap();
+
+		let content_ref_data = ContentRefData::from(&content_ref);
+		assert_eq!(content_ref_data.kind, ContentKind::Sha256 as u8);
+		assert_eq!(content_ref_data.len as usize, payload.len());
+		assert_eq!(&content_ref_data.data[..payload.len()], &payload[..]);
+		assert!(content_ref_data.data[payload.len()..].iter().all(|byte| *byte == 0));
+	}
+}
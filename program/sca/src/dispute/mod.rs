@@ -1,33 +1,38 @@
-//!	# operation
-//!	Functionality related to Operations
+//! # dispute
+//! Functionality related to Disputes
 //!
 //! List of supported instructions
 //!
-//! 1. Initialize Operation -> [initialize_operation]
+//! 1. Start Dispute -> [start_dispute]
+//! 2. Seller Add Info -> [seller_add_info]
+//! 3. Buyer Add Info -> [buyer_add_info]
+//! 4. Arbiter Add Info -> [arbiter_add_info]
+//! 5. Arbiter Vote -> [arbiter_vote]
+//! 6. Replace Arbiters -> [replace_arbiters]
+//! 7. Participant Claim -> [participant_claim]
 //!
 //!
 
-use borsh::{BorshDeserialize, BorshSerialize};
-
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
-    msg, system_instruction::transfer,
+    msg,
     program_error::ProgramError,
-    pubkey::Pubkey, system_program::check_id,
-	clock::Clock, program::invoke,
-	sysvar::{
-		Sysvar,
-		rent::Rent,
-	}
-
+    pubkey::Pubkey,
+	clock::{Clock, UnixTimestamp},
+	sysvar::{self, Sysvar},
 };
 
-use std::str;
+use crate::content::{ContentRef, ContentRefData};
 
 use crate::scatype::{
-	OperationAccount, OperationStatus, TokenVersion,
-	ZERO_ACCOUNT, SCAError, VotingOptions,
+	OperationAccount, OperationStatus, VotingOptions, ArbiterProfile, ArbiterSelection,
+	EvidenceEntry, EvidenceRole, SCAError,
+	EVIDENCE_PERIOD, VOTING_PERIOD, APPEAL_PERIOD, ZERO_ACCOUNT,
+};
+
+use crate::evidence::{
+	create_evidence_log, append_evidence,
 };
 
 use crate::account::{
@@ -35,14 +40,42 @@ use crate::account::{
 	is_signed_by_writable_account,
 };
 
-/// Allows a Buyer/Seller to start a dispute on the operation
-/// Note that this function expectes a BuyerDeposited [OperationAccount].
+use crate::arbiter::{
+	check_arbiter_profile,
+	release_seat,
+	record_missed_vote,
+	slash_missed_vote,
+};
+
+use crate::pool::{
+	arbiter_pool_address,
+	linked_parties,
+	select_arbiters,
+};
+
+use crate::appeal::{
+	record_appeal_vote,
+	replace_appeal_arbiters,
+};
+
+/// Allows a Buyer/Seller to start a dispute on the operation, creating its evidence log.
+/// The evidence period starts now, see [EVIDENCE_PERIOD]. A delivered item can only be disputed within its inspection period, if any.
+/// With random arbiter selection, the three arbiters are picked from the pool here, see [select_arbiters],
+/// and confirm their seat with [crate::operation::register_arbiter].
+/// Note that this function expectes a BuyerDeposited/Shipped/Delivered [OperationAccount].
+///
+/// reason: Content reference of the complaint, conformant to [OperationInstruction::StartDispute]
 ///
-///	Accounts:
-///	1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction and the evidence log.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. EVIDENCELOG - Created here. PDA of [crate::scatype::EVIDENCE_LOG_SEED] and OPERATIONACCOUNT.
+/// 4. SYSTEM PROGRAM - Used to create the evidence log.
+/// 5. ARBITERPOOL - Only for random arbiter selection. Comformant to [crate::scatype::ArbiterPoolHeader]
+/// 6. SLOTHASHES - Only for random arbiter selection. The SlotHashes sysvar.
 #[inline(never)]
-pub fn start_dispute(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+pub fn start_dispute(program_id: &Pubkey, accounts: &[AccountInfo],
+	reason: ContentRef) -> ProgramResult {
 
 	// Iterating accounts is safer than indexing
 	let accounts_iter = &mut accounts.iter();
@@ -61,18 +94,29 @@ pub fn start_dispute(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 	// Check OPERATIONACCOUNT account validity
 	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
 
+	// Get the EVIDENCELOG account, validated when created
+	let evidence_log_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+	// Get the optional ARBITERPOOL and SLOTHASHES accounts, validated when selecting
+	let arbiter_pool_account_info = next_account_info(accounts_iter).ok();
+	let slot_hashes_account_info = next_account_info(accounts_iter).ok();
+
 
 	// ================ Enforce configuration rules section
 
+	let unix_timestamp = current_timestamp();
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::BuyerDeposited {
+	if !operation_account_data.status()?.is_deposited() {
 		msg!("Operation account not setup.");
 		return Err(ProgramError::UninitializedAccount)
 	}
@@ -86,30 +130,78 @@ pub fn start_dispute(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
         return Err(ProgramError::InvalidAccountData)
 	}
 
+	// Once the inspection period is over the delivery is final, see [crate::shipping::finalize_release]
+	let inspection_deadline = operation_account_data.inspection_deadline();
+
+	if operation_account_data.status()? == OperationStatus::Delivered && inspection_deadline != 0 && unix_timestamp >= inspection_deadline {
+		msg!("Inspection period is over.");
+		return Err(ProgramError::Custom(SCAError::InspectionPeriodOver as u32))
+	}
+
 	// ========================= Change state section
 
+	// The shipment and delivery times stay recorded, so the arbiters can weigh the evidence against them
+	msg!("Item shipped at: {}, received at: {}", operation_account_data.shipped_at(), operation_account_data.delivered_at());
+
 	// Set internally; make sure everything not used is zeroed out anyway.
-	operation_account_data.status = OperationStatus::InDispute;
+	operation_account_data.set_status(OperationStatus::InDispute);
+	operation_account_data.set_evidence_deadline(unix_timestamp.saturating_add(EVIDENCE_PERIOD));
+	operation_account_data.set_vote_deadline(unix_timestamp.saturating_add(EVIDENCE_PERIOD).saturating_add(VOTING_PERIOD));
 
 	// Set externally
+	operation_account_data.dispute_initiator = *participant_account_info.key;
+	operation_account_data.set_dispute_started_at(unix_timestamp);
+	operation_account_data.dispute_reason = ContentRefData::from(&reason);
+
+	if operation_account_data.arbiter_selection()? == ArbiterSelection::Random {
+		let (arbiter_pool_account_info, slot_hashes_account_info) =
+			match (arbiter_pool_account_info, slot_hashes_account_info) {
+			(Some(arbiter_pool_account_info), Some(slot_hashes_account_info)) =>
+				(arbiter_pool_account_info, slot_hashes_account_info),
+			_ => {
+				msg!("ARBITERPOOL and SLOTHASHES accounts are required to select the arbiters.");
+				return Err(ProgramError::NotEnoughAccountKeys)
+			}
+		};
+
+		// Nobody linked to the operation can be picked
+		let selected = select_arbiters(program_id, arbiter_pool_account_info, slot_hashes_account_info,
+			operation_account_info.key, &linked_parties(operation_account_data), 3)?;
+
+		operation_account_data.arbiter1 = selected[0];
+		operation_account_data.arbiter2 = selected[1];
+		operation_account_data.arbiter3 = selected[2];
+
+		// Nobody chose them, so there is nothing for the parties to approve
+		operation_account_data.set_seller_approved(true);
+		operation_account_data.set_buyer_approved(true);
+
+		msg!("Arbiters selected: {} {} {}", selected[0], selected[1], selected[2]);
+	}
+
+	// Release the account data before calling into the System program
+	drop(operation_account_bytes);
+
+	create_evidence_log(program_id, operation_account_info.key, participant_account_info,
+		evidence_log_account_info, system_program_account_info, &[])?;
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Participant opened dispute.");
 
 	Ok(())
 }
 
-/// Allow Seller to save additional info. Note that this function expectes a InDispute [OperationAccount].
+/// Allow Seller to append additional info to the evidence log, during the evidence period. Note that this function expectes a InDispute [OperationAccount].
 ///
 /// operation_data: A tuple conformant to [OperationInstruction::SellerAddInfo]
 ///
-///	Accounts:
-///	1. SELLER - Account of the item seller, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Initialized here, reused elsewhere. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction and the log growth.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. EVIDENCELOG - Evidence log of the operation, created by [start_dispute].
+/// 4. SYSTEM PROGRAM - Used to transfer the additional rent.
 #[inline(never)]
 pub fn seller_add_info(program_id: &Pubkey, accounts: &[AccountInfo], 
-	ipfs_hash_bytes: [u8;46]) -> ProgramResult {
+	content_ext: ContentRef) -> ProgramResult {
 
 	// Iterating accounts is safer than indexing
 	let accounts_iter = &mut accounts.iter();
@@ -128,57 +220,72 @@ pub fn seller_add_info(program_id: &Pubkey, accounts: &[AccountInfo],
 	// Check OPERATIONACCOUNT account validity
 	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
 
+	// Get the EVIDENCELOG account, validated when appending
+	let evidence_log_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
 
 	// ================ Enforce configuration rules section
 
+	let unix_timestamp = current_timestamp();
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this a previously activated DATA account??? -> Reject
-	if operation_account_data.status != OperationStatus::InDispute {
+	if operation_account_data.status()? != OperationStatus::InDispute {
 		msg!("Operation account incorrect state.");
 		return Err(ProgramError::InvalidAccountData)
 	}
 
 	// ======================= Enforce data validity using accounts data section
 
-	// Bytes to string, must be valid utf-8 (base58btc ascii in practice)
-	let ipfs_hash_str = match str::from_utf8(&ipfs_hash_bytes) {
-		Ok(v) => v,
-		Err(_e) => return Err(ProgramError::InvalidInstructionData),
-	};
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
 
-	if !ipfs_hash_str.starts_with("Qm") {
-		msg!("Invalid IPFS hash.");
-		return Err(ProgramError::InvalidArgument);
+	if unix_timestamp >= operation_account_data.evidence_deadline() {
+		msg!("Evidence period is over.");
+		return Err(ProgramError::Custom(SCAError::EvidencePeriodOver as u32))
 	}
 
+	// The content reference was already validated when the instruction was unpacked
+
 	// ========================= Change state section
 
+	// Release the account data before calling into the System program
+	drop(operation_account_bytes);
+
 	// Set externally
-	operation_account_data.seller_ipfs_ext = ipfs_hash_bytes;
+	let entry = EvidenceEntry::new(*seller_account_info.key, EvidenceRole::Seller,
+		unix_timestamp, ContentRefData::from(&content_ext));
+
+	append_evidence(program_id, operation_account_info.key, seller_account_info,
+		evidence_log_account_info, system_program_account_info, &entry)?;
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Seller added extra info.");
 
 	Ok(())
 }
 
-/// Allow Buyer to save additional info. Note that this function expectes a InDispute [OperationAccount].
+/// Allow Buyer to append additional info to the evidence log, during the evidence period. Note that this function expectes a InDispute [OperationAccount].
 ///
 /// operation_data: A tuple conformant to [OperationInstruction::BuyerAddInfo]
 ///
-///	Accounts:
-///	1. BUYER - Account of the item buyer, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Initialized here, reused elsewhere. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. BUYER - Account of the item buyer, who also pays for this transaction and the log growth.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. EVIDENCELOG - Evidence log of the operation, created by [start_dispute].
+/// 4. SYSTEM PROGRAM - Used to transfer the additional rent.
 #[inline(never)]
 pub fn buyer_add_info(program_id: &Pubkey, accounts: &[AccountInfo], 
-	ipfs_hash_bytes: [u8;46]) -> ProgramResult {
+	content_ext: ContentRef) -> ProgramResult {
 
 	// Iterating accounts is safer than indexing
 	let accounts_iter = &mut accounts.iter();
@@ -197,53 +304,164 @@ pub fn buyer_add_info(program_id: &Pubkey, accounts: &[AccountInfo],
 	// Check OPERATIONACCOUNT account validity
 	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
 
+	// Get the EVIDENCELOG account, validated when appending
+	let evidence_log_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
 
 	// ================ Enforce configuration rules section
 
+	let unix_timestamp = current_timestamp();
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this a previously activated DATA account??? -> Reject
-	if operation_account_data.status != OperationStatus::InDispute {
+	if operation_account_data.status()? != OperationStatus::InDispute {
 		msg!("Operation account incorrect state.");
 		return Err(ProgramError::InvalidAccountData)
 	}
 
 	// ======================= Enforce data validity using accounts data section
 
-	// Bytes to string, must be valid utf-8 (base58btc ascii in practice)
-	let ipfs_hash_str = match str::from_utf8(&ipfs_hash_bytes) {
-		Ok(v) => v,
-		Err(_e) => return Err(ProgramError::InvalidInstructionData),
-	};
+	if *buyer_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
 
-	if !ipfs_hash_str.starts_with("Qm") {
-		msg!("Invalid IPFS hash.");
-		return Err(ProgramError::InvalidArgument);
+	if unix_timestamp >= operation_account_data.evidence_deadline() {
+		msg!("Evidence period is over.");
+		return Err(ProgramError::Custom(SCAError::EvidencePeriodOver as u32))
 	}
 
+	// The content reference was already validated when the instruction was unpacked
+
 	// ========================= Change state section
 
+	// Release the account data before calling into the System program
+	drop(operation_account_bytes);
+
 	// Set externally
-	operation_account_data.buyer_ipfs_ext = ipfs_hash_bytes;
+	let entry = EvidenceEntry::new(*buyer_account_info.key, EvidenceRole::Buyer,
+		unix_timestamp, ContentRefData::from(&content_ext));
+
+	append_evidence(program_id, operation_account_info.key, buyer_account_info,
+		evidence_log_account_info, system_program_account_info, &entry)?;
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Buyer added extra info.");
 
 	Ok(())
 }
 
-/// Allows an arbiter to vote in an operation.
-/// Note that this function expectes an InDispute/InVoting [OperationAccount].
+/// Allow an arbiter to append a ruling or comment to the evidence log.
+/// Note that this function expectes an InDispute/InVoting/SellerClaim/BuyerClaim [OperationAccount].
+///
+/// operation_data: A tuple conformant to [OperationInstruction::ArbiterAddInfo]
+///
+/// Accounts:
+/// 1. ARBITER - Account of one of the arbiters, who also pays for this transaction and the log growth.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. EVIDENCELOG - Evidence log of the operation, created by [start_dispute].
+/// 4. SYSTEM PROGRAM - Used to transfer the additional rent.
+#[inline(never)]
+pub fn arbiter_add_info(program_id: &Pubkey, accounts: &[AccountInfo], 
+	content_ext: ContentRef) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get ARBITER account
+	let arbiter_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITER account validity
+	is_signed_by_writable_account(arbiter_account_info, "ARBITER account is not a valid account.")?;
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the EVIDENCELOG account, validated when appending
+	let evidence_log_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let operation_account_bytes = operation_account_info.try_borrow_data()?;
+	let operation_account_data = OperationAccount::load(&operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	// Rulings can still be explained once voting ended, until the result is claimed.
+	match operation_account_data.status()? {
+		OperationStatus::InDispute |
+		OperationStatus::InVoting |
+		OperationStatus::SellerClaim |
+		OperationStatus::BuyerClaim |
+		OperationStatus::InAppeal => {},
+		_ => {
+			msg!("Operation account incorrect state.");
+			return Err(ProgramError::InvalidAccountData)
+		}
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if *arbiter_account_info.key != operation_account_data.arbiter1 &&
+		*arbiter_account_info.key != operation_account_data.arbiter2 &&
+		*arbiter_account_info.key != operation_account_data.arbiter3 &&
+		!(arbiter_account_info.key.to_bytes() != ZERO_ACCOUNT &&
+			operation_account_data.appeal_arbiters.contains(arbiter_account_info.key)) {
+		msg!("Invalid Arbiter account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// The content reference was already validated when the instruction was unpacked
+
+	// ========================= Change state section
+
+	// Release the account data before calling into the System program
+	drop(operation_account_bytes);
+
+	// Set externally
+	let entry = EvidenceEntry::new(*arbiter_account_info.key, EvidenceRole::Arbiter,
+		unix_timestamp, ContentRefData::from(&content_ext));
+
+	append_evidence(program_id, operation_account_info.key, arbiter_account_info,
+		evidence_log_account_info, system_program_account_info, &entry)?;
+
+	msg!("Arbiter added extra info.");
+
+	Ok(())
+}
+
+/// Allows an arbiter to vote in an operation, once the evidence period is over.
+/// Note that this function expectes an InDispute/InVoting/InAppeal [OperationAccount].
+/// The vote is counted on the profile of the arbiter. Arbiters picked from the pool vote once they confirmed
+/// their seat, see [crate::operation::register_arbiter].
+/// The verdict of the first panel opens the appeal window, see [APPEAL_PERIOD].
+/// During an appeal only the appeal panel votes, once confirmed, see [record_appeal_vote].
 ///
-///	Accounts:
-///	1. ARBITER - Account of one of the arbiters, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. ARBITER - Account of one of the arbiters, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. ARBITERPROFILE - Profile of the arbiter. Comformant to [ArbiterProfile]
+/// 4. ARBITERSTAKE - Only for a seat locked at registration, see [crate::operation::register_arbiter]. Stake of the arbiter, unlocked by the vote.
 #[inline(never)]
 pub fn arbiter_vote(program_id: &Pubkey, accounts: &[AccountInfo], vote: bool) -> ProgramResult {
 
@@ -264,34 +482,72 @@ pub fn arbiter_vote(program_id: &Pubkey, accounts: &[AccountInfo], vote: bool) -
 	// Check OPERATIONACCOUNT account validity
 	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
 
+	// Get the ARBITERPROFILE account
+	let arbiter_profile_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITERPROFILE account validity
+	check_arbiter_profile(program_id, arbiter_account_info.key, arbiter_profile_account_info)?;
+
 
 	// ================ Enforce configuration rules section
 
+	let unix_timestamp = current_timestamp();
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::InDispute &&
-        operation_account_data.status != OperationStatus::InVoting {
+	if operation_account_data.status()? != OperationStatus::InDispute &&
+        operation_account_data.status()? != OperationStatus::InVoting &&
+        operation_account_data.status()? != OperationStatus::InAppeal {
 		msg!("Operation account incorrect state.");
 		return Err(ProgramError::InvalidAccountData)
 	}
 
+	// CHECK: Are the parties still submitting evidence??? -> Reject
+	if unix_timestamp < operation_account_data.evidence_deadline() {
+		msg!("Evidence period still open.");
+		return Err(ProgramError::Custom(SCAError::EvidencePeriodOpen as u32))
+	}
+
+	// CHECK: Were arbiters replaced and not yet approved??? -> Reject
+	if !operation_account_data.seller_approved() ||
+		!operation_account_data.buyer_approved() {
+		msg!("Arbiters not approved.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
 
 	// ======================= Enforce data validity using accounts data section
 
-	if operation_account_data.arbiter1 == *arbiter_account_info.key {
+	let in_appeal = operation_account_data.status()? == OperationStatus::InAppeal;
+
+	// CHECK: Did the arbiter picked from the pool confirm the seat??? -> Reject
+	if in_appeal || operation_account_data.arbiter_selection()? == ArbiterSelection::Random {
+		if let Some(seat) = operation_account_data.arbiter_seat(arbiter_account_info.key) {
+			if !operation_account_data.seat_locked(seat) {
+				msg!("Arbiter seat not confirmed.");
+				return Err(ProgramError::InvalidAccountData)
+			}
+		}
+	}
+
+	if in_appeal {
+
+		record_appeal_vote(operation_account_data, arbiter_account_info.key, vote)?;
+
+	} else if operation_account_data.arbiter1 == *arbiter_account_info.key {
 
-		match operation_account_data.arbiter_vote_1 {
+		match operation_account_data.arbiter_vote_1()? {
 			VotingOptions::NoVote => {
-				if vote == false {
-					operation_account_data.arbiter_vote_1 = VotingOptions::Buyer;
+				if !vote {
+					operation_account_data.set_arbiter_vote_1(VotingOptions::Buyer);
 				} else {
-					operation_account_data.arbiter_vote_1 = VotingOptions::Seller;
+					operation_account_data.set_arbiter_vote_1(VotingOptions::Seller);
 				}
 			},
 			_ => {
@@ -301,22 +557,22 @@ pub fn arbiter_vote(program_id: &Pubkey, accounts: &[AccountInfo], vote: bool) -
 		}
 
     } else if operation_account_data.arbiter2 == *arbiter_account_info.key {
-        if operation_account_data.arbiter_vote_2 == VotingOptions::NoVote {
-            if vote == false {
-                operation_account_data.arbiter_vote_2 = VotingOptions::Buyer;
+        if operation_account_data.arbiter_vote_2()? == VotingOptions::NoVote {
+            if !vote {
+                operation_account_data.set_arbiter_vote_2(VotingOptions::Buyer);
             } else {
-                operation_account_data.arbiter_vote_2 = VotingOptions::Seller;
+                operation_account_data.set_arbiter_vote_2(VotingOptions::Seller);
             }
         } else {
             msg!("Already voted.");
             return Err(ProgramError::AccountAlreadyInitialized)               
         }
     } else if operation_account_data.arbiter3 == *arbiter_account_info.key {
-        if operation_account_data.arbiter_vote_3 == VotingOptions::NoVote {
-            if vote == false {
-                operation_account_data.arbiter_vote_3 = VotingOptions::Buyer;
+        if operation_account_data.arbiter_vote_3()? == VotingOptions::NoVote {
+            if !vote {
+                operation_account_data.set_arbiter_vote_3(VotingOptions::Buyer);
             } else {
-                operation_account_data.arbiter_vote_3 = VotingOptions::Seller;
+                operation_account_data.set_arbiter_vote_3(VotingOptions::Seller);
             }
         } else {
             msg!("Already voted.");
@@ -331,61 +587,281 @@ pub fn arbiter_vote(program_id: &Pubkey, accounts: &[AccountInfo], vote: bool) -
 
 	// Set internally; make sure everything not used is zeroed out anyway.
 
+	// The vote ends the case for the arbiter
+	if let Some(seat) = operation_account_data.arbiter_seat(arbiter_account_info.key) {
+		release_seat(program_id, operation_account_data, seat, arbiter_account_info.key, accounts_iter.as_slice())?;
+	}
+
 	// Set externally
 
-	operation_account_data.status = OperationStatus::InVoting;
-
-    // All votes are mandatory
-    if operation_account_data.arbiter_vote_1 != VotingOptions::NoVote && 
-        operation_account_data.arbiter_vote_2 != VotingOptions::NoVote && 
-        operation_account_data.arbiter_vote_3 != VotingOptions::NoVote
-    {
-        let mut buyer_claim = 0;
-        let mut seller_claim = 0;
-    
-        // Vote count
-        if operation_account_data.arbiter_vote_1 == VotingOptions::Buyer {
-            buyer_claim = buyer_claim + 1;
-        } else if operation_account_data.arbiter_vote_1 == VotingOptions::Seller {
-            seller_claim = seller_claim + 1;
-        }
+	ArbiterProfile::load_mut(&mut arbiter_profile_account_info.try_borrow_mut_data()?)?.add_vote_cast();
 
-        // Vote count
-        if operation_account_data.arbiter_vote_2 == VotingOptions::Buyer {
-            buyer_claim = buyer_claim + 1;
-        } else if operation_account_data.arbiter_vote_2 == VotingOptions::Seller {
-            seller_claim = seller_claim + 1;
-        }
-        
-        // Vote count
-        if operation_account_data.arbiter_vote_3 == VotingOptions::Buyer {
-            buyer_claim = buyer_claim + 1;
-        } else if operation_account_data.arbiter_vote_3 == VotingOptions::Seller {
-            seller_claim = seller_claim + 1;
-        }
+	// The appeal panel tallies its own votes
+	if !in_appeal {
 
-        if buyer_claim > seller_claim {
-            operation_account_data.status = OperationStatus::BuyerClaim;
-            msg!("Buyer claim enabled.");
-        } else {
-            operation_account_data.status = OperationStatus::SellerClaim;
-            msg!("Seller claims enabled.");
-        }
-    }
+		operation_account_data.set_status(OperationStatus::InVoting);
+
+		// All votes are mandatory
+		if operation_account_data.arbiter_vote_1()? != VotingOptions::NoVote && 
+			operation_account_data.arbiter_vote_2()? != VotingOptions::NoVote && 
+			operation_account_data.arbiter_vote_3()? != VotingOptions::NoVote
+		{
+			let mut buyer_claim = 0;
+			let mut seller_claim = 0;
+
+			// Vote count
+			if operation_account_data.arbiter_vote_1()? == VotingOptions::Buyer {
+				buyer_claim += 1;
+			} else if operation_account_data.arbiter_vote_1()? == VotingOptions::Seller {
+				seller_claim += 1;
+			}
+
+			// Vote count
+			if operation_account_data.arbiter_vote_2()? == VotingOptions::Buyer {
+				buyer_claim += 1;
+			} else if operation_account_data.arbiter_vote_2()? == VotingOptions::Seller {
+				seller_claim += 1;
+			}
+
+			// Vote count
+			if operation_account_data.arbiter_vote_3()? == VotingOptions::Buyer {
+				buyer_claim += 1;
+			} else if operation_account_data.arbiter_vote_3()? == VotingOptions::Seller {
+				seller_claim += 1;
+			}
+
+			if buyer_claim > seller_claim {
+				operation_account_data.set_status(OperationStatus::BuyerClaim);
+				msg!("Buyer claim enabled.");
+			} else {
+				operation_account_data.set_status(OperationStatus::SellerClaim);
+				msg!("Seller claims enabled.");
+			}
+
+			operation_account_data.set_appeal_deadline(unix_timestamp.saturating_add(APPEAL_PERIOD));
+		}
+	}
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Arbiter vote recorded.");
 
 	Ok(())
 }
 
-/// Allows a Buyer/Seller to claim the dispute result
+/// Allows a Buyer/Seller to replace the arbiters who did not vote by the deadline.
+/// Their slots and invitations are freed for new arbiters to be invited and register,
+/// and the parties must approve the panel again.
+/// Votes already cast are kept, and the voting period starts over.
+/// Note that this function expectes an InDispute/InVoting/InAppeal [OperationAccount], see [VOTING_PERIOD].
+/// During an appeal, the appeal arbiters who did not vote are replaced from the pool instead,
+/// see [replace_appeal_arbiters].
+///
+/// The replaced arbiters get a missed vote on their profile, and part of their stake
+/// is slashed and paid half to each party, see [crate::scatype::STAKE_SLASH_PERCENT].
+/// They also leave the arbiter pool, see [slash_missed_vote].
+/// With random arbiter selection, the freed slots are filled from the pool right away, and the new arbiters confirm their seat.
+///
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. COUNTERPARTY - Account of the other party, the buyer/seller.
+/// 4. ARBITERPROFILE/ARBITERSTAKE... - Profile and stake of each arbiter being replaced, in any order.
+/// 5. ARBITERPOOL - PDA of [crate::scatype::ARBITER_POOL_SEED], in any order along with the above. It may not exist yet.
+/// 6. SLOTHASHES - Only for random arbiter selection, in any order along with the above. The SlotHashes sysvar.
+#[inline(never)]
+pub fn replace_arbiters(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get PARTICIPANT account
+	let participant_account_info = next_account_info(accounts_iter)?;
+
+	// Check PARTICIPANT account validity
+	is_signed_by_writable_account(participant_account_info, "PARTICIPANT account is not a valid account.")?;
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the COUNTERPARTY account, validated below
+	let counterparty_account_info = next_account_info(accounts_iter)?;
+
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::InDispute &&
+        operation_account_data.status()? != OperationStatus::InVoting &&
+        operation_account_data.status()? != OperationStatus::InAppeal {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// CHECK: Do the arbiters still have time to vote??? -> Reject
+	if unix_timestamp < operation_account_data.vote_deadline() {
+		msg!("Voting period still open.");
+		return Err(ProgramError::Custom(SCAError::VotingPeriodOpen as u32))
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if *participant_account_info.key != operation_account_data.seller &&
+		*participant_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer/Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	let counterparty = if *participant_account_info.key == operation_account_data.seller {
+		operation_account_data.buyer
+	} else {
+		operation_account_data.seller
+	};
+
+	if *counterparty_account_info.key != counterparty || !counterparty_account_info.is_writable {
+		msg!("Invalid COUNTERPARTY account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ========================= Change state section
+
+	if operation_account_data.status()? == OperationStatus::InAppeal {
+		let replaced = replace_appeal_arbiters(program_id, operation_account_info.key, operation_account_data,
+			accounts_iter.as_slice(), participant_account_info, counterparty_account_info)?;
+
+		if replaced == 0 {
+			msg!("No arbiter to replace.");
+			return Err(ProgramError::InvalidAccountData)
+		}
+
+		operation_account_data.set_vote_deadline(unix_timestamp.saturating_add(VOTING_PERIOD));
+
+		msg!("Arbiters replaced: {}", replaced);
+
+		return Ok(())
+	}
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	let mut replaced = 0;
+	let mut replaced_arbiters: Vec<Pubkey> = Vec::with_capacity(3);
+
+	if operation_account_data.arbiter1.to_bytes() != ZERO_ACCOUNT &&
+		operation_account_data.arbiter_vote_1()? == VotingOptions::NoVote {
+		let arbiter = operation_account_data.arbiter1;
+		operation_account_data.remove_invite(&arbiter);
+		operation_account_data.arbiter1 = Default::default();
+		release_seat(program_id, operation_account_data, 0, &arbiter, accounts_iter.as_slice())?;
+		record_missed_vote(program_id, &arbiter, accounts_iter.as_slice())?;
+		slash_missed_vote(program_id, &arbiter, accounts_iter.as_slice(),
+			participant_account_info, counterparty_account_info)?;
+		replaced_arbiters.push(arbiter);
+		replaced += 1;
+	}
+
+	if operation_account_data.arbiter2.to_bytes() != ZERO_ACCOUNT &&
+		operation_account_data.arbiter_vote_2()? == VotingOptions::NoVote {
+		let arbiter = operation_account_data.arbiter2;
+		operation_account_data.remove_invite(&arbiter);
+		operation_account_data.arbiter2 = Default::default();
+		release_seat(program_id, operation_account_data, 1, &arbiter, accounts_iter.as_slice())?;
+		record_missed_vote(program_id, &arbiter, accounts_iter.as_slice())?;
+		slash_missed_vote(program_id, &arbiter, accounts_iter.as_slice(),
+			participant_account_info, counterparty_account_info)?;
+		replaced_arbiters.push(arbiter);
+		replaced += 1;
+	}
+
+	if operation_account_data.arbiter3.to_bytes() != ZERO_ACCOUNT &&
+		operation_account_data.arbiter_vote_3()? == VotingOptions::NoVote {
+		let arbiter = operation_account_data.arbiter3;
+		operation_account_data.remove_invite(&arbiter);
+		operation_account_data.arbiter3 = Default::default();
+		release_seat(program_id, operation_account_data, 2, &arbiter, accounts_iter.as_slice())?;
+		record_missed_vote(program_id, &arbiter, accounts_iter.as_slice())?;
+		slash_missed_vote(program_id, &arbiter, accounts_iter.as_slice(),
+			participant_account_info, counterparty_account_info)?;
+		replaced_arbiters.push(arbiter);
+		replaced += 1;
+	}
+
+	if replaced == 0 {
+		msg!("No arbiter to replace.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	let remaining_account_infos = accounts_iter.as_slice();
+
+	if operation_account_data.arbiter_selection()? == ArbiterSelection::Random {
+		let (arbiter_pool_pubkey, _bump) = arbiter_pool_address(program_id);
+		let arbiter_pool_account_info = remaining_account_infos.iter()
+			.find(|account_info| *account_info.key == arbiter_pool_pubkey);
+
+		let slot_hashes_account_info = remaining_account_infos.iter()
+			.find(|account_info| sysvar::slot_hashes::check_id(account_info.key));
+
+		let (arbiter_pool_account_info, slot_hashes_account_info) =
+			match (arbiter_pool_account_info, slot_hashes_account_info) {
+			(Some(arbiter_pool_account_info), Some(slot_hashes_account_info)) =>
+				(arbiter_pool_account_info, slot_hashes_account_info),
+			_ => {
+				msg!("ARBITERPOOL and SLOTHASHES accounts are required to select the arbiters.");
+				return Err(ProgramError::NotEnoughAccountKeys)
+			}
+		};
+
+		// Nobody linked to the operation can be picked, including the arbiters just replaced
+		let mut excluded = linked_parties(operation_account_data);
+		excluded.extend_from_slice(&replaced_arbiters);
+
+		let mut selected = select_arbiters(program_id, arbiter_pool_account_info, slot_hashes_account_info,
+			operation_account_info.key, &excluded, replaced)?.into_iter();
+
+		if operation_account_data.arbiter1.to_bytes() == ZERO_ACCOUNT {
+			operation_account_data.arbiter1 = selected.next().unwrap_or_default();
+		}
+
+		if operation_account_data.arbiter2.to_bytes() == ZERO_ACCOUNT {
+			operation_account_data.arbiter2 = selected.next().unwrap_or_default();
+		}
+
+		if operation_account_data.arbiter3.to_bytes() == ZERO_ACCOUNT {
+			operation_account_data.arbiter3 = selected.next().unwrap_or_default();
+		}
+
+		msg!("Arbiters selected: {}", replaced);
+	} else {
+		// The panel changed, so it must be approved again
+		operation_account_data.set_seller_approved(false);
+		operation_account_data.set_buyer_approved(false);
+	}
+
+	operation_account_data.set_vote_deadline(unix_timestamp.saturating_add(VOTING_PERIOD));
+
+	msg!("Arbiters replaced: {}", replaced);
+
+	Ok(())
+}
+
+/// Allows a Buyer/Seller to claim the dispute result, once the appeal period is over.
 /// Note that this function expects either SellerClaim or BuyerClaim [OperationAccount].
+/// The appeal bond, if any, goes to the claimant: it is returned to a successful appellant,
+/// and otherwise compensates the winner.
 ///
-///	Accounts:
-///	1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
 #[inline(never)]
 pub fn participant_claim(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
 
@@ -410,21 +886,22 @@ pub fn participant_claim(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progr
 	// ================ Enforce configuration rules section
 
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status == OperationStatus::SellerClaim {
+	if operation_account_data.status()? == OperationStatus::SellerClaim {
 
 		if *participant_account_info.key != operation_account_data.seller {
 			msg!("Invalid Seller account.");
 			return Err(ProgramError::InvalidAccountData)
 		}	
 
-    } else if operation_account_data.status == OperationStatus::BuyerClaim {
+    } else if operation_account_data.status()? == OperationStatus::BuyerClaim {
 
         if *participant_account_info.key != operation_account_data.buyer {
             msg!("Invalid Buyer account.");
@@ -436,22 +913,36 @@ pub fn participant_claim(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progr
 		return Err(ProgramError::UninitializedAccount)
 	}
 
+	// CHECK: Can the verdict still be appealed??? -> Reject
+	if current_timestamp() < operation_account_data.appeal_deadline() {
+		msg!("Appeal period still open.");
+		return Err(ProgramError::Custom(SCAError::AppealPeriodOpen as u32))
+	}
+
 
 	// ========================= Change state section
 
 	// Set internally; make sure everything not used is zeroed out anyway.
+	let payout = operation_account_data.value() + operation_account_data.appeal_bond();
 
 	// Set internally; make sure everything not used is zeroed out anyway.
     // Debit from_account and credit to_account
-    **operation_account_info.try_borrow_mut_lamports()? -= operation_account_data.value;
-    **participant_account_info.try_borrow_mut_lamports()? += operation_account_data.value;
-
-    operation_account_data.status = OperationStatus::DisputeResolved;
-	// Set externally
+    **operation_account_info.try_borrow_mut_lamports()? -= payout;
+    **participant_account_info.try_borrow_mut_lamports()? += payout;
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
+    operation_account_data.set_status(OperationStatus::DisputeResolved);
 	msg!("Dispute concluded.");
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+/// Returns the current time for evidence timestamps, or zero if the Clock is not available.
+pub(crate) fn current_timestamp() -> UnixTimestamp {
+	match Clock::get() {
+		Err(_e) => {
+			msg!("WARN: Could not get a valid Clock. Using zero time.");
+			0
+		},
+		Ok(clock) => clock.unix_timestamp,
+	}
+}
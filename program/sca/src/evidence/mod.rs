@@ -0,0 +1,134 @@
+//! # evidence
+//! Functionality related to the append-only evidence log of disputes
+//!
+//! The log is created when a dispute starts and only grows afterwards,
+//! so earlier submissions can never be replaced. See [EvidenceLogHeader].
+//!
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg, system_instruction::transfer,
+    program_error::ProgramError,
+    pubkey::Pubkey, system_program::check_id,
+	program::invoke,
+	sysvar::{
+		Sysvar,
+		rent::Rent,
+	}
+};
+
+use bytemuck::{bytes_of, cast_slice};
+
+use crate::scatype::{
+	EvidenceEntry, EvidenceLogHeader, SCAError,
+	EVIDENCE_LOG_VERSION, EVIDENCE_LOG_SEED, EVIDENCE_LOG_HEADER_LEN, EVIDENCE_ENTRY_LEN,
+};
+
+use crate::account::{
+	is_owned_and_writable,
+	create_pda_account,
+};
+
+/// Returns the address and bump seed of the evidence log of an operation.
+pub fn evidence_log_address(program_id: &Pubkey, operation: &Pubkey) -> (Pubkey, u8) {
+	Pubkey::find_program_address(&[EVIDENCE_LOG_SEED, operation.as_ref()], program_id)
+}
+
+/// Creates the evidence log PDA of an operation, holding the given initial entries.
+///
+/// Accounts:
+/// 1. PAYER - Pays for the rent of the log.
+/// 2. EVIDENCELOG - Not yet created, must be the PDA given by [evidence_log_address].
+/// 3. SYSTEM PROGRAM - Used to fund, allocate and assign the log.
+pub fn create_evidence_log<'a>(program_id: &Pubkey, operation: &Pubkey,
+	payer_account_info: &AccountInfo<'a>, evidence_log_account_info: &AccountInfo<'a>,
+	system_program_account_info: &AccountInfo<'a>, entries: &[EvidenceEntry]) -> ProgramResult {
+
+	let (evidence_log_pubkey, bump) = evidence_log_address(program_id, operation);
+
+	if *evidence_log_account_info.key != evidence_log_pubkey || !evidence_log_account_info.is_writable {
+		msg!("EVIDENCELOG account is not a valid account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let evidence_log_len = EVIDENCE_LOG_HEADER_LEN + entries.len() * EVIDENCE_ENTRY_LEN;
+
+	create_pda_account(program_id, payer_account_info, evidence_log_account_info, system_program_account_info,
+		evidence_log_len, &[EVIDENCE_LOG_SEED, operation.as_ref(), &[bump]])?;
+
+	// Save
+	let mut evidence_log_bytes = evidence_log_account_info.try_borrow_mut_data()?;
+	evidence_log_bytes[0] = EVIDENCE_LOG_VERSION;
+
+	let evidence_log_header = EvidenceLogHeader::load_mut(&mut evidence_log_bytes)?;
+	evidence_log_header.bump = bump;
+	evidence_log_header.operation = *operation;
+	evidence_log_header.set_count(entries.len() as u32);
+
+	evidence_log_bytes[EVIDENCE_LOG_HEADER_LEN..].copy_from_slice(cast_slice(entries));
+
+	Ok(())
+}
+
+/// Appends an entry to the evidence log of an operation, growing the account by one entry.
+///
+/// Accounts:
+/// 1. PAYER - Pays for the additional rent of the log.
+/// 2. EVIDENCELOG - The log of the operation, created by [create_evidence_log].
+/// 3. SYSTEM PROGRAM - Used to transfer the additional rent.
+pub fn append_evidence<'a>(program_id: &Pubkey, operation: &Pubkey,
+	payer_account_info: &AccountInfo<'a>, evidence_log_account_info: &AccountInfo<'a>,
+	system_program_account_info: &AccountInfo<'a>, entry: &EvidenceEntry) -> ProgramResult {
+
+	is_owned_and_writable(program_id, evidence_log_account_info, "EVIDENCELOG account is not a valid account.")?;
+
+	if !check_id(system_program_account_info.key) {
+		msg!("Invalid System program");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let count = {
+		let mut evidence_log_bytes = evidence_log_account_info.try_borrow_mut_data()?;
+		let evidence_log_len = evidence_log_bytes.len();
+		let evidence_log_header = EvidenceLogHeader::load_mut(&mut evidence_log_bytes)?;
+
+		if evidence_log_header.operation != *operation {
+			msg!("Evidence log belongs to another operation.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		if evidence_log_len != EVIDENCE_LOG_HEADER_LEN + evidence_log_header.count() as usize * EVIDENCE_ENTRY_LEN {
+			return Err(ProgramError::InvalidAccountData)
+		}
+
+		evidence_log_header.count()
+	};
+
+	let entry_offset = EVIDENCE_LOG_HEADER_LEN + count as usize * EVIDENCE_ENTRY_LEN;
+	let evidence_log_len = entry_offset + EVIDENCE_ENTRY_LEN;
+
+	let rent_exemption_balance = match Rent::get() {
+		Err(_e) => return Err(ProgramError::Custom(SCAError::RentError as u32)),
+		Ok(rent) => rent.minimum_balance(evidence_log_len),
+	};
+
+	let rent_shortfall = rent_exemption_balance.saturating_sub(evidence_log_account_info.lamports());
+
+	if rent_shortfall > 0 {
+		invoke(
+			&transfer(payer_account_info.key, evidence_log_account_info.key, rent_shortfall),
+			&[payer_account_info.clone(), evidence_log_account_info.clone(), system_program_account_info.clone()],
+		)?;
+	}
+
+	// The whole new entry is written below, no need to zero it first
+	evidence_log_account_info.realloc(evidence_log_len, false)?;
+
+	// Save
+	let mut evidence_log_bytes = evidence_log_account_info.try_borrow_mut_data()?;
+	evidence_log_bytes[entry_offset..].copy_from_slice(bytes_of(entry));
+	EvidenceLogHeader::load_mut(&mut evidence_log_bytes)?.set_count(count + 1);
+
+	Ok(())
+}
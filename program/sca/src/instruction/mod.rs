@@ -1,26 +1,33 @@
-//!	# instruction
-//!	This crate is tasked with the serialization of instructions sent to the program
+//! # instruction
+//! This crate is tasked with the serialization of instructions sent to the program
 //!
 //!  
 
 use solana_program::{
 	program_error::ProgramError,
-	pubkey::Pubkey,};
+	pubkey::Pubkey,
+	clock::UnixTimestamp,
+};
 use std::convert::TryInto;
-use std::default::Default;
 
 use crate::scatype::{
-	TokenVersion, 
+	TokenVersion, ArbiterSelection, MAX_MILESTONES, MAX_LINE_ITEMS,
 };
 
+use crate::content::ContentRef;
+
 /// The supported instruction set
 #[derive(PartialEq, Debug)]
 pub enum OperationInstruction {
 	/// Encapsulates the variables needed to create an operation.
-	/// Value, TokenVersion, IPFSCID 
-	InitializeOperation((u64, TokenVersion, [u8;46])),
+	/// Value, TokenVersion, ContentRef, ArbiterSelection (optional, Manual by default),
+	/// then optional fields encoded as a 0x00 flag when absent, or a 0x01 flag followed by the value, which can be left out when trailing:
+	/// intended buyer (anyone by default), delivery attestor (none by default),
+	/// inspection period in seconds (manual release by default)
+	InitializeOperation((u64, TokenVersion, ContentRef, ArbiterSelection, Option<Pubkey>, Option<Pubkey>, UnixTimestamp)),
 	/// Buyer registers his own address to indicate participation in the operation.
-	RegisterBuyer,
+	/// Value, TokenVersion and ContentRef the buyer agreed to, listing revision (optional)
+	RegisterBuyer((u64, TokenVersion, ContentRef, Option<u32>)),
 	/// Arbiter registers his own address to indicate participation in the operation.
 	RegisterArbiter,
 	/// Seller/Buyer indicates their approval of arbiters.
@@ -31,16 +38,90 @@ pub enum OperationInstruction {
 	BuyerRelease,
 	/// Seller cancels the operation and returns token to the buyer
 	SellerRefund,
-	/// A participant has requested dispute resolution
-	StartDispute,
-	/// Seller is providing additional info.
-	SellerAddInfo([u8;46]),
-	/// Buyer is providing additional info
-	BuyerAddInfo([u8;46]),
+	/// A participant has requested dispute resolution.
+	/// ContentRef of the complaint
+	StartDispute(ContentRef),
+	/// Seller is appending additional info to the evidence log.
+	SellerAddInfo(ContentRef),
+	/// Buyer is appending additional info to the evidence log.
+	BuyerAddInfo(ContentRef),
 	/// Arbiter votes on dispute, and if all votes are in, result is calculated.
 	ArbiterVote(bool),
 	/// Dispute winner claims token value
 	ParticipantClaim,
+	/// Upgrades an operation account to the current layout version.
+	MigrateOperation,
+	/// Arbiter is appending a ruling or comment to the evidence log.
+	ArbiterAddInfo(ContentRef),
+	/// Seller/Buyer rejects a registered arbiter before approving them.
+	/// Pubkey of the arbiter
+	RejectArbiter(Pubkey),
+	/// Seller/Buyer replaces the arbiters who did not vote in time.
+	ReplaceArbiters,
+	/// Seller/Buyer invites an arbiter, who can then register.
+	/// Pubkey of the arbiter
+	InviteArbiter(Pubkey),
+	/// Arbiter creates or updates their profile.
+	/// Active, ContentRef of the bio
+	RegisterArbiterProfile((bool, ContentRef)),
+	/// Arbiter adds lamports to their stake.
+	/// Lamports to stake
+	StakeArbiter(u64),
+	/// Arbiter requests the unstake, or withdraws once the cooldown is over.
+	UnstakeArbiter,
+	/// Arbiter joins the pool used for random selection.
+	JoinArbiterPool,
+	/// Arbiter leaves the pool used for random selection.
+	LeaveArbiterPool,
+	/// Losing Seller/Buyer appeals the verdict, posting the bond.
+	/// Content reference of the grounds for the appeal
+	AppealVerdict(ContentRef),
+	/// Seller/Buyer proposes a split of the token value.
+	/// Lamports for the buyer, the seller gets the rest
+	ProposeSettlement(u64),
+	/// The other party accepts the proposed split, which is paid out.
+	/// Lamports for the buyer, as proposed
+	AcceptSettlement(u64),
+	/// Seller refunds part of the deposit, the operation stays open for the rest.
+	/// Lamports to refund
+	SellerPartialRefund(u64),
+	/// Seller splits the value in milestones, before a buyer registers.
+	/// Amount and content reference of each milestone
+	DefineMilestones(Vec<(u64, ContentRef)>),
+	/// Buyer releases a single milestone to the seller.
+	/// Index of the milestone
+	ReleaseMilestone(u8),
+	/// Seller splits the order in line items, before a buyer registers. Their total becomes the value.
+	/// Unit price, quantity and content reference of each line item
+	DefineLineItems(Vec<(u64, u32, ContentRef)>),
+	/// Seller refunds units of a single line item.
+	/// Index of the line item, units to refund
+	RefundLineItem(u8, u32),
+	/// Prospective buyer offers a price, before registering.
+	/// Lamports offered
+	ProposePrice(u64),
+	/// Seller accepts an offer, registering its buyer at the offered price.
+	/// Index of the offer in the offer log
+	AcceptOffer(u32),
+	/// Seller replaces the registered buyer, before the deposit.
+	/// Address of the new buyer
+	ReassignBuyer(Pubkey),
+	/// Seller changes the price and item of the listing, before a buyer registers.
+	/// Value, ContentRef
+	UpdateListing((u64, ContentRef)),
+	/// Buyer registers, approves the arbiters designated by the seller and deposits at once.
+	/// Value, TokenVersion and ContentRef the buyer agreed to, the three arbiters, listing revision (optional)
+	RegisterBuyerAndDeposit((u64, TokenVersion, ContentRef, [Pubkey; 3], Option<u32>)),
+	/// Seller records the shipment of the item.
+	/// ContentRef of the tracking
+	MarkShipped(ContentRef),
+	/// Buyer confirms receiving the item.
+	ConfirmReceived,
+	/// Anyone submits the delivery signed by the attestor, verified by the preceding Ed25519 program instruction.
+	/// Delivered, time of delivery
+	AttestDelivery((bool, UnixTimestamp)),
+	/// Anyone releases the value to the seller once the inspection period is over.
+	FinalizeRelease,
 }
 
 impl OperationInstruction {
@@ -53,26 +134,53 @@ impl OperationInstruction {
 		
 		// Check the correct instruction_data size and valid values for each instruction
         match instruction {
-            0 => OperationInstruction::initialize_operation_builder(&data),
-			1 => Ok(OperationInstruction::RegisterBuyer),
+            0 => OperationInstruction::initialize_operation_builder(data),
+			1 => OperationInstruction::register_buyer_builder(data),
 			2 => Ok(OperationInstruction::RegisterArbiter),
-			3 => OperationInstruction::participant_approves_arbiters_builder(&data),
+			3 => OperationInstruction::participant_approves_arbiters_builder(data),
 			4 => Ok(OperationInstruction::BuyerDeposit),
 			5 => Ok(OperationInstruction::BuyerRelease),
 			6 => Ok(OperationInstruction::SellerRefund),
-			7 => Ok(OperationInstruction::StartDispute),
-			8 => OperationInstruction::seller_add_info_builder(&data),
-			9 => OperationInstruction::buyer_add_info_builder(&data),
-			10 => OperationInstruction::arbiter_vote_builder(&data),
+			7 => OperationInstruction::start_dispute_builder(data),
+			8 => OperationInstruction::seller_add_info_builder(data),
+			9 => OperationInstruction::buyer_add_info_builder(data),
+			10 => OperationInstruction::arbiter_vote_builder(data),
 			11 => Ok(OperationInstruction::ParticipantClaim),
-            _ => return Err(ProgramError::InvalidInstructionData),
+			12 => Ok(OperationInstruction::MigrateOperation),
+			13 => OperationInstruction::arbiter_add_info_builder(data),
+			14 => OperationInstruction::reject_arbiter_builder(data),
+			15 => Ok(OperationInstruction::ReplaceArbiters),
+			16 => OperationInstruction::invite_arbiter_builder(data),
+			17 => OperationInstruction::register_arbiter_profile_builder(data),
+			18 => OperationInstruction::stake_arbiter_builder(data),
+			19 => Ok(OperationInstruction::UnstakeArbiter),
+			20 => Ok(OperationInstruction::JoinArbiterPool),
+			21 => Ok(OperationInstruction::LeaveArbiterPool),
+			22 => OperationInstruction::appeal_verdict_builder(data),
+			23 => OperationInstruction::propose_settlement_builder(data),
+			24 => OperationInstruction::accept_settlement_builder(data),
+			25 => OperationInstruction::seller_partial_refund_builder(data),
+			26 => OperationInstruction::define_milestones_builder(data),
+			27 => OperationInstruction::release_milestone_builder(data),
+			28 => OperationInstruction::define_line_items_builder(data),
+			29 => OperationInstruction::refund_line_item_builder(data),
+			30 => OperationInstruction::propose_price_builder(data),
+			31 => OperationInstruction::accept_offer_builder(data),
+			32 => OperationInstruction::reassign_buyer_builder(data),
+			33 => OperationInstruction::update_listing_builder(data),
+			34 => OperationInstruction::register_buyer_and_deposit_builder(data),
+			35 => OperationInstruction::mark_shipped_builder(data),
+			36 => Ok(OperationInstruction::ConfirmReceived),
+			37 => OperationInstruction::attest_delivery_builder(data),
+			38 => Ok(OperationInstruction::FinalizeRelease),
+            _ => Err(ProgramError::InvalidInstructionData),
         }
     }
 
 	/// Returns an [OperationInstruction::InitializeOperation] with appropriate variables already validated and filled
 	fn initialize_operation_builder(data: &[u8]) -> Result<Self, ProgramError> {
 
-		if 	data.len() != 55 {			
+		if 	data.len() < 11 {			
 			return Err(ProgramError::InvalidInstructionData);
 		}
 
@@ -88,25 +196,126 @@ impl OperationInstruction {
 
 		// ================================= 8: token_version
 		// Expecting 1 byte in &data
-		let token_version:TokenVersion;
+		let token_version: TokenVersion = match data[8] {
+			0x00 => TokenVersion::Sol,
+			_ => return Err(ProgramError::InvalidInstructionData),
+		};
 
-		match data[8] {
-			0x00 => {
-				token_version = TokenVersion::Sol;
-			}
+		// ================================= 9: item_content
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (item_content, rest) = ContentRef::unpack(&data[9..])?;
+
+		// ================================= 9 + content length: arbiter_selection
+		// Expecting 0 or 1 byte in &data
+		let (selection, rest) = match rest.split_first() {
+			None => (0x00, rest),
+			Some((selection, rest)) => (*selection, rest),
+		};
+
+		let arbiter_selection: ArbiterSelection = match selection {
+			0x00 => ArbiterSelection::Manual,
+			0x01 => ArbiterSelection::Random,
 			_ => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		// ================================= 10 + content length: intended_buyer
+		// Expecting 0 bytes, a 0x00 flag for anyone, or a 0x01 flag followed by 32 bytes in &data
+		let (intended_buyer, rest) = match OperationInstruction::split_option(rest, 32)? {
+			(None, rest) => (None, rest),
+			(Some(b), rest) => (Some(OperationInstruction::unpack_pubkey(b)?), rest),
+		};
+
+		// ================================= after intended_buyer: attestor
+		// Expecting 0 bytes, a 0x00 flag for none, or a 0x01 flag followed by 32 bytes in &data
+		let (attestor, rest) = match OperationInstruction::split_option(rest, 32)? {
+			(None, rest) => (None, rest),
+			(Some(b), rest) => (Some(OperationInstruction::unpack_pubkey(b)?), rest),
+		};
+
+		// ================================= after attestor: inspection_period
+		// Expecting 0 bytes, a 0x00 flag for manual release, or a 0x01 flag followed by 8 bytes in &data
+		let (inspection_period, rest):(UnixTimestamp, &[u8]) = match OperationInstruction::split_option(rest, 8)? {
+			(None, rest) => (0, rest),
+			(Some(b), rest) => match b.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => (i64::from_le_bytes(b), rest),
+			},
+		};
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::InitializeOperation((value, token_version, item_content, arbiter_selection, intended_buyer, attestor, inspection_period)))
+	}
+
+	/// Splits a trailing optional field encoded as a presence flag followed by `len` bytes, like borsh encodes an [Option].
+	/// A missing flag is read as absent too, so that trailing optional fields can be left out.
+	fn split_option(data: &[u8], len: usize) -> Result<(Option<&[u8]>, &[u8]), ProgramError> {
+
+		match data.split_first() {
+			None => Ok((None, data)),
+			Some((0x00, rest)) => Ok((None, rest)),
+			Some((0x01, rest)) if rest.len() >= len => Ok((Some(&rest[..len]), &rest[len..])),
+			_ => Err(ProgramError::InvalidInstructionData),
 		}
+	}
+
+	/// Returns the [Pubkey] of an optional field that is present, rejecting the zeroed key which means nobody
+	fn unpack_pubkey(data: &[u8]) -> Result<Pubkey, ProgramError> {
+
+		match Pubkey::try_from(data) {
+			Err(_e) => Err(ProgramError::InvalidInstructionData),
+			Ok(b) if b == Pubkey::default() => Err(ProgramError::InvalidInstructionData),
+			Ok(b) => Ok(b),
+		}
+	}
 
-		// ================================= 8: ipfs_cid
-		// Expecting 46 bytes in &data
-		let ipfs_hash_bytes:[u8;46] = match 
-			data[9..55]
+	/// Returns an [OperationInstruction::RegisterBuyer] with appropriate variables already validated and filled
+	fn register_buyer_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() < 11 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: value
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data[0..8]
 			.try_into() {
 				Err(_e) => return Err(ProgramError::InvalidInstructionData),
 				Ok(b) => b,
 		};
+		let value:u64 = u64::from_le_bytes(data_bytes);
+
+		// ================================= 8: token_version
+		// Expecting 1 byte in &data
+		let token_version: TokenVersion = match data[8] {
+			0x00 => TokenVersion::Sol,
+			_ => return Err(ProgramError::InvalidInstructionData),
+		};
 
-		Ok(OperationInstruction::InitializeOperation((value, token_version, ipfs_hash_bytes)))
+		// ================================= 9: item_content
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (item_content, rest) = ContentRef::unpack(&data[9..])?;
+
+		// ================================= 9 + content length: listing_revision
+		// Expecting 0 or 4 bytes in &data
+		let listing_revision = match rest.len() {
+			0 => None,
+			4 => {
+				let data_bytes:[u8;4] = match 
+					rest
+					.try_into() {
+						Err(_e) => return Err(ProgramError::InvalidInstructionData),
+						Ok(b) => b,
+				};
+				Some(u32::from_le_bytes(data_bytes))
+			},
+			_ => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		Ok(OperationInstruction::RegisterBuyer((value, token_version, item_content, listing_revision)))
 	}
 
 	/// Returns an [OperationInstruction::ParticipantApproves] with appropriate variables already validated and filled
@@ -118,58 +327,146 @@ impl OperationInstruction {
 
 		// ================================= 0: is_seller
 		// Expecting 1 byte in &data
-		let is_seller: bool;
-
-		match data[0] {
-			0x00 => {
-				is_seller = false;
-			}
-			0x01 => {
-				is_seller = true;
-			}
+		let is_seller: bool = match data[0] {
+			0x00 => false,
+			0x01 => true,
 			_ => return Err(ProgramError::InvalidInstructionData),
-		}
+		};
 
 		Ok(OperationInstruction::ParticipantApprovesArbiters(is_seller))
 	}
 
+	/// Returns an [OperationInstruction::StartDispute] with appropriate variables already validated and filled
+	fn start_dispute_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: reason
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (reason, rest) = ContentRef::unpack(data)?;
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::StartDispute(reason))
+	}
+
 	/// Returns an [OperationInstruction::SellerAddInfo] with appropriate variables already validated and filled
 	fn seller_add_info_builder(data: &[u8]) -> Result<Self, ProgramError> {
 
-		if 	data.len() != 46 {			
+		// ================================= 0: content_ext
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (content_ext, rest) = ContentRef::unpack(data)?;
+
+		if !rest.is_empty() {
 			return Err(ProgramError::InvalidInstructionData);
 		}
 
-		// ================================= 0: ipfs_cid
-		// Expecting 46 bytes in &data
-		let ipfs_hash_bytes:[u8;46] = match 
-			data[0..46]
-			.try_into() {
-				Err(_e) => return Err(ProgramError::InvalidInstructionData),
-				Ok(b) => b,
-		};
-
-		Ok(OperationInstruction::SellerAddInfo(ipfs_hash_bytes))
+		Ok(OperationInstruction::SellerAddInfo(content_ext))
 	}
 
 	/// Returns an [OperationInstruction::BuyerAddInfo] with appropriate variables already validated and filled
 	fn buyer_add_info_builder(data: &[u8]) -> Result<Self, ProgramError> {
 
-		if 	data.len() != 46 {			
+		// ================================= 0: content_ext
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (content_ext, rest) = ContentRef::unpack(data)?;
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::BuyerAddInfo(content_ext))
+	}	
+
+	/// Returns an [OperationInstruction::ArbiterAddInfo] with appropriate variables already validated and filled
+	fn arbiter_add_info_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: content_ext
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (content_ext, rest) = ContentRef::unpack(data)?;
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::ArbiterAddInfo(content_ext))
+	}
+
+	/// Returns an [OperationInstruction::RejectArbiter] with appropriate variables already validated and filled
+	fn reject_arbiter_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() != 32 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: arbiter
+		// Expecting 32 bytes in &data
+		let arbiter = match Pubkey::try_from(data) {
+			Err(_e) => return Err(ProgramError::InvalidInstructionData),
+			Ok(b) => b,
+		};
+
+		Ok(OperationInstruction::RejectArbiter(arbiter))
+	}
+
+	/// Returns an [OperationInstruction::InviteArbiter] with appropriate variables already validated and filled
+	fn invite_arbiter_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() != 32 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: arbiter
+		// Expecting 32 bytes in &data
+		let arbiter = match Pubkey::try_from(data) {
+			Err(_e) => return Err(ProgramError::InvalidInstructionData),
+			Ok(b) => b,
+		};
+
+		Ok(OperationInstruction::InviteArbiter(arbiter))
+	}
+
+	/// Returns an [OperationInstruction::RegisterArbiterProfile] with appropriate variables already validated and filled
+	fn register_arbiter_profile_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.is_empty() {			
 			return Err(ProgramError::InvalidInstructionData);
 		}
 
-		// ================================= 0: ipfs_cid
-		// Expecting 46 bytes in &data
-		let ipfs_hash_bytes:[u8;46] = match 
-			data[0..46]
+		// ================================= 0: active
+		// Expecting 1 byte in &data
+		let active: bool = match data[0] {
+			0x00 => false,
+			0x01 => true,
+			_ => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		// ================================= 1: bio
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (bio, rest) = ContentRef::unpack(&data[1..])?;
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::RegisterArbiterProfile((active, bio)))
+	}
+
+	/// Returns an [OperationInstruction::StakeArbiter] with appropriate variables already validated and filled
+	fn stake_arbiter_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: amount
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data
 			.try_into() {
 				Err(_e) => return Err(ProgramError::InvalidInstructionData),
 				Ok(b) => b,
 		};
+		let amount:u64 = u64::from_le_bytes(data_bytes);
 
-		Ok(OperationInstruction::BuyerAddInfo(ipfs_hash_bytes))
-	}	
+		Ok(OperationInstruction::StakeArbiter(amount))
+	}
 
 	/// Returns an [OperationInstruction::ArbiterVote] with appropriate variables already validated and filled
 	fn arbiter_vote_builder(data: &[u8]) -> Result<Self, ProgramError> {
@@ -180,18 +477,794 @@ impl OperationInstruction {
 
 		// ================================= 0: vote
 		// Expecting 1 byte in &data
-		let vote: bool;
+		let vote: bool = match data[0] {
+			0x00 => false,
+			0x01 => true,
+			_ => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		Ok(OperationInstruction::ArbiterVote(vote))
+	}
+
+	/// Returns an [OperationInstruction::AppealVerdict] with appropriate variables already validated and filled
+	fn appeal_verdict_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: grounds
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (grounds, rest) = ContentRef::unpack(data)?;
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::AppealVerdict(grounds))
+	}
+
+	/// Returns an [OperationInstruction::ProposeSettlement] with appropriate variables already validated and filled
+	fn propose_settlement_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: buyer_amount
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let buyer_amount:u64 = u64::from_le_bytes(data_bytes);
+
+		Ok(OperationInstruction::ProposeSettlement(buyer_amount))
+	}
+
+	/// Returns an [OperationInstruction::AcceptSettlement] with appropriate variables already validated and filled
+	fn accept_settlement_builder(data: &[u8]) -> Result<Self, ProgramError> {
 
-		match data[0] {
-			0x00 => {
-				vote = false;
+		// ================================= 0: buyer_amount
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let buyer_amount:u64 = u64::from_le_bytes(data_bytes);
+
+		Ok(OperationInstruction::AcceptSettlement(buyer_amount))
+	}
+	/// Returns an [OperationInstruction::SellerPartialRefund] with appropriate variables already validated and filled
+	fn seller_partial_refund_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: amount
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let amount:u64 = u64::from_le_bytes(data_bytes);
+
+		Ok(OperationInstruction::SellerPartialRefund(amount))
+	}
+	/// Returns an [OperationInstruction::DefineMilestones] with appropriate variables already validated and filled
+	fn define_milestones_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: count
+		// Expecting 1 byte in &data
+		let (count, mut rest) = match data.split_first() {
+			Some((count, rest)) => (*count as usize, rest),
+			None => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		if count == 0 || count > MAX_MILESTONES {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 1: milestones
+		// Expecting, for each one, 8 bytes of amount + 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let mut milestones = Vec::with_capacity(count);
+
+		for _ in 0..count {
+			if rest.len() < 8 {
+				return Err(ProgramError::InvalidInstructionData);
 			}
-			0x01 => {
-				vote = true;
+
+			let (amount_bytes, content_bytes) = rest.split_at(8);
+			let amount:u64 = u64::from_le_bytes(amount_bytes.try_into().map_err(|_e| ProgramError::InvalidInstructionData)?);
+
+			let (content, content_rest) = ContentRef::unpack(content_bytes)?;
+
+			milestones.push((amount, content));
+			rest = content_rest;
+		}
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::DefineMilestones(milestones))
+	}
+
+	/// Returns an [OperationInstruction::ReleaseMilestone] with appropriate variables already validated and filled
+	fn release_milestone_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() != 1 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: index
+		// Expecting 1 byte in &data
+		Ok(OperationInstruction::ReleaseMilestone(data[0]))
+	}
+
+	/// Returns an [OperationInstruction::DefineLineItems] with appropriate variables already validated and filled
+	fn define_line_items_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: count
+		// Expecting 1 byte in &data
+		let (count, mut rest) = match data.split_first() {
+			Some((count, rest)) => (*count as usize, rest),
+			None => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		if count == 0 || count > MAX_LINE_ITEMS {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 1: line items
+		// Expecting, for each one, 8 bytes of unit price + 4 bytes of quantity + 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let mut line_items = Vec::with_capacity(count);
+
+		for _ in 0..count {
+			if rest.len() < 12 {
+				return Err(ProgramError::InvalidInstructionData);
 			}
+
+			let (price_bytes, quantity_bytes) = rest.split_at(8);
+			let unit_price:u64 = u64::from_le_bytes(price_bytes.try_into().map_err(|_e| ProgramError::InvalidInstructionData)?);
+
+			let (quantity_bytes, content_bytes) = quantity_bytes.split_at(4);
+			let quantity:u32 = u32::from_le_bytes(quantity_bytes.try_into().map_err(|_e| ProgramError::InvalidInstructionData)?);
+
+			let (content, content_rest) = ContentRef::unpack(content_bytes)?;
+
+			line_items.push((unit_price, quantity, content));
+			rest = content_rest;
+		}
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::DefineLineItems(line_items))
+	}
+
+	/// Returns an [OperationInstruction::RefundLineItem] with appropriate variables already validated and filled
+	fn refund_line_item_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() != 5 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: index
+		// Expecting 1 byte in &data
+		let index = data[0];
+
+		// ================================= 1: quantity
+		// Expecting 4 bytes in &data
+		let quantity_bytes:[u8;4] = match data[1..5].try_into() {
+			Ok(bytes) => bytes,
+			Err(_) => return Err(ProgramError::InvalidInstructionData),
+		};
+		let quantity = u32::from_le_bytes(quantity_bytes);
+
+		Ok(OperationInstruction::RefundLineItem(index, quantity))
+	}
+
+	/// Returns an [OperationInstruction::ProposePrice] with appropriate variables already validated and filled
+	fn propose_price_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: amount
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let amount:u64 = u64::from_le_bytes(data_bytes);
+
+		Ok(OperationInstruction::ProposePrice(amount))
+	}
+
+	/// Returns an [OperationInstruction::AcceptOffer] with appropriate variables already validated and filled
+	fn accept_offer_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: index
+		// Expecting 4 bytes in &data
+		let data_bytes:[u8;4] = match 
+			data
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let index:u32 = u32::from_le_bytes(data_bytes);
+
+		Ok(OperationInstruction::AcceptOffer(index))
+	}
+
+	/// Returns an [OperationInstruction::ReassignBuyer] with appropriate variables already validated and filled
+	fn reassign_buyer_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() != 32 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: buyer
+		// Expecting 32 bytes in &data
+		let buyer = match Pubkey::try_from(data) {
+			Err(_e) => return Err(ProgramError::InvalidInstructionData),
+			Ok(b) => b,
+		};
+
+		Ok(OperationInstruction::ReassignBuyer(buyer))
+	}
+
+	/// Returns an [OperationInstruction::UpdateListing] with appropriate variables already validated and filled
+	fn update_listing_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() < 10 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: value
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data[0..8]
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let value:u64 = u64::from_le_bytes(data_bytes);
+
+		// ================================= 8: item_content
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (item_content, rest) = ContentRef::unpack(&data[8..])?;
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::UpdateListing((value, item_content)))
+	}
+
+	/// Returns an [OperationInstruction::RegisterBuyerAndDeposit] with appropriate variables already validated and filled
+	fn register_buyer_and_deposit_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() < 107 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: value
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data[0..8]
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let value:u64 = u64::from_le_bytes(data_bytes);
+
+		// ================================= 8: token_version
+		// Expecting 1 byte in &data
+		let token_version: TokenVersion = match data[8] {
+			0x00 => TokenVersion::Sol,
 			_ => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		// ================================= 9: item_content
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (item_content, rest) = ContentRef::unpack(&data[9..])?;
+
+		// ================================= 9 + content length: arbiters
+		// Expecting 3 x 32 bytes in &data
+		if rest.len() < 96 {
+			return Err(ProgramError::InvalidInstructionData);
 		}
 
-		Ok(OperationInstruction::ArbiterVote(vote))
+		let (arbiter_bytes, rest) = rest.split_at(96);
+		let mut arbiters = [Pubkey::default(); 3];
+
+		for (arbiter, bytes) in arbiters.iter_mut().zip(arbiter_bytes.chunks_exact(32)) {
+			*arbiter = match Pubkey::try_from(bytes) {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+			};
+		}
+
+		// ================================= 105 + content length: listing_revision
+		// Expecting 0 or 4 bytes in &data
+		let listing_revision = match rest.len() {
+			0 => None,
+			4 => {
+				let data_bytes:[u8;4] = match 
+					rest
+					.try_into() {
+						Err(_e) => return Err(ProgramError::InvalidInstructionData),
+						Ok(b) => b,
+				};
+				Some(u32::from_le_bytes(data_bytes))
+			},
+			_ => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		Ok(OperationInstruction::RegisterBuyerAndDeposit((value, token_version, item_content, arbiters, listing_revision)))
+	}
+
+	/// Returns an [OperationInstruction::MarkShipped] with appropriate variables already validated and filled
+	fn mark_shipped_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		// ================================= 0: tracking_content
+		// Expecting 1 byte of kind + 1 byte of length + up to 128 bytes of payload in &data
+		let (tracking_content, rest) = ContentRef::unpack(data)?;
+
+		if !rest.is_empty() {
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		Ok(OperationInstruction::MarkShipped(tracking_content))
+	}
+
+	/// Returns an [OperationInstruction::AttestDelivery] with appropriate variables already validated and filled
+	fn attest_delivery_builder(data: &[u8]) -> Result<Self, ProgramError> {
+
+		if 	data.len() != 9 {			
+			return Err(ProgramError::InvalidInstructionData);
+		}
+
+		// ================================= 0: delivered
+		// Expecting 1 byte in &data
+		let delivered: bool = match data[0] {
+			0x00 => false,
+			0x01 => true,
+			_ => return Err(ProgramError::InvalidInstructionData),
+		};
+
+		// ================================= 1: delivered_at
+		// Expecting 8 bytes in &data
+		let data_bytes:[u8;8] = match 
+			data[1..9]
+			.try_into() {
+				Err(_e) => return Err(ProgramError::InvalidInstructionData),
+				Ok(b) => b,
+		};
+		let delivered_at:UnixTimestamp = i64::from_le_bytes(data_bytes);
+
+		Ok(OperationInstruction::AttestDelivery((delivered, delivered_at)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::content::{ContentKind, ARWEAVE_TX_ID_LEN};
+
+	const SELLER_TERMS: [u8; ARWEAVE_TX_ID_LEN] = [7; ARWEAVE_TX_ID_LEN];
+
+	fn arweave_content() -> Vec<u8> {
+		[&[ContentKind::Arweave as u8, ARWEAVE_TX_ID_LEN as u8][..], &SELLER_TERMS].concat()
+	}
+
+	fn initialize_data(optional: &[u8]) -> Vec<u8> {
+		[&[0x00][..], &1000u64.to_le_bytes(), &[TokenVersion::Sol as u8], &arweave_content(), optional].concat()
+	}
+
+	fn some(value: &[u8]) -> Vec<u8> {
+		[&[0x01][..], value].concat()
+	}
+
+	fn initialize(value: u64, selection: ArbiterSelection, intended_buyer: Option<Pubkey>, attestor: Option<Pubkey>,
+		inspection_period: UnixTimestamp) -> OperationInstruction {
+		OperationInstruction::InitializeOperation((value, TokenVersion::Sol, ContentRef::Arweave(SELLER_TERMS), selection,
+			intended_buyer, attestor, inspection_period))
+	}
+
+	#[test]
+	fn initialize_operation_defaults_every_optional_field() {
+		let expected = initialize(1000, ArbiterSelection::Manual, None, None, 0);
+
+		assert_eq!(OperationInstruction::unpack_instruction_data(&initialize_data(&[])), Ok(expected));
+	}
+
+	#[test]
+	fn initialize_operation_reads_every_optional_field() {
+		let buyer = Pubkey::new_from_array([2; 32]);
+		let attestor = Pubkey::new_from_array([9; 32]);
+		let data = initialize_data(&[&[ArbiterSelection::Random as u8][..], &some(&buyer.to_bytes()),
+			&some(&attestor.to_bytes()), &some(&3600i64.to_le_bytes())].concat());
+
+		let expected = initialize(1000, ArbiterSelection::Random, Some(buyer), Some(attestor), 3600);
+		assert_eq!(OperationInstruction::unpack_instruction_data(&data), Ok(expected));
+	}
+
+	#[test]
+	fn initialize_operation_reads_absent_flags_like_omitted_fields() {
+		let attestor = Pubkey::new_from_array([9; 32]);
+		let expected = initialize(1000, ArbiterSelection::Manual, None, None, 0);
+
+		assert_eq!(OperationInstruction::unpack_instruction_data(&initialize_data(&[0x00, 0x00, 0x00, 0x00])), Ok(expected));
+
+		// Anyone can register, yet the delivery is attested
+		let data = initialize_data(&[&[0x00, 0x00][..], &some(&attestor.to_bytes())].concat());
+		let expected = initialize(1000, ArbiterSelection::Manual, None, Some(attestor), 0);
+		assert_eq!(OperationInstruction::unpack_instruction_data(&data), Ok(expected));
+
+		// Trailing fields can be left out
+		let data = initialize_data(&[&[0x01][..], &some(&[2; 32])].concat());
+		let expected = initialize(1000, ArbiterSelection::Random, Some(Pubkey::new_from_array([2; 32])), None, 0);
+		assert_eq!(OperationInstruction::unpack_instruction_data(&data), Ok(expected));
+	}
+
+	#[test]
+	fn initialize_operation_rejects_present_zeroed_keys() {
+		let attestor = Pubkey::new_from_array([9; 32]);
+
+		let zeroed_buyer = initialize_data(&[&[0x00][..], &some(&[0; 32])].concat());
+		assert_eq!(OperationInstruction::unpack_instruction_data(&zeroed_buyer), Err(ProgramError::InvalidInstructionData));
+
+		let zeroed_attestor = initialize_data(&[&[0x00, 0x00][..], &some(&[0; 32]), &some(&3600i64.to_le_bytes())].concat());
+		assert_eq!(OperationInstruction::unpack_instruction_data(&zeroed_attestor), Err(ProgramError::InvalidInstructionData));
+
+		let zeroed_buyer_with_attestor = initialize_data(&[&[0x00][..], &some(&[0; 32]), &some(&attestor.to_bytes())].concat());
+		assert_eq!(OperationInstruction::unpack_instruction_data(&zeroed_buyer_with_attestor), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn initialize_operation_rejects_malformed_optional_fields() {
+		let malformed: [Vec<u8>; 6] = [
+			// Unknown arbiter selection
+			initialize_data(&[0x02]),
+			// Unknown presence flag
+			initialize_data(&[0x00, 0x02]),
+			// Truncated intended buyer
+			initialize_data(&[&[0x00][..], &some(&[2; 31])].concat()),
+			// Truncated inspection period
+			initialize_data(&[&[0x00, 0x00, 0x00][..], &some(&[1; 7])].concat()),
+			// Trailing bytes
+			initialize_data(&[&[0x00, 0x00, 0x00][..], &some(&3600i64.to_le_bytes()), &[0x00]].concat()),
+			// Legacy encoding without flags
+			initialize_data(&[&[0x00][..], &[2; 32]].concat()),
+		];
+
+		for data in malformed {
+			assert_eq!(OperationInstruction::unpack_instruction_data(&data), Err(ProgramError::InvalidInstructionData));
+		}
+	}
+
+	#[test]
+	fn initialize_operation_rejects_truncated_terms() {
+		let data = initialize_data(&[]);
+
+		assert_eq!(OperationInstruction::unpack_instruction_data(&data[..10]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(OperationInstruction::unpack_instruction_data(&data[..data.len() - 1]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	fn unpack(tag: u8, data: &[u8]) -> Result<OperationInstruction, ProgramError> {
+		OperationInstruction::unpack_instruction_data(&[&[tag][..], data].concat())
+	}
+
+	#[test]
+	fn unpacks_instructions_without_data_by_tag() {
+		assert_eq!(unpack(12, &[]), Ok(OperationInstruction::MigrateOperation));
+		assert_eq!(unpack(2, &[]), Ok(OperationInstruction::RegisterArbiter));
+		assert_eq!(unpack(4, &[]), Ok(OperationInstruction::BuyerDeposit));
+		assert_eq!(unpack(5, &[]), Ok(OperationInstruction::BuyerRelease));
+		assert_eq!(unpack(6, &[]), Ok(OperationInstruction::SellerRefund));
+		assert_eq!(unpack(11, &[]), Ok(OperationInstruction::ParticipantClaim));
+	}
+
+	#[test]
+	fn rejects_missing_and_unknown_tags() {
+		assert_eq!(OperationInstruction::unpack_instruction_data(&[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(39, &[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(0xff, &[]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn unpacks_flags_only_as_zero_or_one() {
+		assert_eq!(unpack(3, &[0x01]), Ok(OperationInstruction::ParticipantApprovesArbiters(true)));
+		assert_eq!(unpack(10, &[0x00]), Ok(OperationInstruction::ArbiterVote(false)));
+
+		for tag in [3, 10] {
+			assert_eq!(unpack(tag, &[]), Err(ProgramError::InvalidInstructionData));
+			assert_eq!(unpack(tag, &[0x02]), Err(ProgramError::InvalidInstructionData));
+			assert_eq!(unpack(tag, &[0x01, 0x00]), Err(ProgramError::InvalidInstructionData));
+		}
+	}
+
+	#[test]
+	fn add_info_unpacks_a_single_content_ref() {
+		let content = arweave_content();
+
+		assert_eq!(unpack(8, &content), Ok(OperationInstruction::SellerAddInfo(ContentRef::Arweave(SELLER_TERMS))));
+		assert_eq!(unpack(9, &content), Ok(OperationInstruction::BuyerAddInfo(ContentRef::Arweave(SELLER_TERMS))));
+		assert_eq!(unpack(13, &content), Ok(OperationInstruction::ArbiterAddInfo(ContentRef::Arweave(SELLER_TERMS))));
+
+		for tag in [8, 9, 13] {
+			assert_eq!(unpack(tag, &[]), Err(ProgramError::InvalidInstructionData));
+			assert_eq!(unpack(tag, &[&content[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+		}
+	}
+
+	#[test]
+	fn start_dispute_unpacks_the_reason() {
+		let content = arweave_content();
+
+		assert_eq!(unpack(7, &content), Ok(OperationInstruction::StartDispute(ContentRef::Arweave(SELLER_TERMS))));
+		assert_eq!(unpack(7, &[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(7, &[&content[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn reject_arbiter_unpacks_the_arbiter() {
+		let arbiter = Pubkey::new_from_array([3; 32]);
+
+		assert_eq!(unpack(14, &arbiter.to_bytes()), Ok(OperationInstruction::RejectArbiter(arbiter)));
+		assert_eq!(unpack(14, &[3; 31]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(14, &[3; 33]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(15, &[]), Ok(OperationInstruction::ReplaceArbiters));
+	}
+
+	#[test]
+	fn invite_arbiter_unpacks_the_arbiter() {
+		let arbiter = Pubkey::new_from_array([3; 32]);
+
+		assert_eq!(unpack(16, &arbiter.to_bytes()), Ok(OperationInstruction::InviteArbiter(arbiter)));
+		assert_eq!(unpack(16, &[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(16, &[3; 33]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn register_arbiter_profile_unpacks_the_activity_and_bio() {
+		let data = [&[0x01][..], &arweave_content()].concat();
+
+		assert_eq!(unpack(17, &data), Ok(OperationInstruction::RegisterArbiterProfile((true, ContentRef::Arweave(SELLER_TERMS)))));
+		assert_eq!(unpack(17, &[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(17, &[0x01]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(17, &[&[0x02][..], &arweave_content()].concat()), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(17, &[&data[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn stake_arbiter_unpacks_the_amount() {
+		assert_eq!(unpack(18, &5_000u64.to_le_bytes()), Ok(OperationInstruction::StakeArbiter(5_000)));
+		assert_eq!(unpack(18, &[0x01; 7]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(18, &[0x01; 9]), Err(ProgramError::InvalidInstructionData));
+
+		assert_eq!(unpack(19, &[]), Ok(OperationInstruction::UnstakeArbiter));
+	}
+
+	#[test]
+	fn pool_instructions_carry_no_data() {
+		assert_eq!(unpack(20, &[]), Ok(OperationInstruction::JoinArbiterPool));
+		assert_eq!(unpack(21, &[]), Ok(OperationInstruction::LeaveArbiterPool));
+	}
+
+	#[test]
+	fn appeal_verdict_unpacks_the_grounds() {
+		let content = arweave_content();
+
+		assert_eq!(unpack(22, &content), Ok(OperationInstruction::AppealVerdict(ContentRef::Arweave(SELLER_TERMS))));
+		assert_eq!(unpack(22, &[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(22, &[&content[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn settlements_unpack_the_buyer_amount() {
+		assert_eq!(unpack(23, &400u64.to_le_bytes()), Ok(OperationInstruction::ProposeSettlement(400)));
+		assert_eq!(unpack(24, &400u64.to_le_bytes()), Ok(OperationInstruction::AcceptSettlement(400)));
+
+		for tag in [23, 24] {
+			assert_eq!(unpack(tag, &[]), Err(ProgramError::InvalidInstructionData));
+			assert_eq!(unpack(tag, &[0x01; 9]), Err(ProgramError::InvalidInstructionData));
+		}
+	}
+
+	#[test]
+	fn seller_partial_refund_unpacks_the_amount() {
+		assert_eq!(unpack(25, &250u64.to_le_bytes()), Ok(OperationInstruction::SellerPartialRefund(250)));
+		assert_eq!(unpack(25, &[0x01; 4]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn define_milestones_unpacks_every_milestone() {
+		let milestone = [&600u64.to_le_bytes()[..], &arweave_content()].concat();
+		let data = [&[0x02][..], &milestone, &milestone].concat();
+
+		let expected = OperationInstruction::DefineMilestones(vec![
+			(600, ContentRef::Arweave(SELLER_TERMS)),
+			(600, ContentRef::Arweave(SELLER_TERMS)),
+		]);
+		assert_eq!(unpack(26, &data), Ok(expected));
+	}
+
+	#[test]
+	fn define_milestones_rejects_bad_counts_and_lengths() {
+		let milestone = [&600u64.to_le_bytes()[..], &arweave_content()].concat();
+
+		let malformed = [
+			vec![],
+			vec![0x00],
+			[&[MAX_MILESTONES as u8 + 1][..], &milestone.repeat(MAX_MILESTONES + 1)].concat(),
+			// Fewer milestones than counted
+			[&[0x02][..], &milestone].concat(),
+			// Amount without content
+			[&[0x01][..], &600u64.to_le_bytes()].concat(),
+			// More milestones than counted
+			[&[0x01][..], &milestone, &milestone].concat(),
+		];
+
+		for data in malformed {
+			assert_eq!(unpack(26, &data), Err(ProgramError::InvalidInstructionData));
+		}
+	}
+
+	#[test]
+	fn release_milestone_unpacks_the_index() {
+		assert_eq!(unpack(27, &[0x03]), Ok(OperationInstruction::ReleaseMilestone(3)));
+		assert_eq!(unpack(27, &[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(27, &[0x03, 0x00]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn define_line_items_unpacks_every_line_item() {
+		let line_item = [&100u64.to_le_bytes()[..], &3u32.to_le_bytes(), &arweave_content()].concat();
+		let data = [&[0x02][..], &line_item, &line_item].concat();
+
+		let expected = OperationInstruction::DefineLineItems(vec![
+			(100, 3, ContentRef::Arweave(SELLER_TERMS)),
+			(100, 3, ContentRef::Arweave(SELLER_TERMS)),
+		]);
+		assert_eq!(unpack(28, &data), Ok(expected));
+	}
+
+	#[test]
+	fn define_line_items_rejects_bad_counts_and_lengths() {
+		let line_item = [&100u64.to_le_bytes()[..], &3u32.to_le_bytes(), &arweave_content()].concat();
+
+		let malformed = [
+			vec![],
+			vec![0x00],
+			[&[MAX_LINE_ITEMS as u8 + 1][..], &line_item.repeat(MAX_LINE_ITEMS + 1)].concat(),
+			// Fewer line items than counted
+			[&[0x02][..], &line_item].concat(),
+			// Unit price without quantity
+			[&[0x01][..], &100u64.to_le_bytes()].concat(),
+			// More line items than counted
+			[&[0x01][..], &line_item, &line_item].concat(),
+		];
+
+		for data in malformed {
+			assert_eq!(unpack(28, &data), Err(ProgramError::InvalidInstructionData));
+		}
+	}
+
+	#[test]
+	fn refund_line_item_unpacks_the_index_and_quantity() {
+		let data = [&[0x01][..], &2u32.to_le_bytes()].concat();
+
+		assert_eq!(unpack(29, &data), Ok(OperationInstruction::RefundLineItem(1, 2)));
+		assert_eq!(unpack(29, &data[..4]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(29, &[&data[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn offers_unpack_the_price_and_index() {
+		assert_eq!(unpack(30, &900u64.to_le_bytes()), Ok(OperationInstruction::ProposePrice(900)));
+		assert_eq!(unpack(30, &[0x01; 4]), Err(ProgramError::InvalidInstructionData));
+
+		assert_eq!(unpack(31, &7u32.to_le_bytes()), Ok(OperationInstruction::AcceptOffer(7)));
+		assert_eq!(unpack(31, &[0x01; 8]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn reassign_buyer_unpacks_the_buyer() {
+		let buyer = Pubkey::new_from_array([2; 32]);
+
+		assert_eq!(unpack(32, &buyer.to_bytes()), Ok(OperationInstruction::ReassignBuyer(buyer)));
+		assert_eq!(unpack(32, &[2; 31]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(32, &[2; 33]), Err(ProgramError::InvalidInstructionData));
+	}
+
+	fn terms_data() -> Vec<u8> {
+		[&1000u64.to_le_bytes()[..], &[TokenVersion::Sol as u8], &arweave_content()].concat()
+	}
+
+	#[test]
+	fn register_buyer_unpacks_the_terms_and_optional_revision() {
+		let expected = OperationInstruction::RegisterBuyer((1000, TokenVersion::Sol, ContentRef::Arweave(SELLER_TERMS), None));
+		assert_eq!(unpack(1, &terms_data()), Ok(expected));
+
+		let data = [&terms_data()[..], &3u32.to_le_bytes()].concat();
+		let expected = OperationInstruction::RegisterBuyer((1000, TokenVersion::Sol, ContentRef::Arweave(SELLER_TERMS), Some(3)));
+		assert_eq!(unpack(1, &data), Ok(expected));
+	}
+
+	#[test]
+	fn register_buyer_rejects_malformed_terms() {
+		let mut unknown_token = terms_data();
+		unknown_token[8] = 0x01;
+
+		let malformed = [
+			terms_data()[..10].to_vec(),
+			unknown_token,
+			// Truncated revision
+			[&terms_data()[..], &[0x03, 0x00]].concat(),
+			// Trailing bytes
+			[&terms_data()[..], &3u32.to_le_bytes(), &[0x00]].concat(),
+		];
+
+		for data in malformed {
+			assert_eq!(unpack(1, &data), Err(ProgramError::InvalidInstructionData));
+		}
+	}
+
+	#[test]
+	fn update_listing_unpacks_the_value_and_item() {
+		let data = [&1200u64.to_le_bytes()[..], &arweave_content()].concat();
+
+		assert_eq!(unpack(33, &data), Ok(OperationInstruction::UpdateListing((1200, ContentRef::Arweave(SELLER_TERMS)))));
+		assert_eq!(unpack(33, &data[..9]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(33, &[&data[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn register_buyer_and_deposit_unpacks_the_terms_arbiters_and_optional_revision() {
+		let arbiters = [Pubkey::new_from_array([3; 32]), Pubkey::new_from_array([4; 32]), Pubkey::new_from_array([5; 32])];
+		let deal = [&terms_data()[..], &arbiters.map(|arbiter| arbiter.to_bytes()).concat()].concat();
+
+		let expected = OperationInstruction::RegisterBuyerAndDeposit((1000, TokenVersion::Sol, ContentRef::Arweave(SELLER_TERMS), arbiters, None));
+		assert_eq!(unpack(34, &deal), Ok(expected));
+
+		let data = [&deal[..], &3u32.to_le_bytes()].concat();
+		let expected = OperationInstruction::RegisterBuyerAndDeposit((1000, TokenVersion::Sol, ContentRef::Arweave(SELLER_TERMS), arbiters, Some(3)));
+		assert_eq!(unpack(34, &data), Ok(expected));
+	}
+
+	#[test]
+	fn register_buyer_and_deposit_rejects_missing_arbiters_and_trailing_bytes() {
+		let deal = [&terms_data()[..], &[3; 96]].concat();
+
+		assert_eq!(unpack(34, &terms_data()), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(34, &deal[..deal.len() - 1]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(34, &[&deal[..], &[0x03, 0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(34, &[&deal[..], &3u32.to_le_bytes(), &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn mark_shipped_unpacks_the_tracking() {
+		let content = arweave_content();
+
+		assert_eq!(unpack(35, &content), Ok(OperationInstruction::MarkShipped(ContentRef::Arweave(SELLER_TERMS))));
+		assert_eq!(unpack(35, &[]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(35, &[&content[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+
+		assert_eq!(unpack(36, &[]), Ok(OperationInstruction::ConfirmReceived));
+	}
+
+	#[test]
+	fn attest_delivery_unpacks_the_flag_and_time() {
+		let data = [&[0x01][..], &1_700_000_000i64.to_le_bytes()].concat();
+
+		assert_eq!(unpack(37, &data), Ok(OperationInstruction::AttestDelivery((true, 1_700_000_000))));
+		assert_eq!(unpack(37, &data[..8]), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(37, &[&[0x02][..], &data[1..]].concat()), Err(ProgramError::InvalidInstructionData));
+		assert_eq!(unpack(37, &[&data[..], &[0x00]].concat()), Err(ProgramError::InvalidInstructionData));
+	}
+
+	#[test]
+	fn finalize_release_carries_no_data() {
+		assert_eq!(unpack(38, &[]), Ok(OperationInstruction::FinalizeRelease));
 	}
 }
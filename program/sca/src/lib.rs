@@ -4,39 +4,77 @@
 //! A tool to secure sales of physical items in long distance operations.
 //!
 //!
+
 mod instruction;
 mod scatype;
+mod content;
 mod account;
 mod operation;
 mod dispute;
+mod evidence;
+mod arbiter;
+mod pool;
+mod appeal;
+mod settlement;
+mod milestone;
+mod order;
+mod offer;
+mod shipping;
+mod migration;
 
 use solana_program::{
     account_info::AccountInfo,
-    entrypoint,
     entrypoint::ProgramResult,
     pubkey::Pubkey,
-    msg, program_error::ProgramError,
+    program_error::ProgramError,
 	declare_id,
 };
 
 use operation::{
-	initialize_operation,
+	initialize_operation, update_listing,
 	register_buyer, register_arbiter,
-	participant_approves_arbiters, buyer_deposit,
-	buyer_release, seller_refund,
+	invite_arbiter, participant_approves_arbiters, reject_arbiter, buyer_deposit,
+	buyer_release, seller_refund, seller_partial_refund, reassign_buyer,
+	register_buyer_and_deposit,
 };
 
 use dispute::{
 	start_dispute,
-	seller_add_info, buyer_add_info,
-	arbiter_vote, participant_claim,
+	seller_add_info, buyer_add_info, arbiter_add_info,
+	arbiter_vote, replace_arbiters, participant_claim,
 };
 
+use migration::migrate_operation;
+
+use arbiter::{register_arbiter_profile, stake_arbiter, unstake_arbiter};
+
+use pool::{join_arbiter_pool, leave_arbiter_pool};
+
+use appeal::appeal_verdict;
+
+use settlement::{propose_settlement, accept_settlement};
+
+use milestone::{define_milestones, release_milestone};
+
+use order::{define_line_items, refund_line_item};
+
+use offer::{propose_price, accept_offer};
+
+use shipping::{mark_shipped, confirm_received, attest_delivery, finalize_release};
+
 use instruction::OperationInstruction;
 
 declare_id!("7f3bKvFg9WrUr3RGig5gGj8GnEFYMML86ffgxaH19ft1");  // Localhost
 
-entrypoint!(fn_main);
+/// Holds the program entrypoint. The macro checks the custom-heap and custom-panic features
+/// and the solana target, which this crate does not declare.
+#[allow(unexpected_cfgs)]
+mod program_entrypoint {
+	use solana_program::entrypoint;
+	use super::fn_main;
+
+	entrypoint!(fn_main);
+}
 
 pub fn fn_main(
     program_id: &Pubkey,
@@ -50,7 +88,7 @@ pub fn fn_main(
 	}
 
 	let instruction = OperationInstruction::unpack_instruction_data(instruction_data)?;
-	return process_instruction(instruction, program_id, accounts);
+	process_instruction(instruction, program_id, accounts)
 }
 
 /// Executes the appropriate instruction, already deserialized.
@@ -58,41 +96,44 @@ pub fn fn_main(
 fn process_instruction(instruction: OperationInstruction, program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
 
 	match instruction {
-		OperationInstruction::InitializeOperation(operation_data) => {
-			return initialize_operation(program_id, accounts, operation_data);
-		},
-		OperationInstruction::RegisterBuyer => {
-			return register_buyer(program_id, accounts);
-		},
-		OperationInstruction::RegisterArbiter => {
-			return register_arbiter(program_id, accounts);
-		},
-		OperationInstruction::ParticipantApprovesArbiters(is_seller) => {
-			return participant_approves_arbiters(program_id, accounts, is_seller);
-		},
-		OperationInstruction::BuyerDeposit => {
-			return buyer_deposit(program_id, accounts);
-		},
-		OperationInstruction::BuyerRelease => {
-			return buyer_release(program_id, accounts);
-		},
-		OperationInstruction::SellerRefund => {
-			return seller_refund(program_id, accounts);
-		},
-		OperationInstruction::StartDispute => {
-			return start_dispute(program_id, accounts);
-		},
-		OperationInstruction::SellerAddInfo(ipfs_hash_bytes) => {
-			return seller_add_info(program_id, accounts, ipfs_hash_bytes);
-		},
-		OperationInstruction::BuyerAddInfo(ipfs_hash_bytes) => {
-			return buyer_add_info(program_id, accounts, ipfs_hash_bytes);
-		},
-		OperationInstruction::ArbiterVote(vote) => {
-			return arbiter_vote(program_id, accounts, vote);
-		},
-		OperationInstruction::ParticipantClaim => {
-			return participant_claim(program_id, accounts);
-		},
+		OperationInstruction::InitializeOperation(operation_data) => initialize_operation(program_id, accounts, operation_data),
+		OperationInstruction::RegisterBuyer(buyer_data) => register_buyer(program_id, accounts, buyer_data),
+		OperationInstruction::RegisterArbiter => register_arbiter(program_id, accounts),
+		OperationInstruction::ParticipantApprovesArbiters(is_seller) => participant_approves_arbiters(program_id, accounts, is_seller),
+		OperationInstruction::BuyerDeposit => buyer_deposit(program_id, accounts),
+		OperationInstruction::BuyerRelease => buyer_release(program_id, accounts),
+		OperationInstruction::SellerRefund => seller_refund(program_id, accounts),
+		OperationInstruction::StartDispute(reason) => start_dispute(program_id, accounts, reason),
+		OperationInstruction::SellerAddInfo(content_ext) => seller_add_info(program_id, accounts, content_ext),
+		OperationInstruction::BuyerAddInfo(content_ext) => buyer_add_info(program_id, accounts, content_ext),
+		OperationInstruction::ArbiterVote(vote) => arbiter_vote(program_id, accounts, vote),
+		OperationInstruction::ParticipantClaim => participant_claim(program_id, accounts),
+		OperationInstruction::MigrateOperation => migrate_operation(program_id, accounts),
+		OperationInstruction::ArbiterAddInfo(content_ext) => arbiter_add_info(program_id, accounts, content_ext),
+		OperationInstruction::RejectArbiter(arbiter) => reject_arbiter(program_id, accounts, arbiter),
+		OperationInstruction::ReplaceArbiters => replace_arbiters(program_id, accounts),
+		OperationInstruction::InviteArbiter(arbiter) => invite_arbiter(program_id, accounts, arbiter),
+		OperationInstruction::RegisterArbiterProfile(profile_data) => register_arbiter_profile(program_id, accounts, profile_data),
+		OperationInstruction::StakeArbiter(amount) => stake_arbiter(program_id, accounts, amount),
+		OperationInstruction::UnstakeArbiter => unstake_arbiter(program_id, accounts),
+		OperationInstruction::JoinArbiterPool => join_arbiter_pool(program_id, accounts),
+		OperationInstruction::LeaveArbiterPool => leave_arbiter_pool(program_id, accounts),
+		OperationInstruction::AppealVerdict(grounds) => appeal_verdict(program_id, accounts, grounds),
+		OperationInstruction::ProposeSettlement(buyer_amount) => propose_settlement(program_id, accounts, buyer_amount),
+		OperationInstruction::AcceptSettlement(buyer_amount) => accept_settlement(program_id, accounts, buyer_amount),
+		OperationInstruction::SellerPartialRefund(amount) => seller_partial_refund(program_id, accounts, amount),
+		OperationInstruction::DefineMilestones(milestones) => define_milestones(program_id, accounts, milestones),
+		OperationInstruction::ReleaseMilestone(index) => release_milestone(program_id, accounts, index),
+		OperationInstruction::DefineLineItems(line_items) => define_line_items(program_id, accounts, line_items),
+		OperationInstruction::RefundLineItem(index, quantity) => refund_line_item(program_id, accounts, index, quantity),
+		OperationInstruction::ProposePrice(amount) => propose_price(program_id, accounts, amount),
+		OperationInstruction::AcceptOffer(index) => accept_offer(program_id, accounts, index),
+		OperationInstruction::ReassignBuyer(buyer) => reassign_buyer(program_id, accounts, buyer),
+		OperationInstruction::UpdateListing(listing_data) => update_listing(program_id, accounts, listing_data),
+		OperationInstruction::RegisterBuyerAndDeposit(deal_data) => register_buyer_and_deposit(program_id, accounts, deal_data),
+		OperationInstruction::MarkShipped(tracking_content) => mark_shipped(program_id, accounts, tracking_content),
+		OperationInstruction::ConfirmReceived => confirm_received(program_id, accounts),
+		OperationInstruction::AttestDelivery(attestation) => attest_delivery(program_id, accounts, attestation),
+		OperationInstruction::FinalizeRelease => finalize_release(program_id, accounts),
 	}
 }
\ No newline at end of file
@@ -0,0 +1,450 @@
+//! # migration
+//! Functionality related to upgrading Operation accounts between layout versions
+//!
+//! List of supported instructions
+//!
+//! 1. Migrate Operation -> [migrate_operation]
+//!
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg, system_instruction::transfer,
+    program_error::ProgramError,
+    pubkey::Pubkey, system_program::check_id,
+	program::invoke,
+	clock::UnixTimestamp,
+	sysvar::{
+		Sysvar,
+		rent::Rent,
+	}
+
+};
+
+use borsh::BorshDeserialize;
+#[cfg(test)]
+use borsh::BorshSerialize;
+use bytemuck::{bytes_of, Zeroable};
+
+use std::str;
+
+use crate::content::{ContentRef, ContentRefData, IpfsCid};
+
+use crate::scatype::{
+	OperationAccount, OperationStatus, ArbiterSelection, TokenVersion, VotingOptions, SCAError,
+	EvidenceEntry, EvidenceRole,
+	EVIDENCE_PERIOD, VOTING_PERIOD, OPERATION_ACCOUNT_VERSION, OPERATION_ACCOUNT_LEN, LEGACY_OPERATION_ACCOUNT_LEN,
+};
+
+use crate::evidence::create_evidence_log;
+
+use crate::dispute::current_timestamp;
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+};
+
+/// The original, unversioned layout of the [OperationAccount], as serialized with borsh.
+/// Enums are kept as their byte, and the IPFS hashes as 46 bytes of base58 text.
+#[derive(BorshDeserialize)]
+#[cfg_attr(test, derive(BorshSerialize))]
+struct LegacyOperationAccount {
+	status: u8,
+	created_at: UnixTimestamp,
+	token_version: u8,
+	value: u64,
+	seller: [u8; 32],
+	buyer: [u8; 32],
+	ipfs: [u8; 46],
+	arbiter1: [u8; 32],
+	arbiter2: [u8; 32],
+	arbiter3: [u8; 32],
+	seller_approved: bool,
+	buyer_approved: bool,
+	seller_ipfs_ext: [u8; 46],
+	buyer_ipfs_ext: [u8; 46],
+	arbiter_vote_1: u8,
+	arbiter_vote_2: u8,
+	arbiter_vote_3: u8,
+}
+
+/// Upgrades an [OperationAccount] in place from the legacy layout to the current one.
+/// The account is reallocated to the current size, and the PAYER tops up the rent exemption if needed.
+/// Any signer may pay for the migration, since it does not change the state of the operation.
+///
+/// The legacy layout kept the seller and buyer additional info inside the operation.
+/// Operations that went into dispute get their evidence log created here, holding that info.
+/// Ongoing disputes get a full evidence period from the time of migration.
+///
+/// Accounts:
+/// 1. PAYER - Account paying for this transaction and any additional rent.
+/// 2. OPERATIONACCOUNT - Represents the operation to upgrade. Comformant to [OperationAccount] or older.
+/// 3. SYSTEM PROGRAM - Used to transfer the additional rent.
+/// 4. EVIDENCELOG - Optional, required for operations in dispute that predate the evidence log.
+#[inline(never)]
+pub fn migrate_operation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get PAYER account
+	let payer_account_info = next_account_info(accounts_iter)?;
+
+	// Check PAYER account validity
+	is_signed_by_writable_account(payer_account_info, "PAYER account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+	// Get the optional EVIDENCELOG account, validated when created
+	let evidence_log_account_info = next_account_info(accounts_iter).ok();
+
+	// ================ Enforce configuration rules section
+
+	if !check_id(system_program_account_info.key) {
+		msg!("Invalid System program");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ================ Enforce previous state section
+
+	// Upgrade whatever version is stored to the current layout, in memory.
+	let mut legacy_evidence = None;
+	let operation_account_data = upgrade_operation_data(&operation_account_info.data.borrow(),
+		current_timestamp(), &mut legacy_evidence)?;
+
+	// The evidence log must exist for disputes to go on
+	let evidence_log_account_info = match (&legacy_evidence, evidence_log_account_info) {
+		(None, _) => None,
+		(Some(_), Some(account_info)) => Some(account_info),
+		(Some(_), None) => {
+			msg!("EVIDENCELOG account is required to migrate an operation in dispute.");
+			return Err(ProgramError::NotEnoughAccountKeys)
+		},
+	};
+
+	// ======================= Enforce data validity using accounts data section
+
+	// Get the rent exemption for the new size
+	let rent_exemption_balance = match Rent::get() {
+		Err(_e) => return Err(ProgramError::Custom(SCAError::RentError as u32)),
+		Ok(rent) => rent.minimum_balance(OPERATION_ACCOUNT_LEN),
+	};
+
+	// Escrowed value must stay untouched, so the rent top up is on top of the current balance
+	let rent_shortfall = rent_exemption_balance.saturating_sub(
+		operation_account_info.lamports().saturating_sub(escrowed_value(&operation_account_data)?));
+
+	// ========================= Change state section
+
+	if rent_shortfall > 0 {
+		let instruction_transfer = transfer(
+			payer_account_info.key, // Payer
+			operation_account_info.key, // Recipient
+			rent_shortfall
+		);
+
+		invoke(
+			&instruction_transfer,
+			&[payer_account_info.clone(), operation_account_info.clone(), system_program_account_info.clone()],
+		)?;
+	}
+
+	if let (Some(entries), Some(evidence_log_account_info)) = (&legacy_evidence, evidence_log_account_info) {
+		create_evidence_log(program_id, operation_account_info.key, payer_account_info,
+			evidence_log_account_info, system_program_account_info, entries)?;
+	}
+
+	operation_account_info.realloc(OPERATION_ACCOUNT_LEN, true)?;
+
+	// Save
+	operation_account_info.data.borrow_mut().copy_from_slice(bytes_of(&operation_account_data));
+	msg!("Operation account migrated.");
+
+	Ok(())
+}
+
+/// Returns the account data upgraded to the current [OperationAccount] layout.
+///
+/// Only the legacy layout predates the versioned one, so it is converted field by field.
+/// Operations in dispute return the entries their evidence log must start with.
+fn upgrade_operation_data(data: &[u8], unix_timestamp: UnixTimestamp,
+	legacy_evidence: &mut Option<Vec<EvidenceEntry>>) -> Result<OperationAccount, ProgramError> {
+
+	if data.len() == OPERATION_ACCOUNT_LEN &&
+		(data[0] == 0 || data[0] == OPERATION_ACCOUNT_VERSION) {
+		msg!("Operation account already at the current version.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	if data.len() != LEGACY_OPERATION_ACCOUNT_LEN {
+		msg!("Unsupported operation account version.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	let legacy = LegacyOperationAccount::try_from_slice(data)
+		.map_err(|_e| ProgramError::InvalidAccountData)?;
+
+	let status = OperationStatus::try_from(legacy.status)?;
+
+	// Never initialized, there is nothing to keep.
+	if status == OperationStatus::Closed {
+		return Ok(OperationAccount::zeroed())
+	}
+
+	let in_dispute = status == OperationStatus::InDispute;
+	let in_voting = in_dispute || status == OperationStatus::InVoting;
+	let disputed = in_voting || status == OperationStatus::SellerClaim ||
+		status == OperationStatus::BuyerClaim || status == OperationStatus::DisputeResolved;
+
+	let seller = Pubkey::new_from_array(legacy.seller);
+	let buyer = Pubkey::new_from_array(legacy.buyer);
+	let arbiters = [legacy.arbiter1, legacy.arbiter2, legacy.arbiter3].map(Pubkey::new_from_array);
+
+	let mut operation_account_data = OperationAccount::zeroed();
+	operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+	operation_account_data.set_status(status);
+	operation_account_data.set_created_at(legacy.created_at);
+	operation_account_data.set_token_version(TokenVersion::try_from(legacy.token_version)?);
+	operation_account_data.set_value(legacy.value);
+	operation_account_data.seller = seller;
+	operation_account_data.buyer = buyer;
+	operation_account_data.item_content = content_ref_from_text(&legacy.ipfs);
+	[operation_account_data.arbiter1, operation_account_data.arbiter2, operation_account_data.arbiter3] = arbiters;
+	operation_account_data.set_seller_approved(legacy.seller_approved);
+	operation_account_data.set_buyer_approved(legacy.buyer_approved);
+	operation_account_data.set_arbiter_vote_1(VotingOptions::try_from(legacy.arbiter_vote_1)?);
+	operation_account_data.set_arbiter_vote_2(VotingOptions::try_from(legacy.arbiter_vote_2)?);
+	operation_account_data.set_arbiter_vote_3(VotingOptions::try_from(legacy.arbiter_vote_3)?);
+
+	// Arbiters already registered count as invited, so that rejecting or replacing them keeps working.
+	operation_account_data.arbiter_invites[..arbiters.len()].copy_from_slice(&arbiters);
+	operation_account_data.set_arbiter_selection(ArbiterSelection::Manual);
+
+	// Votes had no evidence period before, so ongoing disputes without votes get a full one,
+	// and a full voting period after it.
+	if in_dispute {
+		operation_account_data.set_evidence_deadline(unix_timestamp.saturating_add(EVIDENCE_PERIOD));
+	}
+
+	if in_voting {
+		operation_account_data.set_vote_deadline(operation_account_data.evidence_deadline()
+			.max(unix_timestamp).saturating_add(VOTING_PERIOD));
+	}
+
+	// Disputes kept a single seller and buyer additional info, which moves to the evidence log.
+	// Verdicts given before have no appeal window, and the remaining fields did not exist.
+	if disputed {
+		let mut entries = Vec::with_capacity(2);
+
+		for (role, submitter, text) in [
+			(EvidenceRole::Seller, seller, &legacy.seller_ipfs_ext),
+			(EvidenceRole::Buyer, buyer, &legacy.buyer_ipfs_ext),
+		] {
+			let content = content_ref_from_text(text);
+
+			if content == ContentRefData::default() {
+				continue;
+			}
+
+			// The submission time was never recorded
+			entries.push(EvidenceEntry::new(submitter, role, 0, content));
+		}
+
+		*legacy_evidence = Some(entries);
+	}
+
+	Ok(operation_account_data)
+}
+
+/// Decodes a base58 "Qm..." hash as stored by the legacy layout into an IPFS [ContentRefData].
+/// Unset or unreadable hashes become empty.
+fn content_ref_from_text(text: &[u8]) -> ContentRefData {
+
+	if text.iter().all(|byte| *byte == 0) {
+		return ContentRefData::default();
+	}
+
+	let cid = str::from_utf8(text).ok()
+		.and_then(|cid_str| bs58::decode(cid_str).into_vec().ok())
+		.and_then(|cid_bytes| IpfsCid::new(&cid_bytes).ok());
+
+	match cid {
+		Some(cid) => ContentRefData::from(&ContentRef::Ipfs(cid)),
+		None => {
+			msg!("WARN: Could not decode a stored IPFS hash. Leaving it empty.");
+			ContentRefData::default()
+		},
+	}
+}
+
+/// Returns the token amount currently held in escrow by the operation account.
+fn escrowed_value(operation_account_data: &OperationAccount) -> Result<u64, ProgramError> {
+
+	match operation_account_data.status()? {
+		OperationStatus::BuyerDeposited |
+		OperationStatus::Shipped |
+		OperationStatus::Delivered |
+		OperationStatus::InDispute |
+		OperationStatus::InVoting |
+		OperationStatus::SellerClaim |
+		OperationStatus::BuyerClaim |
+		OperationStatus::InAppeal => Ok(operation_account_data.value() + operation_account_data.appeal_bond()),
+		_ => Ok(0),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ITEM_HASH: &str = "QmWtVXEuMFRphzdzWbrqzuDAtpDJU5teNjwt9Gevo1k6Af";
+	const BUYER_HASH: &str = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+
+	const NOW: UnixTimestamp = 1_700_000_000;
+
+	fn hash_text(hash: &str) -> [u8; 46] {
+		hash.as_bytes().try_into().unwrap()
+	}
+
+	fn ipfs_content(hash: &str) -> ContentRefData {
+		let cid_bytes = bs58::decode(hash).into_vec().unwrap();
+		ContentRefData::from(&ContentRef::Ipfs(IpfsCid::new(&cid_bytes).unwrap()))
+	}
+
+	fn legacy_account(status: OperationStatus) -> LegacyOperationAccount {
+		LegacyOperationAccount {
+			status: status as u8,
+			created_at: 1_600_000_000,
+			token_version: TokenVersion::Sol as u8,
+			value: 1000,
+			seller: [1; 32],
+			buyer: [2; 32],
+			ipfs: hash_text(ITEM_HASH),
+			arbiter1: [3; 32],
+			arbiter2: [4; 32],
+			arbiter3: [5; 32],
+			seller_approved: true,
+			buyer_approved: true,
+			seller_ipfs_ext: [0; 46],
+			buyer_ipfs_ext: hash_text(BUYER_HASH),
+			arbiter_vote_1: VotingOptions::Buyer as u8,
+			arbiter_vote_2: VotingOptions::NoVote as u8,
+			arbiter_vote_3: VotingOptions::Seller as u8,
+		}
+	}
+
+	fn upgrade(legacy: &LegacyOperationAccount) -> (Result<OperationAccount, ProgramError>, Option<Vec<EvidenceEntry>>) {
+		let data = borsh::to_vec(legacy).unwrap();
+		assert_eq!(data.len(), LEGACY_OPERATION_ACCOUNT_LEN);
+
+		let mut legacy_evidence = None;
+		let result = upgrade_operation_data(&data, NOW, &mut legacy_evidence);
+
+		(result, legacy_evidence)
+	}
+
+	#[test]
+	fn upgrades_every_legacy_field() {
+		let (result, legacy_evidence) = upgrade(&legacy_account(OperationStatus::InDispute));
+		let upgraded = result.unwrap();
+
+		let arbiters = [Pubkey::new_from_array([3; 32]), Pubkey::new_from_array([4; 32]), Pubkey::new_from_array([5; 32])];
+
+		let mut expected = OperationAccount::zeroed();
+		expected.version = OPERATION_ACCOUNT_VERSION;
+		expected.set_status(OperationStatus::InDispute);
+		expected.set_created_at(1_600_000_000);
+		expected.set_token_version(TokenVersion::Sol);
+		expected.set_value(1000);
+		expected.seller = Pubkey::new_from_array([1; 32]);
+		expected.buyer = Pubkey::new_from_array([2; 32]);
+		expected.item_content = ipfs_content(ITEM_HASH);
+		[expected.arbiter1, expected.arbiter2, expected.arbiter3] = arbiters;
+		expected.set_seller_approved(true);
+		expected.set_buyer_approved(true);
+		expected.set_arbiter_vote_1(VotingOptions::Buyer);
+		expected.set_arbiter_vote_2(VotingOptions::NoVote);
+		expected.set_arbiter_vote_3(VotingOptions::Seller);
+		expected.arbiter_invites[..3].copy_from_slice(&arbiters);
+		expected.set_arbiter_selection(ArbiterSelection::Manual);
+		expected.set_evidence_deadline(NOW + EVIDENCE_PERIOD);
+		expected.set_vote_deadline(NOW + EVIDENCE_PERIOD + VOTING_PERIOD);
+
+		assert_eq!(upgraded, expected);
+		assert_eq!(bytes_of(&upgraded).len(), OPERATION_ACCOUNT_LEN);
+		assert_eq!(OperationAccount::load(bytes_of(&upgraded)).unwrap(), &expected);
+
+		// Only the buyer had additional info
+		assert_eq!(legacy_evidence, Some(vec![
+			EvidenceEntry::new(expected.buyer, EvidenceRole::Buyer, 0, ipfs_content(BUYER_HASH)),
+		]));
+	}
+
+	#[test]
+	fn voting_disputes_keep_their_evidence_period_closed() {
+		let (result, legacy_evidence) = upgrade(&legacy_account(OperationStatus::InVoting));
+		let upgraded = result.unwrap();
+
+		assert_eq!(upgraded.evidence_deadline(), 0);
+		assert_eq!(upgraded.vote_deadline(), NOW + VOTING_PERIOD);
+		assert_eq!(legacy_evidence.map(|entries| entries.len()), Some(1));
+	}
+
+	#[test]
+	fn undisputed_operations_need_no_evidence_log() {
+		let (result, legacy_evidence) = upgrade(&legacy_account(OperationStatus::BuyerDeposited));
+		let upgraded = result.unwrap();
+
+		assert_eq!(upgraded.status().unwrap(), OperationStatus::BuyerDeposited);
+		assert_eq!(upgraded.evidence_deadline(), 0);
+		assert_eq!(upgraded.vote_deadline(), 0);
+		assert_eq!(legacy_evidence, None);
+	}
+
+	#[test]
+	fn unreadable_hashes_become_empty() {
+		let mut legacy = legacy_account(OperationStatus::Opened);
+		legacy.ipfs = [b'x'; 46];
+
+		let (result, _) = upgrade(&legacy);
+
+		assert_eq!(result.unwrap().item_content, ContentRefData::default());
+	}
+
+	#[test]
+	fn closed_accounts_upgrade_to_uninitialized() {
+		let (result, legacy_evidence) = upgrade(&legacy_account(OperationStatus::Closed));
+
+		assert_eq!(result.unwrap(), OperationAccount::zeroed());
+		assert_eq!(legacy_evidence, None);
+	}
+
+	#[test]
+	fn rejects_current_and_unknown_layouts() {
+		let mut legacy_evidence = None;
+
+		let mut current = vec![0; OPERATION_ACCOUNT_LEN];
+		assert_eq!(upgrade_operation_data(&current, NOW, &mut legacy_evidence), Err(ProgramError::AccountAlreadyInitialized));
+
+		current[0] = OPERATION_ACCOUNT_VERSION;
+		assert_eq!(upgrade_operation_data(&current, NOW, &mut legacy_evidence), Err(ProgramError::AccountAlreadyInitialized));
+
+		let unknown = vec![0; LEGACY_OPERATION_ACCOUNT_LEN + 1];
+		assert_eq!(upgrade_operation_data(&unknown, NOW, &mut legacy_evidence), Err(ProgramError::InvalidAccountData));
+
+		// Invalid status byte
+		let mut legacy = borsh::to_vec(&legacy_account(OperationStatus::Opened)).unwrap();
+		legacy[0] = 0xff;
+		assert_eq!(upgrade_operation_data(&legacy, NOW, &mut legacy_evidence), Err(ProgramError::InvalidAccountData));
+	}
+}
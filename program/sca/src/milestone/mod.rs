@@ -0,0 +1,504 @@
+//! # milestone
+//! Functionality related to paying the value of an operation in stages
+//!
+//! List of supported instructions
+//!
+//! 1. Define Milestones -> [define_milestones]
+//! 2. Release Milestone -> [release_milestone]
+//!
+//! The buyer deposits the whole value, and releases it to the seller one milestone at a time.
+//! Each release reduces the escrowed value, so refunds, disputes and settlements only cover
+//! the milestones not released yet. See [MilestonesHeader].
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use bytemuck::{cast_slice, cast_slice_mut};
+
+use crate::content::{ContentRef, ContentRefData};
+
+use crate::scatype::{
+	OperationAccount, OperationStatus, MilestonesHeader, Milestone,
+	MILESTONES_VERSION, MILESTONES_SEED, MILESTONES_HEADER_LEN, MILESTONE_LEN,
+};
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+	create_pda_account,
+};
+
+/// Returns the address and bump seed of the milestones of an operation.
+pub fn milestones_address(program_id: &Pubkey, operation: &Pubkey) -> (Pubkey, u8) {
+	Pubkey::find_program_address(&[MILESTONES_SEED, operation.as_ref()], program_id)
+}
+
+/// Allows a Seller to split the value of the operation in milestones, before a buyer registers.
+/// The amounts must add up to the value, which the buyer deposits at once as usual.
+/// Note that this function expects an Opened [OperationAccount] without milestones.
+///
+/// milestones: Amount and content reference of each milestone, conformant to [crate::instruction::OperationInstruction::DefineMilestones]
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction and the milestones.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. MILESTONES - Created here. PDA of [MILESTONES_SEED] and OPERATIONACCOUNT.
+/// 4. SYSTEM PROGRAM - Used to create the milestones.
+#[inline(never)]
+pub fn define_milestones(program_id: &Pubkey, accounts: &[AccountInfo],
+	milestones: Vec<(u64, ContentRef)>) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the MILESTONES account
+	let milestones_account_info = next_account_info(accounts_iter)?;
+
+	let (milestones_pubkey, bump) = milestones_address(program_id, operation_account_info.key);
+
+	// Check MILESTONES account validity
+	if *milestones_account_info.key != milestones_pubkey || !milestones_account_info.is_writable {
+		msg!("MILESTONES account is not a valid account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::Opened {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// CHECK: Were the milestones already defined??? -> Reject
+	if operation_account_data.milestone_count() != 0 {
+		msg!("Milestones already defined.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// The count and content references were already validated when the instruction was unpacked
+
+	let mut total: u64 = 0;
+
+	for (amount, _content) in &milestones {
+		if *amount == 0 {
+			msg!("Milestone amounts must be more than zero.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		total = total.checked_add(*amount).ok_or(ProgramError::InvalidArgument)?;
+	}
+
+	if total != operation_account_data.value() {
+		msg!("Milestone amounts must add up to the token value.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_milestone_count(milestones.len() as u8);
+
+	// Release the account data before calling into the System program
+	drop(operation_account_bytes);
+
+	let milestones_len = MILESTONES_HEADER_LEN + milestones.len() * MILESTONE_LEN;
+
+	create_pda_account(program_id, seller_account_info, milestones_account_info, system_program_account_info,
+		milestones_len, &[MILESTONES_SEED, operation_account_info.key.as_ref(), &[bump]])?;
+
+	// Save
+	let mut milestones_bytes = milestones_account_info.try_borrow_mut_data()?;
+	milestones_bytes[0] = MILESTONES_VERSION;
+	milestones_bytes[MILESTONES_HEADER_LEN - 1] = milestones.len() as u8;
+
+	let milestones_header = MilestonesHeader::load_mut(&mut milestones_bytes)?;
+	milestones_header.bump = bump;
+	milestones_header.operation = *operation_account_info.key;
+
+	let milestones: Vec<Milestone> = milestones.iter()
+		.map(|(amount, content)| Milestone::new(*amount, ContentRefData::from(content)))
+		.collect();
+
+	milestones_bytes[MILESTONES_HEADER_LEN..].copy_from_slice(cast_slice(&milestones));
+
+	msg!("Milestones defined: {}", milestones.len());
+
+	Ok(())
+}
+
+/// Allows a Buyer to release a single milestone to the seller.
+/// The escrowed value is reduced by its amount, and the operation ends once all of them are released.
+/// Note that this function expects a BuyerDeposited [OperationAccount] with milestones.
+///
+/// index: Position of the milestone, starting at zero.
+///
+/// Accounts:
+/// 1. BUYER - Account of the item buyer, who also pays for this transaction.
+/// 2. SELLER - Account of the item seller
+/// 3. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 4. MILESTONES - Milestones of the operation, created by [define_milestones].
+#[inline(never)]
+pub fn release_milestone(program_id: &Pubkey, accounts: &[AccountInfo], index: u8) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get BUYER account
+	let buyer_account_info = next_account_info(accounts_iter)?;
+
+	// Check BUYER account validity
+	is_signed_by_writable_account(buyer_account_info, "BUYER account is not a valid account.")?;
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	// Seller is not a signer here, and the pubkey is already stored.
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the MILESTONES account
+	let milestones_account_info = next_account_info(accounts_iter)?;
+
+	// Check MILESTONES account validity
+	is_owned_and_writable(program_id, milestones_account_info, "MILESTONES account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	let mut milestones_bytes = milestones_account_info.try_borrow_mut_data()?;
+	let milestones_header = MilestonesHeader::load_mut(&mut milestones_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if !operation_account_data.status()?.is_deposited() {
+		msg!("Operation account not setup.");
+		return Err(ProgramError::UninitializedAccount)
+	}
+
+	if operation_account_data.milestone_count() == 0 {
+		msg!("Operation has no milestones.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	let milestones_pubkey = Pubkey::create_program_address(
+		&[MILESTONES_SEED, operation_account_info.key.as_ref(), &[milestones_header.bump]], program_id)?;
+
+	if milestones_pubkey != *milestones_account_info.key {
+		msg!("Milestones belong to another operation.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if *buyer_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if index >= milestones_header.count {
+		msg!("Invalid milestone.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let milestones: &mut [Milestone] = cast_slice_mut(&mut milestones_bytes[MILESTONES_HEADER_LEN..]);
+	let milestone = &mut milestones[index as usize];
+
+	if milestone.released() {
+		msg!("Milestone already released.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	// Released milestones always come out of the value, so this only fails on a broken account
+	if milestone.amount() > operation_account_data.value() {
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ========================= Change state section
+
+	// Debit from_account and credit to_account
+	**operation_account_info.try_borrow_mut_lamports()? -= milestone.amount();
+	**seller_account_info.try_borrow_mut_lamports()? += milestone.amount();
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	milestone.set_released(true);
+	operation_account_data.set_value(operation_account_data.value() - milestone.amount());
+
+	// Settlements proposed for the previous value no longer apply
+	operation_account_data.settlement_proposer = Default::default();
+	operation_account_data.set_settlement_buyer_amount(0);
+
+	if operation_account_data.value() == 0 {
+		operation_account_data.set_status(OperationStatus::ReleaseRefund);
+	}
+
+	msg!("Milestone {} released, remaining token value: {}", index, operation_account_data.value());
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bytemuck::{bytes_of, Zeroable};
+
+	use crate::scatype::{SCAError, OPERATION_ACCOUNT_VERSION};
+
+	use crate::operation::buyer_release;
+	use crate::dispute::participant_claim;
+
+	use crate::content::ARWEAVE_TX_ID_LEN;
+
+	const SELLER: Pubkey = Pubkey::new_from_array([1; 32]);
+	const BUYER: Pubkey = Pubkey::new_from_array([2; 32]);
+	const OPERATION: Pubkey = Pubkey::new_from_array([9; 32]);
+
+	const VALUE: u64 = 1_000;
+	const BALANCE: u64 = 5_000;
+
+	fn operation(status: OperationStatus, milestone_count: u8) -> OperationAccount {
+		let mut operation_account_data = OperationAccount::zeroed();
+		operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+		operation_account_data.set_status(status);
+		operation_account_data.set_value(VALUE);
+		operation_account_data.seller = SELLER;
+		operation_account_data.buyer = BUYER;
+		operation_account_data.set_milestone_count(milestone_count);
+		operation_account_data.settlement_proposer = SELLER;
+		operation_account_data.set_settlement_buyer_amount(400);
+
+		operation_account_data
+	}
+
+	/// Lays out the milestones account of [OPERATION] with the given amounts, none of them released.
+	fn milestones(amounts: &[u64]) -> Vec<u8> {
+		let milestones_header = MilestonesHeader {
+			version: MILESTONES_VERSION,
+			bump: milestones_address(&crate::id(), &OPERATION).1,
+			operation: OPERATION,
+			count: amounts.len() as u8,
+		};
+
+		let milestones: Vec<Milestone> = amounts.iter()
+			.map(|amount| Milestone::new(*amount, ContentRefData::zeroed()))
+			.collect();
+
+		[bytes_of(&milestones_header), cast_slice(&milestones)].concat()
+	}
+
+	/// Runs [define_milestones] by the seller, returning the result.
+	fn define(amounts: &[u64]) -> ProgramResult {
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let milestones_key = milestones_address(&program_id, &OPERATION).0;
+
+		let mut operation_data = bytes_of(&operation(OperationStatus::Opened, 0)).to_vec();
+		let (mut seller_lamports, mut operation_lamports, mut milestones_lamports, mut system_lamports) = (BALANCE, BALANCE, 0, 0);
+		let (mut seller_data, mut milestones_data, mut system_data) = (vec![], vec![], vec![]);
+
+		let accounts = [
+			AccountInfo::new(&SELLER, true, true, &mut seller_lamports, &mut seller_data, &system_program, false, 0),
+			AccountInfo::new(&OPERATION, false, true, &mut operation_lamports, &mut operation_data, &program_id, false, 0),
+			AccountInfo::new(&milestones_key, false, true, &mut milestones_lamports, &mut milestones_data, &system_program, false, 0),
+			AccountInfo::new(&system_program, false, false, &mut system_lamports, &mut system_data, &system_program, true, 0),
+		];
+
+		let milestones = amounts.iter()
+			.map(|amount| (*amount, ContentRef::Arweave([1; ARWEAVE_TX_ID_LEN])))
+			.collect();
+
+		define_milestones(&program_id, &accounts, milestones)
+	}
+
+	/// Runs [release_milestone] by the buyer, returning the result, the operation, the milestones
+	/// and the lamports of the operation and the seller afterwards.
+	fn release(operation_account_data: &OperationAccount, milestones_data: &[u8], index: u8)
+		-> (ProgramResult, OperationAccount, Vec<u8>, [u64; 2]) {
+
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let milestones_key = milestones_address(&program_id, &OPERATION).0;
+
+		let mut operation_data = bytes_of(operation_account_data).to_vec();
+		let mut milestones_data = milestones_data.to_vec();
+		let mut lamports = [BALANCE + operation_account_data.value(), BALANCE];
+		let [operation_lamports, seller_lamports] = &mut lamports;
+		let (mut buyer_lamports, mut milestones_lamports) = (BALANCE, BALANCE);
+		let (mut buyer_data, mut seller_data) = (vec![], vec![]);
+
+		let accounts = [
+			AccountInfo::new(&BUYER, true, true, &mut buyer_lamports, &mut buyer_data, &system_program, false, 0),
+			AccountInfo::new(&SELLER, false, true, seller_lamports, &mut seller_data, &system_program, false, 0),
+			AccountInfo::new(&OPERATION, false, true, operation_lamports, &mut operation_data, &program_id, false, 0),
+			AccountInfo::new(&milestones_key, false, true, &mut milestones_lamports, &mut milestones_data, &program_id, false, 0),
+		];
+
+		let result = release_milestone(&program_id, &accounts, index);
+		drop(accounts);
+
+		(result, *OperationAccount::load(&operation_data).unwrap(), milestones_data, lamports)
+	}
+
+	/// Runs a payout of the whole remaining value to the seller, returning the lamports of the
+	/// operation and the seller afterwards.
+	fn pay_out(operation_account_data: &OperationAccount, handler: fn(&Pubkey, &[AccountInfo]) -> ProgramResult,
+		by_buyer: bool) -> [u64; 2] {
+
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+
+		let mut operation_data = bytes_of(operation_account_data).to_vec();
+		let mut lamports = [BALANCE + operation_account_data.value(), BALANCE];
+		let [operation_lamports, seller_lamports] = &mut lamports;
+		let mut buyer_lamports = BALANCE;
+		let (mut buyer_data, mut seller_data) = (vec![], vec![]);
+
+		let seller_account_info = AccountInfo::new(&SELLER, !by_buyer, true, seller_lamports, &mut seller_data,
+			&system_program, false, 0);
+		let operation_account_info = AccountInfo::new(&OPERATION, false, true, operation_lamports, &mut operation_data,
+			&program_id, false, 0);
+
+		let result = if by_buyer {
+			let buyer_account_info = AccountInfo::new(&BUYER, true, true, &mut buyer_lamports, &mut buyer_data,
+				&system_program, false, 0);
+			handler(&program_id, &[buyer_account_info, seller_account_info, operation_account_info])
+		} else {
+			handler(&program_id, &[seller_account_info, operation_account_info])
+		};
+
+		assert_eq!(result, Ok(()));
+
+		lamports
+	}
+
+	fn released(milestones_data: &[u8]) -> Vec<bool> {
+		let milestones: &[Milestone] = cast_slice(&milestones_data[MILESTONES_HEADER_LEN..]);
+		milestones.iter().map(Milestone::released).collect()
+	}
+
+	#[test]
+	fn milestones_must_add_up_to_the_value() {
+		assert_eq!(define(&[400, 500]), Err(ProgramError::InvalidArgument));
+		assert_eq!(define(&[400, 700]), Err(ProgramError::InvalidArgument));
+		assert_eq!(define(&[400, 0, 600]), Err(ProgramError::InvalidArgument));
+		assert_eq!(define(&[u64::MAX, VALUE + 1]), Err(ProgramError::InvalidArgument));
+
+		// The amounts are accepted, creating the account needs the Rent sysvar of a running cluster
+		assert_eq!(define(&[400, 600]), Err(ProgramError::Custom(SCAError::RentError as u32)));
+	}
+
+	#[test]
+	fn releases_come_out_of_the_value() {
+		let (result, operation_account_data, milestones_data, lamports) =
+			release(&operation(OperationStatus::BuyerDeposited, 2), &milestones(&[300, 700]), 0);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(lamports, [BALANCE + 700, BALANCE + 300]);
+		assert_eq!(operation_account_data.value(), 700);
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::BuyerDeposited));
+		assert_eq!(released(&milestones_data), [true, false]);
+
+		let (result, _operation_account_data, _milestones_data, lamports) =
+			release(&operation_account_data, &milestones_data, 0);
+
+		assert_eq!(result, Err(ProgramError::AccountAlreadyInitialized));
+		assert_eq!(lamports, [BALANCE + 700, BALANCE]);
+
+		let (result, operation_account_data, milestones_data, lamports) =
+			release(&operation_account_data, &milestones_data, 1);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(lamports, [BALANCE, BALANCE + 700]);
+		assert_eq!(operation_account_data.value(), 0);
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::ReleaseRefund));
+		assert_eq!(released(&milestones_data), [true, true]);
+	}
+
+	#[test]
+	fn releases_clear_the_pending_settlement() {
+		let (result, operation_account_data, _milestones_data, _lamports) =
+			release(&operation(OperationStatus::BuyerDeposited, 2), &milestones(&[300, 700]), 1);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(operation_account_data.settlement_proposer, Pubkey::default());
+		assert_eq!(operation_account_data.settlement_buyer_amount(), 0);
+	}
+
+	#[test]
+	fn releases_need_a_deposit() {
+		let (result, _operation_account_data, _milestones_data, _lamports) =
+			release(&operation(OperationStatus::BuyerRegistered, 2), &milestones(&[300, 700]), 0);
+		assert_eq!(result, Err(ProgramError::UninitializedAccount));
+
+		let (result, _operation_account_data, _milestones_data, _lamports) =
+			release(&operation(OperationStatus::BuyerDeposited, 2), &milestones(&[300, 700]), 2);
+		assert_eq!(result, Err(ProgramError::InvalidArgument));
+	}
+
+	#[test]
+	fn later_payouts_only_cover_the_rest() {
+		let (_result, mut operation_account_data, _milestones_data, _lamports) =
+			release(&operation(OperationStatus::BuyerDeposited, 2), &milestones(&[300, 700]), 0);
+
+		assert_eq!(pay_out(&operation_account_data, buyer_release, true), [BALANCE, BALANCE + 700]);
+
+		operation_account_data.set_status(OperationStatus::SellerClaim);
+		assert_eq!(pay_out(&operation_account_data, participant_claim, false), [BALANCE, BALANCE + 700]);
+	}
+}
@@ -0,0 +1,502 @@
+//! # offer
+//! Functionality related to negotiating the price of an operation before a buyer registers
+//!
+//! List of supported instructions
+//!
+//! 1. Propose Price -> [propose_price]
+//! 2. Accept Offer -> [accept_offer]
+//!
+//! Prospective buyers offer a price instead of registering at the asking value. Offers are appended
+//! to the offer log of the operation, so the negotiation stays on-chain, and the seller registers the
+//! buyer of the accepted one at its price. See [OfferLogHeader].
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg, system_instruction::transfer,
+    program_error::ProgramError,
+    pubkey::Pubkey, system_program::check_id,
+	program::invoke,
+	sysvar::{
+		Sysvar,
+		rent::Rent,
+	}
+};
+
+use bytemuck::{bytes_of, cast_slice_mut};
+
+use crate::scatype::{
+	OperationAccount, OperationStatus, OfferLogHeader, Offer, SCAError, ZERO_ACCOUNT,
+	OFFER_LOG_VERSION, OFFER_LOG_SEED, OFFER_LOG_HEADER_LEN, OFFER_LEN,
+};
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+	create_pda_account,
+};
+
+use crate::dispute::current_timestamp;
+
+/// Returns the address and bump seed of the offer log of an operation.
+pub fn offer_log_address(program_id: &Pubkey, operation: &Pubkey) -> (Pubkey, u8) {
+	Pubkey::find_program_address(&[OFFER_LOG_SEED, operation.as_ref()], program_id)
+}
+
+/// Allows a prospective Buyer to offer a price for an operation, instead of registering at its value.
+/// The first offer creates the offer log, later ones are appended to it. Buyers may offer again.
+/// Operations split in milestones or line items keep their price, and reserved ones only take offers from their buyer.
+/// Note that this function expects an Opened [OperationAccount].
+///
+/// amount: Lamports offered, conformant to [crate::instruction::OperationInstruction::ProposePrice]
+///
+/// Accounts:
+/// 1. BUYER - Account of the prospective buyer, who also pays for this transaction and the offer log.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. OFFERLOG - PDA of [OFFER_LOG_SEED] and OPERATIONACCOUNT, created with the first offer.
+/// 4. SYSTEM PROGRAM - Used to create or grow the offer log.
+#[inline(never)]
+pub fn propose_price(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get BUYER account
+	let buyer_account_info = next_account_info(accounts_iter)?;
+
+	// Check BUYER account validity
+	is_signed_by_writable_account(buyer_account_info, "BUYER account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the OFFERLOG account, validated when created or appended to
+	let offer_log_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::Opened {
+		msg!("Operation account not setup.");
+		return Err(ProgramError::UninitializedAccount)
+	}
+
+	// CHECK: Would a new price break the milestones or line items??? -> Reject
+	if operation_account_data.milestone_count() != 0 || operation_account_data.line_item_count() != 0 {
+		msg!("Operations with milestones or line items keep their price.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *buyer_account_info.key == operation_account_data.seller {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if operation_account_data.intended_buyer.to_bytes() != ZERO_ACCOUNT &&
+		*buyer_account_info.key != operation_account_data.intended_buyer {
+		msg!("Operation reserved for another buyer.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if amount == 0 {
+		msg!("Offers must be more than zero.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ========================= Change state section
+
+	// The operation is not changed until the seller accepts
+	drop(operation_account_bytes);
+
+	let offer = Offer::new(*buyer_account_info.key, amount, current_timestamp());
+
+	let index = if offer_log_account_info.data_is_empty() {
+		create_offer_log(program_id, operation_account_info.key, buyer_account_info,
+			offer_log_account_info, system_program_account_info, &offer)?
+	} else {
+		append_offer(program_id, operation_account_info.key, buyer_account_info,
+			offer_log_account_info, system_program_account_info, &offer)?
+	};
+
+	msg!("Offer {} proposed: {}", index, amount);
+
+	Ok(())
+}
+
+/// Allows a Seller to accept an offer, registering its buyer with the offered price as the new value.
+/// Offers made before the latest listing update cannot be accepted, see [crate::operation::update_listing].
+/// Note that this function expects an Opened [OperationAccount].
+///
+/// index: Position of the offer in the offer log, conformant to [crate::instruction::OperationInstruction::AcceptOffer]
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. OFFERLOG - Offer log of the operation, created by [propose_price].
+#[inline(never)]
+pub fn accept_offer(program_id: &Pubkey, accounts: &[AccountInfo], index: u32) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the OFFERLOG account
+	let offer_log_account_info = next_account_info(accounts_iter)?;
+
+	// Check OFFERLOG account validity
+	is_owned_and_writable(program_id, offer_log_account_info, "OFFERLOG account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	let mut offer_log_bytes = offer_log_account_info.try_borrow_mut_data()?;
+	let offer_log_len = offer_log_bytes.len();
+	let offer_log_header = OfferLogHeader::load_mut(&mut offer_log_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::Opened {
+		msg!("Operation account not setup.");
+		return Err(ProgramError::UninitializedAccount)
+	}
+
+	// CHECK: Were milestones or line items defined after the offer??? -> Reject
+	if operation_account_data.milestone_count() != 0 || operation_account_data.line_item_count() != 0 {
+		msg!("Operations with milestones or line items keep their price.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if offer_log_header.operation != *operation_account_info.key {
+		msg!("Offer log belongs to another operation.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if offer_log_len != OFFER_LOG_HEADER_LEN + offer_log_header.count() as usize * OFFER_LEN {
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if index >= offer_log_header.count() {
+		msg!("Invalid offer.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let offers: &mut [Offer] = cast_slice_mut(&mut offer_log_bytes[OFFER_LOG_HEADER_LEN..]);
+	let offer = &mut offers[index as usize];
+
+	if offer.accepted() {
+		msg!("Offer already accepted.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	// The buyer offered for a listing that has changed since
+	if operation_account_data.listing_revision() != 0 &&
+		offer.offered_at() <= operation_account_data.listing_updated_at() {
+		msg!("Offer made before the listing was updated.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// Arbiters designated beforehand cannot be the buyer
+	if operation_account_data.is_arbiter(&offer.buyer) {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	offer.set_accepted(true);
+
+	if operation_account_data.arbiters_filled() {
+		operation_account_data.set_status(OperationStatus::ArbitersRegistered);
+	} else {
+		operation_account_data.set_status(OperationStatus::BuyerRegistered);
+	}
+
+	// Set externally
+	operation_account_data.buyer = offer.buyer;
+	operation_account_data.set_value(offer.amount());
+
+	msg!("Offer {} accepted, buyer registered at: {}", index, offer.amount());
+
+	Ok(())
+}
+
+/// Creates the offer log PDA of an operation, holding its first offer. Returns the index of the offer.
+fn create_offer_log<'a>(program_id: &Pubkey, operation: &Pubkey,
+	payer_account_info: &AccountInfo<'a>, offer_log_account_info: &AccountInfo<'a>,
+	system_program_account_info: &AccountInfo<'a>, offer: &Offer) -> Result<u32, ProgramError> {
+
+	let (offer_log_pubkey, bump) = offer_log_address(program_id, operation);
+
+	if *offer_log_account_info.key != offer_log_pubkey || !offer_log_account_info.is_writable {
+		msg!("OFFERLOG account is not a valid account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	create_pda_account(program_id, payer_account_info, offer_log_account_info, system_program_account_info,
+		OFFER_LOG_HEADER_LEN + OFFER_LEN, &[OFFER_LOG_SEED, operation.as_ref(), &[bump]])?;
+
+	// Save
+	let mut offer_log_bytes = offer_log_account_info.try_borrow_mut_data()?;
+	offer_log_bytes[0] = OFFER_LOG_VERSION;
+
+	let offer_log_header = OfferLogHeader::load_mut(&mut offer_log_bytes)?;
+	offer_log_header.bump = bump;
+	offer_log_header.operation = *operation;
+	offer_log_header.set_count(1);
+
+	offer_log_bytes[OFFER_LOG_HEADER_LEN..].copy_from_slice(bytes_of(offer));
+
+	Ok(0)
+}
+
+/// Appends an offer to the offer log of an operation, growing the account by one offer. Returns the index of the offer.
+fn append_offer<'a>(program_id: &Pubkey, operation: &Pubkey,
+	payer_account_info: &AccountInfo<'a>, offer_log_account_info: &AccountInfo<'a>,
+	system_program_account_info: &AccountInfo<'a>, offer: &Offer) -> Result<u32, ProgramError> {
+
+	is_owned_and_writable(program_id, offer_log_account_info, "OFFERLOG account is not a valid account.")?;
+
+	if !check_id(system_program_account_info.key) {
+		msg!("Invalid System program");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let count = {
+		let mut offer_log_bytes = offer_log_account_info.try_borrow_mut_data()?;
+		let offer_log_len = offer_log_bytes.len();
+		let offer_log_header = OfferLogHeader::load_mut(&mut offer_log_bytes)?;
+
+		if offer_log_header.operation != *operation {
+			msg!("Offer log belongs to another operation.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		if offer_log_len != OFFER_LOG_HEADER_LEN + offer_log_header.count() as usize * OFFER_LEN {
+			return Err(ProgramError::InvalidAccountData)
+		}
+
+		offer_log_header.count()
+	};
+
+	let offer_offset = OFFER_LOG_HEADER_LEN + count as usize * OFFER_LEN;
+	let offer_log_len = offer_offset + OFFER_LEN;
+
+	let rent_exemption_balance = match Rent::get() {
+		Err(_e) => return Err(ProgramError::Custom(SCAError::RentError as u32)),
+		Ok(rent) => rent.minimum_balance(offer_log_len),
+	};
+
+	let rent_shortfall = rent_exemption_balance.saturating_sub(offer_log_account_info.lamports());
+
+	if rent_shortfall > 0 {
+		invoke(
+			&transfer(payer_account_info.key, offer_log_account_info.key, rent_shortfall),
+			&[payer_account_info.clone(), offer_log_account_info.clone(), system_program_account_info.clone()],
+		)?;
+	}
+
+	// The whole new offer is written below, no need to zero it first
+	offer_log_account_info.realloc(offer_log_len, false)?;
+
+	// Save
+	let mut offer_log_bytes = offer_log_account_info.try_borrow_mut_data()?;
+	offer_log_bytes[offer_offset..].copy_from_slice(bytes_of(offer));
+	OfferLogHeader::load_mut(&mut offer_log_bytes)?.set_count(count + 1);
+
+	Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bytemuck::{cast_slice, Zeroable};
+
+	use crate::scatype::OPERATION_ACCOUNT_VERSION;
+
+	const SELLER: Pubkey = Pubkey::new_from_array([1; 32]);
+	const BUYER: Pubkey = Pubkey::new_from_array([2; 32]);
+	const OTHER_BUYER: Pubkey = Pubkey::new_from_array([3; 32]);
+	const ARBITER: Pubkey = Pubkey::new_from_array([7; 32]);
+	const OPERATION: Pubkey = Pubkey::new_from_array([9; 32]);
+
+	const VALUE: u64 = 1_000;
+	const BALANCE: u64 = 5_000;
+
+	fn operation() -> OperationAccount {
+		let mut operation_account_data = OperationAccount::zeroed();
+		operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+		operation_account_data.set_status(OperationStatus::Opened);
+		operation_account_data.set_value(VALUE);
+		operation_account_data.seller = SELLER;
+
+		operation_account_data
+	}
+
+	/// Lays out the offer log of [OPERATION] with the given offers, none of them accepted.
+	fn offer_log(offers: &[Offer]) -> Vec<u8> {
+		let mut offer_log_header = OfferLogHeader::zeroed();
+		offer_log_header.version = OFFER_LOG_VERSION;
+		offer_log_header.bump = offer_log_address(&crate::id(), &OPERATION).1;
+		offer_log_header.operation = OPERATION;
+		offer_log_header.set_count(offers.len() as u32);
+
+		[bytes_of(&offer_log_header), cast_slice(offers)].concat()
+	}
+
+	/// Runs [accept_offer] by the seller, returning the result, the operation and the offer log afterwards.
+	fn accept(operation_account_data: &OperationAccount, offer_log_data: &[u8], index: u32)
+		-> (ProgramResult, OperationAccount, Vec<u8>) {
+
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let offer_log_key = offer_log_address(&program_id, &OPERATION).0;
+
+		let mut operation_data = bytes_of(operation_account_data).to_vec();
+		let mut offer_log_data = offer_log_data.to_vec();
+		let (mut seller_lamports, mut operation_lamports, mut offer_log_lamports) = (BALANCE, BALANCE, BALANCE);
+		let mut seller_data = vec![];
+
+		let accounts = [
+			AccountInfo::new(&SELLER, true, true, &mut seller_lamports, &mut seller_data, &system_program, false, 0),
+			AccountInfo::new(&OPERATION, false, true, &mut operation_lamports, &mut operation_data, &program_id, false, 0),
+			AccountInfo::new(&offer_log_key, false, true, &mut offer_log_lamports, &mut offer_log_data, &program_id, false, 0),
+		];
+
+		let result = accept_offer(&program_id, &accounts, index);
+		drop(accounts);
+
+		(result, *OperationAccount::load(&operation_data).unwrap(), offer_log_data)
+	}
+
+	fn accepted(offer_log_data: &[u8]) -> Vec<bool> {
+		let offers: &[Offer] = cast_slice(&offer_log_data[OFFER_LOG_HEADER_LEN..]);
+		offers.iter().map(Offer::accepted).collect()
+	}
+
+	#[test]
+	fn registers_the_buyer_at_the_offered_price() {
+		let offers = [Offer::new(BUYER, 800, 10), Offer::new(OTHER_BUYER, 900, 20)];
+		let (result, operation_account_data, offer_log_data) = accept(&operation(), &offer_log(&offers), 1);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(operation_account_data.buyer, OTHER_BUYER);
+		assert_eq!(operation_account_data.value(), 900);
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::BuyerRegistered));
+		assert_eq!(accepted(&offer_log_data), [false, true]);
+
+		// Once registered, no other offer can be accepted
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation_account_data, &offer_log_data, 0);
+		assert_eq!(result, Err(ProgramError::UninitializedAccount));
+	}
+
+	#[test]
+	fn keeps_the_designated_arbiters() {
+		let mut operation_account_data = operation();
+		operation_account_data.arbiter1 = ARBITER;
+		operation_account_data.arbiter2 = Pubkey::new_from_array([5; 32]);
+		operation_account_data.arbiter3 = Pubkey::new_from_array([6; 32]);
+
+		let offers = [Offer::new(BUYER, 800, 10), Offer::new(ARBITER, 900, 20)];
+
+		let (result, accepted_data, _offer_log_data) = accept(&operation_account_data, &offer_log(&offers), 0);
+		assert_eq!(result, Ok(()));
+		assert_eq!(accepted_data.status(), Ok(OperationStatus::ArbitersRegistered));
+
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation_account_data, &offer_log(&offers), 1);
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+	}
+
+	#[test]
+	fn rejects_accepted_and_missing_offers() {
+		let mut offers = [Offer::new(BUYER, 800, 10)];
+		offers[0].set_accepted(true);
+
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation(), &offer_log(&offers), 0);
+		assert_eq!(result, Err(ProgramError::AccountAlreadyInitialized));
+
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation(), &offer_log(&offers), 1);
+		assert_eq!(result, Err(ProgramError::InvalidArgument));
+	}
+
+	#[test]
+	fn rejects_offers_older_than_the_listing() {
+		let mut operation_account_data = operation();
+		operation_account_data.set_listing_revision(1);
+		operation_account_data.set_listing_updated_at(20);
+
+		let offers = [Offer::new(BUYER, 800, 10), Offer::new(BUYER, 850, 20), Offer::new(OTHER_BUYER, 900, 30)];
+
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation_account_data, &offer_log(&offers), 0);
+		assert_eq!(result, Err(ProgramError::InvalidArgument));
+
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation_account_data, &offer_log(&offers), 1);
+		assert_eq!(result, Err(ProgramError::InvalidArgument));
+
+		let (result, operation_account_data, _offer_log_data) = accept(&operation_account_data, &offer_log(&offers), 2);
+		assert_eq!(result, Ok(()));
+		assert_eq!(operation_account_data.value(), 900);
+	}
+
+	#[test]
+	fn split_operations_keep_their_price() {
+		let offers = [Offer::new(BUYER, 800, 10)];
+
+		let mut operation_account_data = operation();
+		operation_account_data.set_milestone_count(2);
+
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation_account_data, &offer_log(&offers), 0);
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+
+		let mut operation_account_data = operation();
+		operation_account_data.set_line_item_count(2);
+
+		let (result, _operation_account_data, _offer_log_data) = accept(&operation_account_data, &offer_log(&offers), 0);
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+	}
+}
@@ -1,21 +1,31 @@
-//!	# operation
-//!	Functionality related to Operations
+//! # operation
+//! Functionality related to Operations
 //!
 //! List of supported instructions
 //!
 //! 1. Initialize Operation -> [initialize_operation]
+//! 2. Update Listing -> [update_listing]
+//! 3. Register Buyer -> [register_buyer]
+//! 4. Register Buyer And Deposit -> [register_buyer_and_deposit]
+//! 5. Reassign Buyer -> [reassign_buyer]
+//! 6. Invite Arbiter -> [invite_arbiter]
+//! 7. Register Arbiter -> [register_arbiter]
+//! 8. Participant Approves Arbiters -> [participant_approves_arbiters]
+//! 9. Reject Arbiter -> [reject_arbiter]
+//! 10. Buyer Deposit -> [buyer_deposit]
+//! 11. Buyer Release -> [buyer_release]
+//! 12. Seller Refund -> [seller_refund]
+//! 13. Seller Partial Refund -> [seller_partial_refund]
 //!
 //!
 
-use borsh::{BorshDeserialize, BorshSerialize};
-
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg, system_instruction::transfer,
     program_error::ProgramError,
     pubkey::Pubkey, system_program::check_id,
-	clock::Clock, program::invoke,
+	clock::{Clock, UnixTimestamp}, program::invoke,
 	sysvar::{
 		Sysvar,
 		rent::Rent,
@@ -23,11 +33,11 @@ use solana_program::{
 
 };
 
-use std::str;
+use crate::content::{ContentRef, ContentRefData};
 
 use crate::scatype::{
-	OperationAccount, OperationStatus, TokenVersion,
-	ZERO_ACCOUNT, SCAError
+	OperationAccount, OperationStatus, TokenVersion, ArbiterProfile, ArbiterStake, ArbiterSelection,
+	ZERO_ACCOUNT, SCAError, OPERATION_ACCOUNT_VERSION, APPEAL_SEAT,
 };
 
 use crate::account::{
@@ -35,20 +45,28 @@ use crate::account::{
 	is_signed_by_writable_account,
 };
 
+use crate::arbiter::{check_arbiter_profile, check_arbiter_stake, release_seat};
+
+use crate::dispute::current_timestamp;
+
 /// Initializes an Operation. Note that this function expectes a CLOSED [OperationAccount].
 /// It resets the account before using it, to make absolutely sure it's empty.
+/// An intended buyer reserves the operation, so no one else can register.
+/// An attestor can sign the delivery of the item instead of the buyer, see [crate::shipping::attest_delivery].
+/// An inspection period lets anyone release the value once the buyer had that long to dispute the delivery,
+/// see [crate::shipping::finalize_release].
 ///
 /// operation_data: A tuple conformant to [OperationInstruction::InitializeOperation]
 ///
-///	Accounts:
-///	1. SELLER - Account of the item seller, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Initialized here, reused elsewhere. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Initialized here, reused elsewhere. Comformant to [OperationAccount]
 #[inline(never)]
 pub fn initialize_operation(program_id: &Pubkey, accounts: &[AccountInfo], 
-	operation_data: (u64, TokenVersion,  [u8;46])) -> ProgramResult {
+	operation_data: (u64, TokenVersion, ContentRef, ArbiterSelection, Option<Pubkey>, Option<Pubkey>, UnixTimestamp)) -> ProgramResult {
 
 	// Destructure operation data tuple
-	let (value,  token_version, ipfs_hash_bytes) = operation_data;
+	let (value,  token_version, item_content, arbiter_selection, intended_buyer, attestor, inspection_period) = operation_data;
 
 	// Iterating accounts is safer than indexing
 	let accounts_iter = &mut accounts.iter();
@@ -81,66 +99,180 @@ pub fn initialize_operation(program_id: &Pubkey, accounts: &[AccountInfo],
 		Ok(clock) => clock.unix_timestamp,
 	};
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this a previously activated DATA account??? -> Reject
-	if operation_account_data.status != OperationStatus::Closed {
+	if operation_account_data.status()? != OperationStatus::Closed {
 		msg!("Operation account already in use.");
 		return Err(ProgramError::AccountAlreadyInitialized)
 	}
 
 	// ======================= Enforce data validity using accounts data section
 
-	// Bytes to string, must be valid utf-8 (base58btc ascii in practice)
-	let ipfs_hash_str = match str::from_utf8(&ipfs_hash_bytes) {
-		Ok(v) => v,
-		Err(_e) => return Err(ProgramError::InvalidInstructionData),
-	};
+	// The content reference was already validated when the instruction was unpacked
 
-	if !ipfs_hash_str.starts_with("Qm") {
-		msg!("Invalid IPFS hash.");
-		return Err(ProgramError::InvalidArgument);
+	if intended_buyer == Some(*seller_account_info.key) {
+		msg!("Invalid intended Buyer account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// The parties cannot attest the delivery themselves
+	if attestor.is_some_and(|attestor| attestor == *seller_account_info.key || Some(attestor) == intended_buyer) {
+		msg!("Invalid Attestor account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if inspection_period < 0 {
+		msg!("Inspection period cannot be negative.");
+		return Err(ProgramError::InvalidArgument)
 	}
 
 	// ========================= Change state section
 
 	// Set internally; make sure everything not used is zeroed out anyway.
-	operation_account_data.status = OperationStatus::Opened;
-	operation_account_data.created_at = unix_timestamp;
+	operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+	operation_account_data.set_status(OperationStatus::Opened);
+	operation_account_data.set_created_at(unix_timestamp);
 	operation_account_data.buyer = Default::default(); // Not known yet at this point.
 
 	operation_account_data.arbiter1 = Default::default(); // Not known yet at this point.
 	operation_account_data.arbiter2 = Default::default(); // Not known yet at this point.
 	operation_account_data.arbiter3 = Default::default(); // Not known yet at this point.
 
-	operation_account_data.seller_approved = false;
-	operation_account_data.buyer_approved = false;
+	operation_account_data.set_seller_approved(false);
+	operation_account_data.set_buyer_approved(false);
 
 	// Set externally
-	operation_account_data.token_version = token_version;
-	operation_account_data.value = value;
+	operation_account_data.set_token_version(token_version);
+	operation_account_data.set_value(value);
 	operation_account_data.seller = *seller_account_info.key;
-	operation_account_data.ipfs = ipfs_hash_bytes;
+	operation_account_data.item_content = ContentRefData::from(&item_content);
+	operation_account_data.set_arbiter_selection(arbiter_selection);
+	operation_account_data.intended_buyer = intended_buyer.unwrap_or_default();
+
+	operation_account_data.set_listing_revision(0);
+	operation_account_data.set_listing_updated_at(0);
+
+	operation_account_data.set_shipped_at(0);
+	operation_account_data.tracking_content = Default::default();
+	operation_account_data.set_delivered_at(0);
+	operation_account_data.attestor = attestor.unwrap_or_default();
+	operation_account_data.set_inspection_period(inspection_period);
+	operation_account_data.set_inspection_deadline(0);
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Operation successfully initialized!");
 
 	Ok(())
 }
 
-/// Allows a Buyer to confirm his participation in an operation.
+/// Allows a Seller to change the price and item of an operation, before a buyer registers.
+/// Every update bumps the listing revision, which buyers can assert when registering,
+/// and offers made before the update can no longer be accepted.
+/// Operations split in milestones or line items keep their price, only the item can change.
 /// Note that this function expectes an OPENED [OperationAccount].
 ///
-///	Accounts:
-///	1. BUYER - Account of the item buyer, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// listing_data: A tuple conformant to [OperationInstruction::UpdateListing]
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
 #[inline(never)]
-pub fn register_buyer(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+pub fn update_listing(program_id: &Pubkey, accounts: &[AccountInfo], 
+	listing_data: (u64, ContentRef)) -> ProgramResult {
+
+	// Destructure listing data tuple
+	let (value, item_content) = listing_data;
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::Opened {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	// The content reference was already validated when the instruction was unpacked
+
+	// Milestone amounts and line item totals must keep adding up to the value
+	if (operation_account_data.milestone_count() != 0 || operation_account_data.line_item_count() != 0) &&
+		value != operation_account_data.value() {
+		msg!("Operations with milestones or line items keep their price.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let listing_revision = operation_account_data.listing_revision().checked_add(1)
+		.ok_or(ProgramError::InvalidAccountData)?;
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_listing_revision(listing_revision);
+	operation_account_data.set_listing_updated_at(current_timestamp());
+
+	// Set externally
+	operation_account_data.set_value(value);
+	operation_account_data.item_content = ContentRefData::from(&item_content);
+
+	msg!("Listing updated, revision: {}", listing_revision);
+
+	Ok(())
+}
+
+/// Allows a Buyer to confirm his participation in an operation, on the terms they reviewed.
+/// The registration fails if the value, token or item differ from the current ones,
+/// or if a listing revision is given and the listing was updated since. See [update_listing].
+/// Operations reserved for an intended buyer only accept that one.
+/// Note that this function expectes an OPENED [OperationAccount].
+///
+/// buyer_data: A tuple conformant to [OperationInstruction::RegisterBuyer]
+///
+/// Accounts:
+/// 1. BUYER - Account of the item buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn register_buyer(program_id: &Pubkey, accounts: &[AccountInfo], 
+	buyer_data: (u64, TokenVersion, ContentRef, Option<u32>)) -> ProgramResult {
+
+	// Destructure buyer data tuple
+	let (value, token_version, item_content, listing_revision) = buyer_data;
 
 	// Iterating accounts is safer than indexing
 	let accounts_iter = &mut accounts.iter();
@@ -163,14 +295,15 @@ pub fn register_buyer(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
 	// ================ Enforce configuration rules section
 
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::Opened {
+	if operation_account_data.status()? != OperationStatus::Opened {
 		msg!("Operation account not setup.");
 		return Err(ProgramError::UninitializedAccount)
 	}
@@ -182,28 +315,376 @@ pub fn register_buyer(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
 
 	// ======================= Enforce data validity using accounts data section
 
+	check_listing_terms(operation_account_data, buyer_account_info.key,
+		value, token_version, &item_content, listing_revision)?;
 
 	// ========================= Change state section
 
 	// Set internally; make sure everything not used is zeroed out anyway.
-	operation_account_data.status = OperationStatus::BuyerRegistered;
+	// Arbiters designated by the seller beforehand only wait for the buyer approval.
+	if operation_account_data.arbiters_filled() {
+		operation_account_data.set_status(OperationStatus::ArbitersRegistered);
+	} else {
+		operation_account_data.set_status(OperationStatus::BuyerRegistered);
+	}
 
 	// Set externally
 	operation_account_data.buyer = *buyer_account_info.key;
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Buyer registered to operation successfully.");
 
 	Ok(())
 }
 
-/// Allows an arbiter to confirm his participation in an operation.
-/// Note that this function expectes an BuyerRegistered [OperationAccount].
+/// Allows a Buyer to register, approve the arbiters designated by the seller and deposit in a single step,
+/// for deals agreed beforehand. Either every step succeeds or none does.
+/// The terms are checked as in [register_buyer], including the reservation for an intended buyer.
+/// Note that this function expects an OPENED [OperationAccount] with Manual arbiter selection,
+/// in which the seller already designated the 3 arbiters: they registered and the seller approved them
+/// while the operation was open, see [invite_arbiter]. Otherwise the buyer must use [register_buyer].
 ///
-///	Accounts:
-///	1. ARBITER - Account of one of the arbiters, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// deal_data: A tuple conformant to [OperationInstruction::RegisterBuyerAndDeposit]
+///
+/// Accounts:
+/// 1. BUYER - Account of the item buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. SYSTEM PROGRAM - Used to transfer the deposit.
+#[inline(never)]
+pub fn register_buyer_and_deposit(program_id: &Pubkey, accounts: &[AccountInfo], 
+	deal_data: (u64, TokenVersion, ContentRef, [Pubkey; 3], Option<u32>)) -> ProgramResult {
+
+	// Destructure deal data tuple
+	let (value, token_version, item_content, arbiters, listing_revision) = deal_data;
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get BUYER account
+	let buyer_account_info = next_account_info(accounts_iter)?;
+
+	// Check BUYER account validity
+	is_signed_by_writable_account(buyer_account_info, "BUYER account is not a valid account.")?;
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+	// ================ Enforce configuration rules section
+
+	if !check_id(system_program_account_info.key) {
+		msg!("Invalid System program");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::Opened {
+		msg!("Operation account not setup.");
+		return Err(ProgramError::UninitializedAccount)
+	}
+
+	if operation_account_data.arbiter_selection()? != ArbiterSelection::Manual {
+		msg!("Arbiters are selected at random for this operation.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if !operation_account_data.arbiters_filled() || !operation_account_data.seller_approved() {
+		msg!("Arbiters were not designated by the seller.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *buyer_account_info.key == operation_account_data.seller {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	check_listing_terms(operation_account_data, buyer_account_info.key,
+		value, token_version, &item_content, listing_revision)?;
+
+	// The buyer approves the arbiters they reviewed, not whoever holds the slots now
+	if arbiters != [operation_account_data.arbiter1, operation_account_data.arbiter2, operation_account_data.arbiter3] {
+		msg!("Arbiters differ from the ones the buyer approved.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// Get deposit for buyer account
+	let rent_exemption_balance = match Rent::get() {
+		Err(_e) => return Err(ProgramError::Custom(SCAError::RentError as u32)),
+		Ok(rent) => rent.minimum_balance(buyer_account_info.data_len()),
+	};
+
+	if buyer_account_info.lamports() < (rent_exemption_balance + operation_account_data.value()) {
+		return Err(ProgramError::InsufficientFunds);
+	}
+
+	// ========================= Change state section
+
+	let deposit_value = operation_account_data.value();
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_buyer_approved(true);
+	operation_account_data.set_status(OperationStatus::BuyerDeposited);
+
+	// Set externally
+	operation_account_data.buyer = *buyer_account_info.key;
+
+	// The account data must be released before the transfer can borrow it.
+	drop(operation_account_bytes);
+
+	let instruction_transfer = transfer(
+		buyer_account_info.key, // Payer
+		operation_account_info.key, // Recipient
+		deposit_value
+	);
+
+	invoke(
+		&instruction_transfer,
+		&[buyer_account_info.clone(), operation_account_info.clone(), system_program_account_info.clone()],
+	)?;
+
+	msg!("Buyer registered, approved arbiters and deposited token value ok.");
+
+	Ok(())
+}
+
+/// Rejects a buyer who cannot take the operation, or who agreed to different terms than the current ones.
+fn check_listing_terms(operation_account_data: &OperationAccount, buyer: &Pubkey,
+	value: u64, token_version: TokenVersion, item_content: &ContentRef, listing_revision: Option<u32>) -> ProgramResult {
+
+	if operation_account_data.intended_buyer.to_bytes() != ZERO_ACCOUNT &&
+		*buyer != operation_account_data.intended_buyer {
+		msg!("Operation reserved for another buyer.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// Arbiters designated or invited beforehand, and the attestor, cannot be the buyer
+	if operation_account_data.is_arbiter(buyer) || operation_account_data.is_invited(buyer) ||
+		*buyer == operation_account_data.attestor {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if listing_revision.is_some_and(|revision| revision != operation_account_data.listing_revision()) {
+		msg!("Listing updated since the buyer agreed to it.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if value != operation_account_data.value() ||
+		token_version != operation_account_data.token_version()? ||
+		ContentRefData::from(item_content) != operation_account_data.item_content {
+		msg!("Listing terms differ from the ones the buyer agreed to.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	Ok(())
+}
+
+/// Allows a Seller to replace the registered buyer, for instance after someone else registered first.
+/// The operation opens again, reserved for the new buyer, who must still register with [register_buyer]
+/// and agree to the listing terms. Every arbiter invited or registered so far is dropped,
+/// since the previous buyer may have picked them.
+/// Note that this function expectes a BuyerRegistered [OperationAccount].
+///
+/// buyer: Public key of the new buyer, conformant to [OperationInstruction::ReassignBuyer]
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. ARBITERSTAKE... - Stake of each registered arbiter, in any order, which gets unlocked. Comformant to [ArbiterStake]
+#[inline(never)]
+pub fn reassign_buyer(program_id: &Pubkey, accounts: &[AccountInfo], buyer: Pubkey) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::BuyerRegistered {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if buyer.to_bytes() == ZERO_ACCOUNT || buyer == operation_account_data.seller || buyer == operation_account_data.buyer {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	let arbiters = [operation_account_data.arbiter1, operation_account_data.arbiter2, operation_account_data.arbiter3];
+
+	for (seat, arbiter) in arbiters.iter().enumerate() {
+		release_seat(program_id, operation_account_data, seat, arbiter, accounts_iter.as_slice())?;
+	}
+
+	operation_account_data.arbiter1 = Default::default();
+	operation_account_data.arbiter2 = Default::default();
+	operation_account_data.arbiter3 = Default::default();
+	operation_account_data.arbiter_invites = Default::default();
+
+	operation_account_data.set_seller_approved(false);
+	operation_account_data.set_buyer_approved(false);
+	operation_account_data.buyer = Default::default();
+	operation_account_data.set_status(OperationStatus::Opened);
+
+	// Set externally
+	operation_account_data.intended_buyer = buyer;
+
+	msg!("Buyer reassigned.");
+
+	Ok(())
+}
+
+/// Allows a Buyer/Seller to invite an arbiter, who can then register with [register_arbiter].
+/// The seller can designate the arbiters of a pre-agreed deal while the operation is still open,
+/// see [register_buyer_and_deposit].
+/// Note that this function expectes an Opened/BuyerRegistered [OperationAccount],
+/// or an InDispute/InVoting one with a slot freed by [crate::dispute::replace_arbiters].
+/// Operations with random arbiter selection take no invitations.
+///
+/// arbiter: Public key of the arbiter to invite, conformant to [OperationInstruction::InviteArbiter]
+///
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn invite_arbiter(program_id: &Pubkey, accounts: &[AccountInfo], 
+	arbiter: Pubkey) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get PARTICIPANT account
+	let participant_account_info = next_account_info(accounts_iter)?;
+
+	// Check PARTICIPANT account validity
+	is_signed_by_writable_account(participant_account_info, "PARTICIPANT account is not a valid account.")?;
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	let status = operation_account_data.status()?;
+
+	if status != OperationStatus::Opened &&
+		status != OperationStatus::BuyerRegistered &&
+		status != OperationStatus::InDispute &&
+		status != OperationStatus::InVoting {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if operation_account_data.arbiter_selection()? != ArbiterSelection::Manual {
+		msg!("Arbiters are selected at random for this operation.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *participant_account_info.key != operation_account_data.seller &&
+		*participant_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer/Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if arbiter.to_bytes() == ZERO_ACCOUNT ||
+		arbiter == operation_account_data.seller ||
+		arbiter == operation_account_data.buyer ||
+		arbiter == operation_account_data.intended_buyer {
+		msg!("Invalid Arbiter account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if operation_account_data.is_invited(&arbiter) {
+		msg!("Arbiter already invited.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	// ========================= Change state section
+
+	// Set externally
+	operation_account_data.add_invite(arbiter)?;
+
+	msg!("Arbiter invited.");
+
+	Ok(())
+}
+
+/// Allows an invited arbiter to confirm their participation in an operation.
+/// The arbiter must have an active profile, see [crate::arbiter::register_arbiter_profile],
+/// and an eligible stake, see [crate::arbiter::stake_arbiter], which stays locked while they hold the seat.
+/// Note that this function expectes an Opened/BuyerRegistered [OperationAccount],
+/// or an InDispute/InVoting one with a slot freed by [crate::dispute::replace_arbiters].
+/// Arbiters picked from the pool must confirm their seat here before voting, once the dispute started,
+/// and so must the appeal panel, see [crate::appeal::appeal_verdict].
+///
+/// Accounts:
+/// 1. ARBITER - Account of one of the arbiters, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. ARBITERPROFILE - Profile of the arbiter. Comformant to [ArbiterProfile]
+/// 4. ARBITERSTAKE - Stake of the arbiter. Comformant to [ArbiterStake]
 #[inline(never)]
 pub fn register_arbiter(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
 
@@ -224,30 +705,101 @@ pub fn register_arbiter(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
 	// Check OPERATIONACCOUNT account validity
 	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
 
+	// Get the ARBITERPROFILE account
+	let arbiter_profile_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITERPROFILE account validity
+	check_arbiter_profile(program_id, arbiter_account_info.key, arbiter_profile_account_info)?;
+
+	// Get the ARBITERSTAKE account
+	let arbiter_stake_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITERSTAKE account validity
+	check_arbiter_stake(program_id, arbiter_account_info.key, arbiter_stake_account_info)?;
+
 
 	// ================ Enforce configuration rules section
 
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	let mut arbiter_profile_bytes = arbiter_profile_account_info.try_borrow_mut_data()?;
+	let arbiter_profile_data = ArbiterProfile::load_mut(&mut arbiter_profile_bytes)?;
+
+	let mut arbiter_stake_bytes = arbiter_stake_account_info.try_borrow_mut_data()?;
+	let arbiter_stake_data = ArbiterStake::load_mut(&mut arbiter_stake_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::BuyerRegistered {
-		msg!("Operation account does not have a Buyer.");
+	let status = operation_account_data.status()?;
+
+	if status != OperationStatus::Opened &&
+		status != OperationStatus::BuyerRegistered &&
+		status != OperationStatus::InDispute &&
+		status != OperationStatus::InVoting &&
+		status != OperationStatus::InAppeal {
+		msg!("Operation account incorrect state.");
 		return Err(ProgramError::InvalidAccountData)
 	}
 
 	if *arbiter_account_info.key == operation_account_data.seller ||
-		*arbiter_account_info.key == operation_account_data.buyer {
+		*arbiter_account_info.key == operation_account_data.buyer ||
+		*arbiter_account_info.key == operation_account_data.intended_buyer {
 		msg!("Invalid Arbiter account.");
 		return Err(ProgramError::InvalidAccountData)
 	}
 
 	// ======================= Enforce data validity using accounts data section
 
+	// Arbiters picked from the pool already hold their seat, and confirm it here
+	if status == OperationStatus::InAppeal || operation_account_data.arbiter_selection()? == ArbiterSelection::Random {
+
+		let seat = match operation_account_data.arbiter_seat(arbiter_account_info.key) {
+			Some(seat) if seat < APPEAL_SEAT && (status == OperationStatus::InDispute || status == OperationStatus::InVoting) => seat,
+			Some(seat) if seat >= APPEAL_SEAT && status == OperationStatus::InAppeal => seat,
+			_ => {
+				msg!("Arbiter was not picked.");
+				return Err(ProgramError::InvalidAccountData)
+			}
+		};
+
+		if operation_account_data.seat_locked(seat) {
+			msg!("Arbiter seat already confirmed.");
+			return Err(ProgramError::AccountAlreadyInitialized)
+		}
+
+		// ========================= Change state section
+
+		// The pick predates any unstake request, so the stake is locked anyway
+		operation_account_data.set_seat_locked(seat, true);
+		arbiter_stake_data.add_active_case();
+		arbiter_profile_data.add_case_accepted();
+
+		msg!("Arbiter confirmed the seat successfully.");
+
+		return Ok(())
+	}
+
+	// Only the arbiters invited by the parties can take a slot
+	if !operation_account_data.is_invited(arbiter_account_info.key) {
+		msg!("Arbiter was not invited.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if !arbiter_profile_data.active() {
+		msg!("Arbiter is not active.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if !arbiter_stake_data.is_eligible() {
+		msg!("Arbiter stake is not enough, or being withdrawn.");
+		return Err(ProgramError::Custom(SCAError::InsufficientStake as u32))
+	}
+
 	if operation_account_data.arbiter1 == *arbiter_account_info.key ||
 		operation_account_data.arbiter2 == *arbiter_account_info.key ||
 		operation_account_data.arbiter3 == *arbiter_account_info.key {
@@ -272,25 +824,35 @@ pub fn register_arbiter(program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
 		return Err(ProgramError::AccountAlreadyInitialized)
 	}
 
-	if operation_account_data.arbiter1.to_bytes() != ZERO_ACCOUNT &&
+	// The stake stays locked until the arbiter votes or leaves the seat
+	if let Some(seat) = operation_account_data.arbiter_seat(arbiter_account_info.key) {
+		operation_account_data.set_seat_locked(seat, true);
+		arbiter_stake_data.add_active_case();
+	}
+
+	// Replacements during a dispute keep the dispute status
+	if status == OperationStatus::BuyerRegistered &&
+		operation_account_data.arbiter1.to_bytes() != ZERO_ACCOUNT &&
 		operation_account_data.arbiter2.to_bytes() != ZERO_ACCOUNT &&
 		operation_account_data.arbiter3.to_bytes() != ZERO_ACCOUNT {
-		operation_account_data.status = OperationStatus::ArbitersRegistered;
+		operation_account_data.set_status(OperationStatus::ArbitersRegistered);
 	}
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
+	arbiter_profile_data.add_case_accepted();
+
 	msg!("Arbiter registered to operation successfully.");
 
 	Ok(())
 }
 
 /// Allows a Buyer/Seller to confirm approval of arbiters
-/// Note that this function expectes an ArbitersRegistered [OperationAccount].
+/// Note that this function expectes an ArbitersRegistered [OperationAccount],
+/// or an InDispute/InVoting one whose arbiters were replaced,
+/// or an Opened one whose arbiters were designated by the seller, who approves them alone.
 ///
-///	Accounts:
-///	1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
 #[inline(never)]
 pub fn participant_approves_arbiters(program_id: &Pubkey, accounts: &[AccountInfo], 
 	is_seller: bool) -> ProgramResult {
@@ -316,14 +878,27 @@ pub fn participant_approves_arbiters(program_id: &Pubkey, accounts: &[AccountInf
 	// ================ Enforce configuration rules section
 
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::ArbitersRegistered {
+	let status = operation_account_data.status()?;
+
+	let replaced_arbiters_registered = (status == OperationStatus::InDispute ||
+		status == OperationStatus::InVoting) &&
+		operation_account_data.arbiter1.to_bytes() != ZERO_ACCOUNT &&
+		operation_account_data.arbiter2.to_bytes() != ZERO_ACCOUNT &&
+		operation_account_data.arbiter3.to_bytes() != ZERO_ACCOUNT;
+
+	let designated_arbiters_registered = status == OperationStatus::Opened &&
+		is_seller &&
+		operation_account_data.arbiters_filled();
+
+	if status != OperationStatus::ArbitersRegistered && !replaced_arbiters_registered && !designated_arbiters_registered {
 		msg!("Operation account not setup.");
 		return Err(ProgramError::UninitializedAccount)
 	}
@@ -331,44 +906,147 @@ pub fn participant_approves_arbiters(program_id: &Pubkey, accounts: &[AccountInf
 
 	// ======================= Enforce data validity using accounts data section
 
-	if is_seller == true {
+	if is_seller {
 		if *participant_account_info.key != operation_account_data.seller {
 			msg!("Invalid Seller account.");
 			return Err(ProgramError::InvalidAccountData)
 		}	
-		operation_account_data.seller_approved = true;
+		operation_account_data.set_seller_approved(true);
 	} else {
 		if *participant_account_info.key != operation_account_data.buyer {
 			msg!("Invalid Buyer account.");
 			return Err(ProgramError::InvalidAccountData)
 		}
-		operation_account_data.buyer_approved = true;
+		operation_account_data.set_buyer_approved(true);
 	}
 
 	// ========================= Change state section
 
 	// Set internally; make sure everything not used is zeroed out anyway.
 
-	if operation_account_data.seller_approved == true &&
-		operation_account_data.buyer_approved == true {
-			operation_account_data.status = OperationStatus::ArbitersApproved;
+	// Approvals during a dispute keep the dispute status
+	if status == OperationStatus::ArbitersRegistered &&
+		operation_account_data.seller_approved() &&
+		operation_account_data.buyer_approved() {
+			operation_account_data.set_status(OperationStatus::ArbitersApproved);
+	}
+
+	msg!("Participant approved operation successfully.");
+
+	Ok(())
+}
+
+/// Allows a Buyer/Seller to reject a registered arbiter, before both approved the arbiters.
+/// The arbiter slot is freed for another arbiter to register, and any approval is reset.
+/// The invitation of the arbiter is revoked, so pending invitations can be rejected too.
+/// Note that this function expectes an Opened/BuyerRegistered/ArbitersRegistered [OperationAccount].
+///
+/// arbiter: Public key of the arbiter to reject, conformant to [OperationInstruction::RejectArbiter]
+///
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. ARBITERSTAKE - Only for a registered arbiter, whose stake is unlocked. Comformant to [ArbiterStake]
+#[inline(never)]
+pub fn reject_arbiter(program_id: &Pubkey, accounts: &[AccountInfo], 
+	arbiter: Pubkey) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get PARTICIPANT account
+	let participant_account_info = next_account_info(accounts_iter)?;
+
+	// Check PARTICIPANT account validity
+	is_signed_by_writable_account(participant_account_info, "PARTICIPANT account is not a valid account.")?;
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	let status = operation_account_data.status()?;
+
+	if status != OperationStatus::Opened &&
+		status != OperationStatus::BuyerRegistered &&
+		status != OperationStatus::ArbitersRegistered {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *participant_account_info.key != operation_account_data.seller &&
+		*participant_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer/Seller account.");
+		return Err(ProgramError::InvalidAccountData)
 	}
 
+	// ======================= Enforce data validity using accounts data section
+
+	if arbiter.to_bytes() == ZERO_ACCOUNT {
+		msg!("Invalid Arbiter account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ========================= Change state section
+
 	// Set externally
+	let was_invited = operation_account_data.remove_invite(&arbiter);
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
-	msg!("Participant approved operation successfully.");
+	if let Some(seat) = operation_account_data.arbiter_seat(&arbiter) {
+		release_seat(program_id, operation_account_data, seat, &arbiter, accounts_iter.as_slice())?;
+	}
+
+	if operation_account_data.arbiter1 == arbiter {
+		operation_account_data.arbiter1 = Default::default();
+	} else if operation_account_data.arbiter2 == arbiter {
+		operation_account_data.arbiter2 = Default::default();
+	} else if operation_account_data.arbiter3 == arbiter {
+		operation_account_data.arbiter3 = Default::default();
+	} else if was_invited {
+		// Not registered yet, the panel did not change
+		msg!("Arbiter invitation revoked.");
+		return Ok(())
+	} else {
+		msg!("Invalid Arbiter account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// Set internally; the panel changed, so it must be approved again.
+	operation_account_data.set_seller_approved(false);
+	operation_account_data.set_buyer_approved(false);
+
+	// Arbiters designated before a buyer registers keep the operation open
+	if status != OperationStatus::Opened {
+		operation_account_data.set_status(OperationStatus::BuyerRegistered);
+	}
+
+	msg!("Arbiter rejected.");
 
 	Ok(())
 }
 
 /// Allows a Buyer to make his token deposit in an operation.
-/// Note that this function expects an ArbitersApproved [OperationAccount].
+/// Note that this function expects an ArbitersApproved [OperationAccount],
+/// or a BuyerRegistered one whose arbiters are only selected if a dispute starts.
 ///
-///	Accounts:
-///	1. BUYER - Account of the item buyer, who also pays for this transaction.
-///	2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. BUYER - Account of the item buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
 #[inline(never)]
 pub fn buyer_deposit(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
 
@@ -393,19 +1071,25 @@ pub fn buyer_deposit(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 
 	// ================ Enforce configuration rules section
 
-	if check_id(system_program_account_info.key) != true {
+	if !check_id(system_program_account_info.key) {
 		msg!("Invalid System program");
 		return Err(ProgramError::InvalidArgument)
 	}
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::ArbitersApproved {
+	let ready_status = match operation_account_data.arbiter_selection()? {
+		ArbiterSelection::Manual => OperationStatus::ArbitersApproved,
+		ArbiterSelection::Random => OperationStatus::BuyerRegistered,
+	};
+
+	if operation_account_data.status()? != ready_status {
 		msg!("Operation account not setup.");
 		return Err(ProgramError::UninitializedAccount)
 	}
@@ -426,7 +1110,7 @@ pub fn buyer_deposit(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 	// Does the from account have enough lamports to transfer? 
 	// Alternatively, thsi could be a specific acct created for the express purpose
 	// of moving lamports here, in which case would need to match exactly.
-	if buyer_account_info.lamports() < (rent_exemption_balance + operation_account_data.value) {
+	if buyer_account_info.lamports() < (rent_exemption_balance + operation_account_data.value()) {
 		return Err(ProgramError::InsufficientFunds);
 	}
 
@@ -435,14 +1119,22 @@ pub fn buyer_deposit(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 	/* 
 	// Won't work because program does not own Buyer acct.
 	// Debit from_account and credit to_account
-	**buyer_account_info.try_borrow_mut_lamports()? -= operation_account_data.value;
-	**operation_account_info.try_borrow_mut_lamports()? += operation_account_data.value;
+	**buyer_account_info.try_borrow_mut_lamports()? -= operation_account_data.value();
+	**operation_account_info.try_borrow_mut_lamports()? += operation_account_data.value();
 	*/
 
+	let deposit_value = operation_account_data.value();
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_status(OperationStatus::BuyerDeposited);
+
+	// The account data must be released before the transfer can borrow it.
+	drop(operation_account_bytes);
+
 	let instruction_transfer = transfer(
 		buyer_account_info.key, // Payer
 		operation_account_info.key, // Recipient
-		operation_account_data.value
+		deposit_value
 	);
 
 	invoke(
@@ -450,25 +1142,18 @@ pub fn buyer_deposit(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 		&[buyer_account_info.clone(), operation_account_info.clone(), system_program_account_info.clone()],
 	)?;
 
-	// Set internally; make sure everything not used is zeroed out anyway.
-	operation_account_data.status = OperationStatus::BuyerDeposited;
-
-	// Set externally
-
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Buyer deposit token value ok.");
 
 	Ok(())
 }
 
 /// Allows a Buyer to release his token deposit in an operation to the seller.
-/// Note that this function expects a BuyerDeposit [OperationAccount].
+/// Note that this function expects a BuyerDeposited/Shipped/Delivered [OperationAccount].
 ///
-///	Accounts:
-///	1. BUYER - Account of the item buyer, who also pays for this transaction.
-///	2. SELLER - Account of the item seller
-///	3. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. BUYER - Account of the item buyer, who also pays for this transaction.
+/// 2. SELLER - Account of the item seller
+/// 3. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
 #[inline(never)]
 pub fn buyer_release(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
 
@@ -497,14 +1182,15 @@ pub fn buyer_release(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 
 	// ================ Enforce configuration rules section
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::BuyerDeposited {
+	if !operation_account_data.status()?.is_deposited() {
 		msg!("Operation account not setup.");
 		return Err(ProgramError::UninitializedAccount)
 	}
@@ -524,28 +1210,24 @@ pub fn buyer_release(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 	// ========================= Change state section
 
 	// Debit from_account and credit to_account
-	**operation_account_info.try_borrow_mut_lamports()? -= operation_account_data.value;
-	**seller_account_info.try_borrow_mut_lamports()? += operation_account_data.value;
+	**operation_account_info.try_borrow_mut_lamports()? -= operation_account_data.value();
+	**seller_account_info.try_borrow_mut_lamports()? += operation_account_data.value();
 
 	// Set internally; make sure everything not used is zeroed out anyway.
-	operation_account_data.status = OperationStatus::ReleaseRefund;
+	operation_account_data.set_status(OperationStatus::ReleaseRefund);
 
-	// Set externally
-
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Buyer release token value ok.");
 
 	Ok(())
 }
 
 /// Allows a Seller to return the token deposit in an operation to the buyer.
-/// Note that this function expects a BuyerDeposit [OperationAccount].
+/// Note that this function expects a BuyerDeposited/Shipped/Delivered [OperationAccount].
 ///
-///	Accounts:
-///	1. SELLER - Account of the item seller, who also pays for this transaction.
-///	2. BUYER - Account of the item buyer, who also pays for this transaction.
-///	3. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. BUYER - Account of the item buyer, who also pays for this transaction.
+/// 3. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
 #[inline(never)]
 pub fn seller_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
 
@@ -574,14 +1256,15 @@ pub fn seller_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 
 	// ================ Enforce configuration rules section
 
-	// Load the account so that we can read it and/or modify it.
-	let mut operation_account_data = OperationAccount::try_from_slice(&operation_account_info.data.borrow())?;
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
 
 	// ================ Enforce previous state section
 
 
 	// CHECK: Is this DATA account in an incorrect state??? -> Reject
-	if operation_account_data.status != OperationStatus::BuyerDeposited {
+	if !operation_account_data.status()?.is_deposited() {
 		msg!("Operation account not setup.");
 		return Err(ProgramError::UninitializedAccount)
 	}
@@ -601,17 +1284,110 @@ pub fn seller_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
 	// ========================= Change state section
 
 	// Debit from_account and credit to_account
-	**operation_account_info.try_borrow_mut_lamports()? -= operation_account_data.value;
-	**buyer_account_info.try_borrow_mut_lamports()? += operation_account_data.value;
+	**operation_account_info.try_borrow_mut_lamports()? -= operation_account_data.value();
+	**buyer_account_info.try_borrow_mut_lamports()? += operation_account_data.value();
 
 	// Set internally; make sure everything not used is zeroed out anyway.
-	operation_account_data.status = OperationStatus::ReleaseRefund;
-
-	// Set externally
+	operation_account_data.set_status(OperationStatus::ReleaseRefund);
 
-	// Save
-	operation_account_data.serialize(&mut &mut operation_account_info.data.borrow_mut()[..])?;
 	msg!("Seller refund token value ok.");
 
 	Ok(())
 }
+
+/// Allows a Seller to return part of the token deposit in an operation to the buyer, for instance for a minor defect.
+/// The escrowed value is reduced by the refund, and the operation stays open for the buyer to release the rest.
+/// Any settlement proposed for the previous value is dropped. Operations paid in milestones cannot be partially refunded,
+/// and orders of several line items are refunded per line instead.
+/// Note that this function expects a BuyerDeposited/Shipped/Delivered [OperationAccount].
+///
+/// amount: Lamports to refund, less than the escrowed value. Use [seller_refund] to refund it all.
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. BUYER - Account of the item buyer.
+/// 3. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn seller_partial_refund(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+
+	//	Get BUYER account
+	let buyer_account_info = next_account_info(accounts_iter)?;
+
+	// Check BUYER account validity
+	// Buyer is not a signer here, and the pubkey is already stored.
+	
+	// Get the OPERATIONACCOUNT account 
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if !operation_account_data.status()?.is_deposited() {
+		msg!("Operation account not setup.");
+		return Err(ProgramError::UninitializedAccount)
+	}
+
+	if *buyer_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}	
+	// ======================= Enforce data validity using accounts data section
+
+	// Refunds would no longer match the amounts of the milestones
+	if operation_account_data.milestone_count() != 0 {
+		msg!("Operations paid in milestones cannot be partially refunded.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// Refunds of orders must say which line items they cover
+	if operation_account_data.line_item_count() != 0 {
+		msg!("Orders of several line items are refunded per line.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if amount == 0 || amount >= operation_account_data.value() {
+		msg!("Partial refund must be more than zero and less than the token value.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ========================= Change state section
+
+	// Debit from_account and credit to_account
+	**operation_account_info.try_borrow_mut_lamports()? -= amount;
+	**buyer_account_info.try_borrow_mut_lamports()? += amount;
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_value(operation_account_data.value() - amount);
+	operation_account_data.settlement_proposer = Default::default();
+	operation_account_data.set_settlement_buyer_amount(0);
+
+	msg!("Seller partial refund ok, remaining token value: {}", operation_account_data.value());
+
+	Ok(())
+}
@@ -0,0 +1,559 @@
+//! # order
+//! Functionality related to orders of several items in a single operation
+//!
+//! List of supported instructions
+//!
+//! 1. Define Line Items -> [define_line_items]
+//! 2. Refund Line Item -> [refund_line_item]
+//!
+//! The seller lists each item of the order with its unit price and quantity, and their total becomes
+//! the value the buyer deposits. The seller can later refund units of any line, reducing the escrowed value,
+//! so that bundles share a single escrow and arbiter set. See [LineItemsHeader].
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use bytemuck::{cast_slice, cast_slice_mut};
+
+use crate::content::{ContentRef, ContentRefData};
+
+use crate::scatype::{
+	OperationAccount, OperationStatus, LineItemsHeader, LineItem,
+	LINE_ITEMS_VERSION, LINE_ITEMS_SEED, LINE_ITEMS_HEADER_LEN, LINE_ITEM_LEN,
+};
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+	create_pda_account,
+};
+
+/// Returns the address and bump seed of the line items of an operation.
+pub fn line_items_address(program_id: &Pubkey, operation: &Pubkey) -> (Pubkey, u8) {
+	Pubkey::find_program_address(&[LINE_ITEMS_SEED, operation.as_ref()], program_id)
+}
+
+/// Allows a Seller to split the operation in line items, before a buyer registers.
+/// The value of the operation becomes the total of the line items, which the buyer deposits at once as usual.
+/// Milestones, if any, must be defined afterwards, adding up to the new value.
+/// Note that this function expects an Opened [OperationAccount] without line items or milestones.
+///
+/// line_items: Unit price, quantity and content reference of each line item, conformant to [crate::instruction::OperationInstruction::DefineLineItems]
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction and the line items.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. LINEITEMS - Created here. PDA of [LINE_ITEMS_SEED] and OPERATIONACCOUNT.
+/// 4. SYSTEM PROGRAM - Used to create the line items.
+#[inline(never)]
+pub fn define_line_items(program_id: &Pubkey, accounts: &[AccountInfo],
+	line_items: Vec<(u64, u32, ContentRef)>) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the LINEITEMS account
+	let line_items_account_info = next_account_info(accounts_iter)?;
+
+	let (line_items_pubkey, bump) = line_items_address(program_id, operation_account_info.key);
+
+	// Check LINEITEMS account validity
+	if *line_items_account_info.key != line_items_pubkey || !line_items_account_info.is_writable {
+		msg!("LINEITEMS account is not a valid account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::Opened {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// CHECK: Were the line items already defined??? -> Reject
+	if operation_account_data.line_item_count() != 0 {
+		msg!("Line items already defined.");
+		return Err(ProgramError::AccountAlreadyInitialized)
+	}
+
+	// CHECK: Would the new value break the milestones??? -> Reject
+	if operation_account_data.milestone_count() != 0 {
+		msg!("Line items must be defined before the milestones.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// The count and content references were already validated when the instruction was unpacked
+
+	let mut total: u64 = 0;
+
+	for (unit_price, quantity, _content) in &line_items {
+		if *unit_price == 0 || *quantity == 0 {
+			msg!("Line item prices and quantities must be more than zero.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		let line_total = unit_price.checked_mul(*quantity as u64).ok_or(ProgramError::InvalidArgument)?;
+		total = total.checked_add(line_total).ok_or(ProgramError::InvalidArgument)?;
+	}
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_line_item_count(line_items.len() as u8);
+	operation_account_data.set_value(total);
+
+	// Release the account data before calling into the System program
+	drop(operation_account_bytes);
+
+	let line_items_len = LINE_ITEMS_HEADER_LEN + line_items.len() * LINE_ITEM_LEN;
+
+	create_pda_account(program_id, seller_account_info, line_items_account_info, system_program_account_info,
+		line_items_len, &[LINE_ITEMS_SEED, operation_account_info.key.as_ref(), &[bump]])?;
+
+	// Save
+	let mut line_items_bytes = line_items_account_info.try_borrow_mut_data()?;
+	line_items_bytes[0] = LINE_ITEMS_VERSION;
+	line_items_bytes[LINE_ITEMS_HEADER_LEN - 1] = line_items.len() as u8;
+
+	let line_items_header = LineItemsHeader::load_mut(&mut line_items_bytes)?;
+	line_items_header.bump = bump;
+	line_items_header.operation = *operation_account_info.key;
+
+	let line_items: Vec<LineItem> = line_items.iter()
+		.map(|(unit_price, quantity, content)| LineItem::new(*unit_price, *quantity, ContentRefData::from(content)))
+		.collect();
+
+	line_items_bytes[LINE_ITEMS_HEADER_LEN..].copy_from_slice(cast_slice(&line_items));
+
+	msg!("Line items defined: {}, token value: {}", line_items.len(), total);
+
+	Ok(())
+}
+
+/// Allows a Seller to refund units of a single line item to the buyer, for instance when out of stock.
+/// The escrowed value is reduced by the refund, and the operation ends once every unit is refunded.
+/// Any settlement proposed for the previous value is dropped. Orders paid in milestones cannot be refunded per line.
+/// Note that this function expects a BuyerDeposited/Shipped/Delivered [OperationAccount] with line items.
+///
+/// index: Position of the line item, starting at zero.
+/// quantity: Units to refund, up to those not refunded yet.
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. BUYER - Account of the item buyer
+/// 3. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 4. LINEITEMS - Line items of the operation, created by [define_line_items].
+#[inline(never)]
+pub fn refund_line_item(program_id: &Pubkey, accounts: &[AccountInfo], index: u8, quantity: u32) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+
+	//	Get BUYER account
+	let buyer_account_info = next_account_info(accounts_iter)?;
+
+	// Check BUYER account validity
+	// Buyer is not a signer here, and the pubkey is already stored.
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the LINEITEMS account
+	let line_items_account_info = next_account_info(accounts_iter)?;
+
+	// Check LINEITEMS account validity
+	is_owned_and_writable(program_id, line_items_account_info, "LINEITEMS account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	let mut line_items_bytes = line_items_account_info.try_borrow_mut_data()?;
+	let line_items_header = LineItemsHeader::load_mut(&mut line_items_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if !operation_account_data.status()?.is_deposited() {
+		msg!("Operation account not setup.");
+		return Err(ProgramError::UninitializedAccount)
+	}
+
+	if operation_account_data.line_item_count() == 0 {
+		msg!("Operation has no line items.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// Refunds would no longer match the amounts of the milestones
+	if operation_account_data.milestone_count() != 0 {
+		msg!("Operations paid in milestones cannot be refunded per line.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	let line_items_pubkey = Pubkey::create_program_address(
+		&[LINE_ITEMS_SEED, operation_account_info.key.as_ref(), &[line_items_header.bump]], program_id)?;
+
+	if line_items_pubkey != *line_items_account_info.key {
+		msg!("Line items belong to another operation.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *buyer_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if index >= line_items_header.count {
+		msg!("Invalid line item.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let line_items: &mut [LineItem] = cast_slice_mut(&mut line_items_bytes[LINE_ITEMS_HEADER_LEN..]);
+	let line_item = &mut line_items[index as usize];
+
+	if quantity == 0 || quantity > line_item.quantity() - line_item.refunded() {
+		msg!("Refund must be more than zero and up to the units not refunded yet.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// The line totals were checked when defined, and only refunds come out of the value
+	let amount = line_item.unit_price() * quantity as u64;
+
+	if amount > operation_account_data.value() {
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ========================= Change state section
+
+	// Debit from_account and credit to_account
+	**operation_account_info.try_borrow_mut_lamports()? -= amount;
+	**buyer_account_info.try_borrow_mut_lamports()? += amount;
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	line_item.set_refunded(line_item.refunded() + quantity);
+	operation_account_data.set_value(operation_account_data.value() - amount);
+
+	// Settlements proposed for the previous value no longer apply
+	operation_account_data.settlement_proposer = Default::default();
+	operation_account_data.set_settlement_buyer_amount(0);
+
+	if operation_account_data.value() == 0 {
+		operation_account_data.set_status(OperationStatus::ReleaseRefund);
+	}
+
+	msg!("Line item {} refunded: {}, remaining token value: {}", index, amount, operation_account_data.value());
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bytemuck::{bytes_of, Zeroable};
+
+	use crate::scatype::{SCAError, OPERATION_ACCOUNT_VERSION};
+
+	use crate::operation::buyer_release;
+	use crate::dispute::participant_claim;
+	use crate::shipping::finalize_release;
+
+	use crate::content::ARWEAVE_TX_ID_LEN;
+
+	const SELLER: Pubkey = Pubkey::new_from_array([1; 32]);
+	const BUYER: Pubkey = Pubkey::new_from_array([2; 32]);
+	const OPERATION: Pubkey = Pubkey::new_from_array([9; 32]);
+
+	/// Four units at 100 and three at 200.
+	const LINE_ITEMS: [(u64, u32); 2] = [(100, 4), (200, 3)];
+	const VALUE: u64 = 1_000;
+	const BALANCE: u64 = 5_000;
+
+	fn operation(status: OperationStatus) -> OperationAccount {
+		let mut operation_account_data = OperationAccount::zeroed();
+		operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+		operation_account_data.set_status(status);
+		operation_account_data.set_value(VALUE);
+		operation_account_data.seller = SELLER;
+		operation_account_data.buyer = BUYER;
+		operation_account_data.set_line_item_count(LINE_ITEMS.len() as u8);
+		operation_account_data.settlement_proposer = BUYER;
+		operation_account_data.set_settlement_buyer_amount(400);
+
+		operation_account_data
+	}
+
+	/// Lays out the line items account of [OPERATION] with [LINE_ITEMS], none of them refunded.
+	fn line_items() -> Vec<u8> {
+		let line_items_header = LineItemsHeader {
+			version: LINE_ITEMS_VERSION,
+			bump: line_items_address(&crate::id(), &OPERATION).1,
+			operation: OPERATION,
+			count: LINE_ITEMS.len() as u8,
+		};
+
+		let line_items: Vec<LineItem> = LINE_ITEMS.iter()
+			.map(|(unit_price, quantity)| LineItem::new(*unit_price, *quantity, ContentRefData::zeroed()))
+			.collect();
+
+		[bytes_of(&line_items_header), cast_slice(&line_items)].concat()
+	}
+
+	/// Runs [define_line_items] by the seller, returning the result and the operation afterwards.
+	fn define(line_items: &[(u64, u32)]) -> (ProgramResult, OperationAccount) {
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let line_items_key = line_items_address(&program_id, &OPERATION).0;
+
+		let mut operation_account_data = operation(OperationStatus::Opened);
+		operation_account_data.set_value(0);
+		operation_account_data.set_line_item_count(0);
+
+		let mut operation_data = bytes_of(&operation_account_data).to_vec();
+		let (mut seller_lamports, mut operation_lamports, mut line_items_lamports, mut system_lamports) = (BALANCE, BALANCE, 0, 0);
+		let (mut seller_data, mut line_items_data, mut system_data) = (vec![], vec![], vec![]);
+
+		let accounts = [
+			AccountInfo::new(&SELLER, true, true, &mut seller_lamports, &mut seller_data, &system_program, false, 0),
+			AccountInfo::new(&OPERATION, false, true, &mut operation_lamports, &mut operation_data, &program_id, false, 0),
+			AccountInfo::new(&line_items_key, false, true, &mut line_items_lamports, &mut line_items_data, &system_program, false, 0),
+			AccountInfo::new(&system_program, false, false, &mut system_lamports, &mut system_data, &system_program, true, 0),
+		];
+
+		let line_items = line_items.iter()
+			.map(|(unit_price, quantity)| (*unit_price, *quantity, ContentRef::Arweave([1; ARWEAVE_TX_ID_LEN])))
+			.collect();
+
+		let result = define_line_items(&program_id, &accounts, line_items);
+		drop(accounts);
+
+		(result, *OperationAccount::load(&operation_data).unwrap())
+	}
+
+	/// Runs [refund_line_item] by the seller, returning the result, the operation, the line items
+	/// and the lamports of the operation and the buyer afterwards.
+	fn refund(operation_account_data: &OperationAccount, line_items_data: &[u8], index: u8, quantity: u32)
+		-> (ProgramResult, OperationAccount, Vec<u8>, [u64; 2]) {
+
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let line_items_key = line_items_address(&program_id, &OPERATION).0;
+
+		let mut operation_data = bytes_of(operation_account_data).to_vec();
+		let mut line_items_data = line_items_data.to_vec();
+		let mut lamports = [BALANCE + operation_account_data.value(), BALANCE];
+		let [operation_lamports, buyer_lamports] = &mut lamports;
+		let (mut seller_lamports, mut line_items_lamports) = (BALANCE, BALANCE);
+		let (mut seller_data, mut buyer_data) = (vec![], vec![]);
+
+		let accounts = [
+			AccountInfo::new(&SELLER, true, true, &mut seller_lamports, &mut seller_data, &system_program, false, 0),
+			AccountInfo::new(&BUYER, false, true, buyer_lamports, &mut buyer_data, &system_program, false, 0),
+			AccountInfo::new(&OPERATION, false, true, operation_lamports, &mut operation_data, &program_id, false, 0),
+			AccountInfo::new(&line_items_key, false, true, &mut line_items_lamports, &mut line_items_data, &program_id, false, 0),
+		];
+
+		let result = refund_line_item(&program_id, &accounts, index, quantity);
+		drop(accounts);
+
+		(result, *OperationAccount::load(&operation_data).unwrap(), line_items_data, lamports)
+	}
+
+	/// Runs a payout of the whole remaining value, returning the lamports of the operation,
+	/// the seller and the buyer afterwards.
+	fn pay_out(operation_account_data: &OperationAccount, handler: fn(&Pubkey, &[AccountInfo]) -> ProgramResult,
+		accounts: &[Pubkey]) -> [u64; 3] {
+
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+
+		let mut operation_data = bytes_of(operation_account_data).to_vec();
+		let mut lamports = [BALANCE + operation_account_data.value(), BALANCE, BALANCE];
+		let [operation_lamports, seller_lamports, buyer_lamports] = &mut lamports;
+		let (mut seller_data, mut buyer_data) = (vec![], vec![]);
+
+		let seller_account_info = AccountInfo::new(&SELLER, accounts[0] == SELLER, true, seller_lamports, &mut seller_data,
+			&system_program, false, 0);
+		let buyer_account_info = AccountInfo::new(&BUYER, accounts[0] == BUYER, true, buyer_lamports, &mut buyer_data,
+			&system_program, false, 0);
+		let operation_account_info = AccountInfo::new(&OPERATION, false, true, operation_lamports, &mut operation_data,
+			&program_id, false, 0);
+
+		let accounts: Vec<AccountInfo> = accounts.iter()
+			.map(|key| match *key {
+				SELLER => seller_account_info.clone(),
+				BUYER => buyer_account_info.clone(),
+				_ => operation_account_info.clone(),
+			})
+			.collect();
+
+		assert_eq!(handler(&program_id, &accounts), Ok(()));
+		drop(accounts);
+		drop((seller_account_info, buyer_account_info, operation_account_info));
+
+		lamports
+	}
+
+	fn refunded(line_items_data: &[u8]) -> Vec<u32> {
+		let line_items: &[LineItem] = cast_slice(&line_items_data[LINE_ITEMS_HEADER_LEN..]);
+		line_items.iter().map(LineItem::refunded).collect()
+	}
+
+	#[test]
+	fn line_items_set_the_value() {
+		let (result, operation_account_data) = define(&LINE_ITEMS);
+
+		// The line items are accepted, creating the account needs the Rent sysvar of a running cluster
+		assert_eq!(result, Err(ProgramError::Custom(SCAError::RentError as u32)));
+		assert_eq!(operation_account_data.value(), VALUE);
+
+		assert_eq!(define(&[(100, 4), (0, 3)]).0, Err(ProgramError::InvalidArgument));
+		assert_eq!(define(&[(100, 0)]).0, Err(ProgramError::InvalidArgument));
+		assert_eq!(define(&[(u64::MAX, 2)]).0, Err(ProgramError::InvalidArgument));
+		assert_eq!(define(&[(u64::MAX, 1), (1, 1)]).0, Err(ProgramError::InvalidArgument));
+	}
+
+	#[test]
+	fn refunds_come_out_of_the_value() {
+		let (result, operation_account_data, line_items_data, lamports) =
+			refund(&operation(OperationStatus::BuyerDeposited), &line_items(), 0, 2);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(lamports, [BALANCE + 800, BALANCE + 200]);
+		assert_eq!(operation_account_data.value(), 800);
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::BuyerDeposited));
+		assert_eq!(refunded(&line_items_data), [2, 0]);
+
+		let (result, _operation_account_data, _line_items_data, lamports) =
+			refund(&operation_account_data, &line_items_data, 0, 3);
+
+		assert_eq!(result, Err(ProgramError::InvalidArgument));
+		assert_eq!(lamports, [BALANCE + 800, BALANCE]);
+
+		let (result, operation_account_data, line_items_data, _lamports) =
+			refund(&operation_account_data, &line_items_data, 0, 2);
+		assert_eq!(result, Ok(()));
+
+		let (result, operation_account_data, line_items_data, lamports) =
+			refund(&operation_account_data, &line_items_data, 1, 3);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(lamports, [BALANCE, BALANCE + 600]);
+		assert_eq!(operation_account_data.value(), 0);
+		assert_eq!(operation_account_data.status(), Ok(OperationStatus::ReleaseRefund));
+		assert_eq!(refunded(&line_items_data), [4, 3]);
+	}
+
+	#[test]
+	fn refunds_clear_the_pending_settlement() {
+		let (result, operation_account_data, _line_items_data, _lamports) =
+			refund(&operation(OperationStatus::Delivered), &line_items(), 1, 1);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(operation_account_data.settlement_proposer, Pubkey::default());
+		assert_eq!(operation_account_data.settlement_buyer_amount(), 0);
+	}
+
+	#[test]
+	fn refunds_need_a_deposit_and_no_milestones() {
+		let (result, _operation_account_data, _line_items_data, _lamports) =
+			refund(&operation(OperationStatus::BuyerRegistered), &line_items(), 0, 1);
+		assert_eq!(result, Err(ProgramError::UninitializedAccount));
+
+		let mut operation_account_data = operation(OperationStatus::BuyerDeposited);
+		operation_account_data.set_milestone_count(2);
+
+		let (result, _operation_account_data, _line_items_data, _lamports) =
+			refund(&operation_account_data, &line_items(), 0, 1);
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+
+		let (result, _operation_account_data, _line_items_data, _lamports) =
+			refund(&operation(OperationStatus::BuyerDeposited), &line_items(), 0, 0);
+		assert_eq!(result, Err(ProgramError::InvalidArgument));
+	}
+
+	#[test]
+	fn later_payouts_only_cover_the_rest() {
+		let (_result, mut operation_account_data, _line_items_data, _lamports) =
+			refund(&operation(OperationStatus::BuyerDeposited), &line_items(), 1, 2);
+
+		assert_eq!(pay_out(&operation_account_data, buyer_release, &[BUYER, SELLER, OPERATION]),
+			[BALANCE, BALANCE + 600, BALANCE]);
+
+		operation_account_data.set_status(OperationStatus::BuyerClaim);
+		assert_eq!(pay_out(&operation_account_data, participant_claim, &[BUYER, OPERATION]),
+			[BALANCE, BALANCE, BALANCE + 600]);
+	}
+
+	#[test]
+	fn finalized_releases_only_cover_the_rest() {
+		let (_result, mut operation_account_data, _line_items_data, _lamports) =
+			refund(&operation(OperationStatus::Delivered), &line_items(), 0, 3);
+
+		// Inspection period already over
+		operation_account_data.set_inspection_deadline(-1);
+
+		assert_eq!(pay_out(&operation_account_data, finalize_release, &[SELLER, OPERATION]),
+			[BALANCE, BALANCE + 700, BALANCE]);
+	}
+}
@@ -0,0 +1,511 @@
+//! # pool
+//! Functionality related to the pool of arbiters available for random selection
+//!
+//! List of supported instructions
+//!
+//! 1. Join Arbiter Pool -> [join_arbiter_pool]
+//! 2. Leave Arbiter Pool -> [leave_arbiter_pool]
+//!
+//! Operations using [crate::scatype::ArbiterSelection::Random] get their arbiters from the pool,
+//! see [select_arbiters]. Arbiters leave the pool when they stop being eligible, or are slashed for a missed vote.
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg, system_instruction::transfer,
+    program_error::ProgramError,
+    pubkey::Pubkey, system_program::check_id,
+	program::invoke,
+	hash::hashv,
+	sysvar::{
+		self,
+		Sysvar,
+		rent::Rent,
+	}
+};
+
+use bytemuck::{cast_slice, cast_slice_mut};
+
+use crate::scatype::{
+	OperationAccount, ArbiterPoolHeader, ArbiterProfile, ArbiterStake, SCAError,
+	ARBITER_POOL_VERSION, ARBITER_POOL_SEED, ARBITER_POOL_HEADER_LEN, ARBITER_POOL_ENTRY_LEN,
+};
+
+use crate::arbiter::{
+	check_arbiter_profile,
+	check_arbiter_stake,
+};
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+	create_pda_account,
+};
+
+/// Returns the address and bump seed of the arbiter pool.
+pub fn arbiter_pool_address(program_id: &Pubkey) -> (Pubkey, u8) {
+	Pubkey::find_program_address(&[ARBITER_POOL_SEED], program_id)
+}
+
+/// Checks that the account is the existing arbiter pool.
+pub fn check_arbiter_pool(program_id: &Pubkey, arbiter_pool_account_info: &AccountInfo) -> ProgramResult {
+
+	is_owned_and_writable(program_id, arbiter_pool_account_info, "ARBITERPOOL account is not a valid account.")?;
+
+	let mut arbiter_pool_bytes = arbiter_pool_account_info.try_borrow_mut_data()?;
+	let arbiter_pool_len = arbiter_pool_bytes.len();
+	let arbiter_pool_header = ArbiterPoolHeader::load_mut(&mut arbiter_pool_bytes)?;
+
+	let arbiter_pool_pubkey = Pubkey::create_program_address(
+		&[ARBITER_POOL_SEED, &[arbiter_pool_header.bump]], program_id)?;
+
+	if arbiter_pool_pubkey != *arbiter_pool_account_info.key {
+		msg!("ARBITERPOOL account is not a valid account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	if arbiter_pool_len < ARBITER_POOL_HEADER_LEN + arbiter_pool_header.count() as usize * ARBITER_POOL_ENTRY_LEN {
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	Ok(())
+}
+
+/// Removes an arbiter from the pool, returning whether they were in it.
+/// The pool may not exist yet, in which case the account must still be its address.
+/// The freed space is kept for the next arbiter to join.
+pub fn remove_from_pool(program_id: &Pubkey, arbiter_pool_account_info: &AccountInfo, arbiter: &Pubkey) -> Result<bool, ProgramError> {
+
+	if *arbiter_pool_account_info.owner != *program_id {
+		let (arbiter_pool_pubkey, _bump) = arbiter_pool_address(program_id);
+
+		if *arbiter_pool_account_info.key != arbiter_pool_pubkey {
+			msg!("ARBITERPOOL account is not a valid account.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		return Ok(false)
+	}
+
+	check_arbiter_pool(program_id, arbiter_pool_account_info)?;
+
+	let mut arbiter_pool_bytes = arbiter_pool_account_info.try_borrow_mut_data()?;
+	let count = ArbiterPoolHeader::load_mut(&mut arbiter_pool_bytes)?.count() as usize;
+
+	let arbiters: &mut [Pubkey] = cast_slice_mut(
+		&mut arbiter_pool_bytes[ARBITER_POOL_HEADER_LEN..ARBITER_POOL_HEADER_LEN + count * ARBITER_POOL_ENTRY_LEN]);
+
+	let index = match arbiters.iter().position(|member| member == arbiter) {
+		Some(index) => index,
+		None => return Ok(false),
+	};
+
+	// Order does not matter, so the last arbiter takes the freed place
+	arbiters.swap(index, count - 1);
+	arbiters[count - 1] = Default::default();
+
+	ArbiterPoolHeader::load_mut(&mut arbiter_pool_bytes)?.set_count(count as u32 - 1);
+
+	msg!("Arbiter left the pool.");
+
+	Ok(true)
+}
+
+/// Picks `picks` different arbiters from the pool, skipping the excluded ones.
+/// The entropy comes from the most recent hash in the SlotHashes sysvar, mixed with the operation.
+pub fn select_arbiters(program_id: &Pubkey, arbiter_pool_account_info: &AccountInfo,
+	slot_hashes_account_info: &AccountInfo, operation: &Pubkey,
+	excluded: &[Pubkey], picks: usize) -> Result<Vec<Pubkey>, ProgramError> {
+
+	check_arbiter_pool(program_id, arbiter_pool_account_info)?;
+
+	if !sysvar::slot_hashes::check_id(slot_hashes_account_info.key) {
+		msg!("Invalid SlotHashes sysvar");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// Entries are (slot, hash) pairs after a u64 length, the most recent first
+	let slot_hashes_bytes = slot_hashes_account_info.try_borrow_data()?;
+
+	if slot_hashes_bytes.len() < 48 {
+		msg!("No recent slot hash available.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	let recent_slot_hash = &slot_hashes_bytes[16..48];
+
+	let mut arbiter_pool_bytes = arbiter_pool_account_info.try_borrow_mut_data()?;
+	let count = ArbiterPoolHeader::load_mut(&mut arbiter_pool_bytes)?.count() as usize;
+
+	let arbiters: &[Pubkey] = cast_slice(
+		&arbiter_pool_bytes[ARBITER_POOL_HEADER_LEN..ARBITER_POOL_HEADER_LEN + count * ARBITER_POOL_ENTRY_LEN]);
+
+	let mut selected: Vec<Pubkey> = Vec::with_capacity(picks);
+
+	for pick in 0..picks {
+
+		if count == 0 {
+			break;
+		}
+
+		let seed = hashv(&[recent_slot_hash, operation.as_ref(), &[pick as u8]]).to_bytes();
+
+		let mut start_bytes = [0u8; 8];
+		start_bytes.copy_from_slice(&seed[..8]);
+		let start = (u64::from_le_bytes(start_bytes) % count as u64) as usize;
+
+		// Walk the pool from the random start until an arbiter can be taken
+		let arbiter = (0..count)
+			.map(|offset| arbiters[(start + offset) % count])
+			.find(|arbiter| !excluded.contains(arbiter) && !selected.contains(arbiter));
+
+		match arbiter {
+			Some(arbiter) => selected.push(arbiter),
+			None => break,
+		}
+	}
+
+	if selected.len() < picks {
+		msg!("Not enough arbiters in the pool.");
+		return Err(ProgramError::Custom(SCAError::NotEnoughArbiters as u32))
+	}
+
+	Ok(selected)
+}
+
+/// Returns everyone linked to the operation, who cannot be picked as arbiter: the parties, the intended buyer,
+/// the delivery attestor, the arbiters of both panels and every invited arbiter. Arbiters replaced for not voting already left the pool,
+/// see [crate::arbiter::slash_missed_vote]. Unused entries are zeroed, so they never match an arbiter of the pool.
+pub fn linked_parties(operation_account_data: &OperationAccount) -> Vec<Pubkey> {
+
+	let mut linked = vec![
+		operation_account_data.seller, operation_account_data.buyer, operation_account_data.intended_buyer,
+		operation_account_data.attestor, operation_account_data.arbiter1, operation_account_data.arbiter2, operation_account_data.arbiter3,
+	];
+	linked.extend_from_slice(&operation_account_data.arbiter_invites);
+	linked.extend_from_slice(&operation_account_data.appeal_arbiters);
+
+	linked
+}
+
+/// Adds an arbiter to the pool, creating it if needed.
+/// The arbiter must have an active profile and an eligible stake.
+///
+/// Accounts:
+/// 1. ARBITER - Account of the arbiter, who also pays for this transaction and the pool growth.
+/// 2. ARBITERPROFILE - Profile of the arbiter. Comformant to [ArbiterProfile]
+/// 3. ARBITERSTAKE - Stake of the arbiter. Comformant to [ArbiterStake]
+/// 4. ARBITERPOOL - PDA of [ARBITER_POOL_SEED]. Comformant to [ArbiterPoolHeader]
+/// 5. SYSTEM PROGRAM - Used to create and grow the pool.
+#[inline(never)]
+pub fn join_arbiter_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get ARBITER account
+	let arbiter_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITER account validity
+	is_signed_by_writable_account(arbiter_account_info, "ARBITER account is not a valid account.")?;
+
+	// Get the ARBITERPROFILE account
+	let arbiter_profile_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITERPROFILE account validity
+	check_arbiter_profile(program_id, arbiter_account_info.key, arbiter_profile_account_info)?;
+
+	// Get the ARBITERSTAKE account
+	let arbiter_stake_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITERSTAKE account validity
+	check_arbiter_stake(program_id, arbiter_account_info.key, arbiter_stake_account_info)?;
+
+	// Get the ARBITERPOOL account, validated below
+	let arbiter_pool_account_info = next_account_info(accounts_iter)?;
+
+	let system_program_account_info = next_account_info(accounts_iter)?;
+
+	// ================ Enforce configuration rules section
+
+	if !check_id(system_program_account_info.key) {
+		msg!("Invalid System program");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ================ Enforce previous state section
+
+	if !ArbiterProfile::load_mut(&mut arbiter_profile_account_info.try_borrow_mut_data()?)?.active() {
+		msg!("Arbiter is not active.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if !ArbiterStake::load_mut(&mut arbiter_stake_account_info.try_borrow_mut_data()?)?.is_eligible() {
+		msg!("Arbiter stake is not enough, or being withdrawn.");
+		return Err(ProgramError::Custom(SCAError::InsufficientStake as u32))
+	}
+
+	if *arbiter_pool_account_info.owner == *program_id {
+		check_arbiter_pool(program_id, arbiter_pool_account_info)?;
+	} else {
+		let (arbiter_pool_pubkey, bump) = arbiter_pool_address(program_id);
+
+		if *arbiter_pool_account_info.key != arbiter_pool_pubkey || !arbiter_pool_account_info.is_writable {
+			msg!("ARBITERPOOL account is not a valid account.");
+			return Err(ProgramError::InvalidArgument)
+		}
+
+		create_pda_account(program_id, arbiter_account_info, arbiter_pool_account_info, system_program_account_info,
+			ARBITER_POOL_HEADER_LEN, &[ARBITER_POOL_SEED, &[bump]])?;
+
+		let mut arbiter_pool_bytes = arbiter_pool_account_info.try_borrow_mut_data()?;
+		arbiter_pool_bytes[0] = ARBITER_POOL_VERSION;
+		ArbiterPoolHeader::load_mut(&mut arbiter_pool_bytes)?.bump = bump;
+
+		msg!("Arbiter pool created.");
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	let (count, arbiter_pool_len) = {
+		let mut arbiter_pool_bytes = arbiter_pool_account_info.try_borrow_mut_data()?;
+		let arbiter_pool_len = arbiter_pool_bytes.len();
+		let count = ArbiterPoolHeader::load_mut(&mut arbiter_pool_bytes)?.count() as usize;
+
+		let arbiters: &[Pubkey] = cast_slice(
+			&arbiter_pool_bytes[ARBITER_POOL_HEADER_LEN..ARBITER_POOL_HEADER_LEN + count * ARBITER_POOL_ENTRY_LEN]);
+
+		if arbiters.contains(arbiter_account_info.key) {
+			msg!("Arbiter already in the pool.");
+			return Err(ProgramError::AccountAlreadyInitialized)
+		}
+
+		(count, arbiter_pool_len)
+	};
+
+	let entry_offset = ARBITER_POOL_HEADER_LEN + count * ARBITER_POOL_ENTRY_LEN;
+
+	// ========================= Change state section
+
+	// Space left by arbiters who left is reused before growing the pool
+	if arbiter_pool_len < entry_offset + ARBITER_POOL_ENTRY_LEN {
+
+		let rent_exemption_balance = match Rent::get() {
+			Err(_e) => return Err(ProgramError::Custom(SCAError::RentError as u32)),
+			Ok(rent) => rent.minimum_balance(entry_offset + ARBITER_POOL_ENTRY_LEN),
+		};
+
+		let rent_shortfall = rent_exemption_balance.saturating_sub(arbiter_pool_account_info.lamports());
+
+		if rent_shortfall > 0 {
+			invoke(
+				&transfer(arbiter_account_info.key, arbiter_pool_account_info.key, rent_shortfall),
+				&[arbiter_account_info.clone(), arbiter_pool_account_info.clone(), system_program_account_info.clone()],
+			)?;
+		}
+
+		// The whole new entry is written below, no need to zero it first
+		arbiter_pool_account_info.realloc(entry_offset + ARBITER_POOL_ENTRY_LEN, false)?;
+	}
+
+	// Save
+	let mut arbiter_pool_bytes = arbiter_pool_account_info.try_borrow_mut_data()?;
+	arbiter_pool_bytes[entry_offset..entry_offset + ARBITER_POOL_ENTRY_LEN].copy_from_slice(arbiter_account_info.key.as_ref());
+	ArbiterPoolHeader::load_mut(&mut arbiter_pool_bytes)?.set_count(count as u32 + 1);
+
+	msg!("Arbiter joined the pool.");
+
+	Ok(())
+}
+
+/// Removes an arbiter from the pool, so that they are no longer picked for new disputes.
+/// Arbiters also leave the pool when they deactivate their profile or request the unstake.
+///
+/// Accounts:
+/// 1. ARBITER - Account of the arbiter, who also pays for this transaction.
+/// 2. ARBITERPOOL - The pool. Comformant to [ArbiterPoolHeader]
+#[inline(never)]
+pub fn leave_arbiter_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get ARBITER account
+	let arbiter_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITER account validity
+	is_signed_by_writable_account(arbiter_account_info, "ARBITER account is not a valid account.")?;
+
+	// Get the ARBITERPOOL account
+	let arbiter_pool_account_info = next_account_info(accounts_iter)?;
+
+	// Check ARBITERPOOL account validity
+	check_arbiter_pool(program_id, arbiter_pool_account_info)?;
+
+	// ========================= Change state section
+
+	if !remove_from_pool(program_id, arbiter_pool_account_info, arbiter_account_info.key)? {
+		msg!("Arbiter is not in the pool.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bytemuck::{bytes_of, Zeroable};
+
+	const OPERATION: Pubkey = Pubkey::new_from_array([1; 32]);
+	const RECENT_SLOT_HASH: [u8; 32] = [0x42; 32];
+
+	fn arbiter(index: u8) -> Pubkey {
+		Pubkey::new_from_array([index + 100; 32])
+	}
+
+	fn pool(size: u8) -> Vec<Pubkey> {
+		(0..size).map(arbiter).collect()
+	}
+
+	fn pool_data(arbiters: &[Pubkey]) -> Vec<u8> {
+		let (_pubkey, bump) = arbiter_pool_address(&crate::id());
+
+		let mut header = ArbiterPoolHeader::zeroed();
+		header.version = ARBITER_POOL_VERSION;
+		header.bump = bump;
+		header.set_count(arbiters.len() as u32);
+
+		// Space left by an arbiter who left stays at the end
+		[bytes_of(&header), cast_slice(arbiters), &[0; ARBITER_POOL_ENTRY_LEN]].concat()
+	}
+
+	/// SlotHashes sysvar data holding a single (slot, hash) entry.
+	fn slot_hashes_data() -> Vec<u8> {
+		[&1u64.to_le_bytes()[..], &7u64.to_le_bytes(), &RECENT_SLOT_HASH].concat()
+	}
+
+	fn select_from(mut pool_data: Vec<u8>, slot_hashes_key: Pubkey, mut slot_hashes_data: Vec<u8>,
+		excluded: &[Pubkey], picks: usize) -> Result<Vec<Pubkey>, ProgramError> {
+
+		let program_id = crate::id();
+		let (pool_key, _bump) = arbiter_pool_address(&program_id);
+		let sysvar_owner = sysvar::id();
+		let (mut pool_lamports, mut slot_hashes_lamports) = (0, 0);
+
+		let arbiter_pool_account_info = AccountInfo::new(&pool_key, false, true, &mut pool_lamports, &mut pool_data,
+			&program_id, false, 0);
+		let slot_hashes_account_info = AccountInfo::new(&slot_hashes_key, false, false, &mut slot_hashes_lamports,
+			&mut slot_hashes_data, &sysvar_owner, false, 0);
+
+		select_arbiters(&program_id, &arbiter_pool_account_info, &slot_hashes_account_info, &OPERATION, excluded, picks)
+	}
+
+	fn select(arbiters: &[Pubkey], excluded: &[Pubkey], picks: usize) -> Result<Vec<Pubkey>, ProgramError> {
+		select_from(pool_data(arbiters), sysvar::slot_hashes::id(), slot_hashes_data(), excluded, picks)
+	}
+
+	/// Index the walk of the given pick starts from, as derived in [select_arbiters].
+	fn start(pick: u8, count: usize) -> usize {
+		let seed = hashv(&[&RECENT_SLOT_HASH, OPERATION.as_ref(), &[pick]]).to_bytes();
+		(u64::from_le_bytes(seed[..8].try_into().unwrap()) % count as u64) as usize
+	}
+
+	#[test]
+	fn picks_different_arbiters_from_the_pool() {
+		let arbiters = pool(10);
+		let selected = select(&arbiters, &[], 3).unwrap();
+
+		assert_eq!(selected.len(), 3);
+		assert!(selected.iter().all(|picked| arbiters.contains(picked)));
+		assert!(selected[0] != selected[1] && selected[0] != selected[2] && selected[1] != selected[2]);
+
+		// The same slot hash and operation always pick the same arbiters
+		assert_eq!(select(&arbiters, &[], 3), Ok(selected));
+	}
+
+	#[test]
+	fn walks_the_pool_from_a_random_start() {
+		let arbiters = pool(10);
+		let first = start(0, arbiters.len());
+
+		assert_eq!(select(&arbiters, &[], 1), Ok(vec![arbiters[first]]));
+
+		// Taken arbiters are skipped by moving on to the next one, wrapping around the end
+		let excluded = [arbiters[first]];
+		assert_eq!(select(&arbiters, &excluded, 1), Ok(vec![arbiters[(first + 1) % arbiters.len()]]));
+	}
+
+	#[test]
+	fn never_picks_excluded_arbiters() {
+		let arbiters = pool(6);
+		let excluded = [arbiters[0], arbiters[2], arbiters[4]];
+
+		let mut selected = select(&arbiters, &excluded, 3).unwrap();
+		selected.sort();
+
+		assert_eq!(selected, vec![arbiters[1], arbiters[3], arbiters[5]]);
+	}
+
+	#[test]
+	fn fails_without_enough_eligible_arbiters() {
+		let arbiters = pool(4);
+		let not_enough = Err(ProgramError::Custom(SCAError::NotEnoughArbiters as u32));
+
+		assert_eq!(select(&arbiters, &arbiters[..2], 3), not_enough);
+		assert_eq!(select(&arbiters, &[], 5), not_enough);
+		assert_eq!(select(&[], &[], 1), not_enough);
+	}
+
+	#[test]
+	fn rejects_invalid_slot_hashes() {
+		let arbiters = pool(4);
+
+		assert_eq!(select_from(pool_data(&arbiters), sysvar::clock::id(), slot_hashes_data(), &[], 3),
+			Err(ProgramError::InvalidArgument));
+		assert_eq!(select_from(pool_data(&arbiters), sysvar::slot_hashes::id(), slot_hashes_data()[..47].to_vec(), &[], 3),
+			Err(ProgramError::InvalidAccountData));
+	}
+
+	#[test]
+	fn rejects_a_pool_shorter_than_its_count() {
+		let arbiters = pool(4);
+		let data = pool_data(&arbiters);
+
+		assert_eq!(select_from(data[..ARBITER_POOL_HEADER_LEN + 3 * ARBITER_POOL_ENTRY_LEN].to_vec(),
+			sysvar::slot_hashes::id(), slot_hashes_data(), &[], 3), Err(ProgramError::InvalidAccountData));
+	}
+
+	#[test]
+	fn links_everyone_taking_part_in_the_operation() {
+		let mut operation_account_data = OperationAccount::zeroed();
+		operation_account_data.seller = Pubkey::new_from_array([1; 32]);
+		operation_account_data.buyer = Pubkey::new_from_array([2; 32]);
+		operation_account_data.intended_buyer = Pubkey::new_from_array([3; 32]);
+		operation_account_data.attestor = Pubkey::new_from_array([4; 32]);
+		operation_account_data.arbiter1 = arbiter(0);
+		operation_account_data.arbiter2 = arbiter(1);
+		operation_account_data.arbiter3 = arbiter(2);
+		operation_account_data.arbiter_invites[3] = arbiter(3);
+		operation_account_data.appeal_arbiters[4] = arbiter(4);
+
+		let linked = linked_parties(&operation_account_data);
+
+		for party in [1, 2, 3, 4].map(|byte| Pubkey::new_from_array([byte; 32])).iter().chain(pool(5).iter()) {
+			assert!(linked.contains(party));
+		}
+		assert!(!linked.contains(&arbiter(5)));
+
+		// Only the linked ones are left out of the selection
+		let arbiters = pool(8);
+		let mut selected = select(&arbiters, &linked, 3).unwrap();
+		selected.sort();
+		assert_eq!(selected, arbiters[5..].to_vec());
+	}
+}
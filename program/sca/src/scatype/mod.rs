@@ -1,29 +1,201 @@
-//!	# types - Solana Universal Trading Types
-//!	This crate contains the Solana Universal Trading specific type definitions, as required by the Solana program
+//! # types - Solana Universal Trading Types
+//! This crate contains the Solana Universal Trading specific type definitions, as required by the Solana program
 //!
 //! The available types are:
 //! TokenVersion -> [TokenVersion]
 //! OperationStatus -> [OperationStatus]
 //! OperationAccount -> [OperationAccount]
+//! EvidenceRole -> [EvidenceRole]
+//! EvidenceLogHeader -> [EvidenceLogHeader]
+//! EvidenceEntry -> [EvidenceEntry]
+//! ArbiterProfile -> [ArbiterProfile]
+//! ArbiterStake -> [ArbiterStake]
+//! ArbiterSelection -> [ArbiterSelection]
+//! ArbiterPoolHeader -> [ArbiterPoolHeader]
+//! MilestonesHeader -> [MilestonesHeader]
+//! Milestone -> [Milestone]
+//! LineItemsHeader -> [LineItemsHeader]
+//! LineItem -> [LineItem]
+//! OfferLogHeader -> [OfferLogHeader]
+//! Offer -> [Offer]
+//! PodU32 -> [PodU32]
+//! PodU64 -> [PodU64]
+//! PodI64 -> [PodI64]
 
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
 use solana_program::{
     pubkey::Pubkey,
 	clock::UnixTimestamp,
+	native_token::LAMPORTS_PER_SOL,
+	msg, program_error::ProgramError,
 };
 
+use crate::content::ContentRefData;
+
+use std::convert::TryFrom;
+use std::mem::size_of;
+
 /// Special Zero account that owns all keypairs
 pub const ZERO_ACCOUNT:[u8;32] = [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]; 
 
+/// Current layout version of the [OperationAccount].
+/// Version 0 is reserved for freshly created accounts that were never initialized.
+pub const OPERATION_ACCOUNT_VERSION: u8 = 1;
+
+/// Size in bytes of the current [OperationAccount] layout.
+pub const OPERATION_ACCOUNT_LEN: usize = size_of::<OperationAccount>();
+
+/// Size in bytes of the original, unversioned layout. See [crate::migration::migrate_operation].
+pub const LEGACY_OPERATION_ACCOUNT_LEN: usize = 321;
+
+/// Maximum number of pending and accepted arbiter invitations of an operation.
+pub const MAX_ARBITER_INVITES: usize = 6;
+
+/// Time in seconds, from the start of a dispute, during which the parties submit evidence.
+/// Arbiters can only vote once it is over.
+#[cfg(not(feature = "short-periods"))]
+pub const EVIDENCE_PERIOD: UnixTimestamp = 3 * 24 * 60 * 60;
+
+/// Time in seconds, from the start of a dispute, during which the parties submit evidence.
+/// Arbiters can only vote once it is over.
+#[cfg(feature = "short-periods")]
+pub const EVIDENCE_PERIOD: UnixTimestamp = 30;
+
+/// Time in seconds that arbiters have to vote, once the evidence period is over.
+/// After it, the parties can replace the arbiters who did not vote.
+#[cfg(not(feature = "short-periods"))]
+pub const VOTING_PERIOD: UnixTimestamp = 7 * 24 * 60 * 60;
+
+/// Time in seconds that arbiters have to vote, once the evidence period is over.
+/// After it, the parties can replace the arbiters who did not vote.
+#[cfg(feature = "short-periods")]
+pub const VOTING_PERIOD: UnixTimestamp = 60;
+
+/// Time in seconds, from the verdict of the first panel, during which the losing party can appeal.
+/// The winner can only claim once it is over.
+#[cfg(not(feature = "short-periods"))]
+pub const APPEAL_PERIOD: UnixTimestamp = 3 * 24 * 60 * 60;
+
+/// Time in seconds, from the verdict of the first panel, during which the losing party can appeal.
+/// The winner can only claim once it is over.
+#[cfg(feature = "short-periods")]
+pub const APPEAL_PERIOD: UnixTimestamp = 30;
+
+/// Number of arbiters of the appeal panel, picked from the arbiter pool.
+pub const APPEAL_PANEL_SIZE: usize = 5;
+
+/// Seat of the first appeal arbiter, after the three arbiter slots. See [OperationAccount::arbiter_seat].
+pub const APPEAL_SEAT: usize = 3;
+
+/// Percentage of the operation value the appellant posts as a bond.
+/// The bond goes to whoever wins the appeal, so it is returned to a successful appellant.
+pub const APPEAL_BOND_PERCENT: u64 = 10;
+
+/// Current layout version of the evidence log, see [EvidenceLogHeader].
+pub const EVIDENCE_LOG_VERSION: u8 = 1;
+
+/// Seed of the evidence log PDA, together with the operation account address.
+pub const EVIDENCE_LOG_SEED: &[u8] = b"evidence";
+
+/// Size in bytes of the [EvidenceLogHeader].
+pub const EVIDENCE_LOG_HEADER_LEN: usize = size_of::<EvidenceLogHeader>();
+
+/// Size in bytes of each [EvidenceEntry].
+pub const EVIDENCE_ENTRY_LEN: usize = size_of::<EvidenceEntry>();
+
+/// Current layout version of the [ArbiterProfile].
+pub const ARBITER_PROFILE_VERSION: u8 = 1;
+
+/// Seed of the arbiter profile PDA, together with the arbiter address.
+pub const ARBITER_PROFILE_SEED: &[u8] = b"arbiter";
+
+/// Size in bytes of the [ArbiterProfile].
+pub const ARBITER_PROFILE_LEN: usize = size_of::<ArbiterProfile>();
+
+/// Current layout version of the [ArbiterStake].
+pub const ARBITER_STAKE_VERSION: u8 = 1;
+
+/// Seed of the arbiter stake PDA, together with the arbiter address.
+pub const ARBITER_STAKE_SEED: &[u8] = b"stake";
+
+/// Size in bytes of the [ArbiterStake].
+pub const ARBITER_STAKE_LEN: usize = size_of::<ArbiterStake>();
+
+/// Minimum stake, in lamports, for an arbiter to register to operations.
+pub const MIN_ARBITER_STAKE: u64 = LAMPORTS_PER_SOL;
+
+/// Percentage of the stake slashed from an arbiter replaced for not voting in time.
+pub const STAKE_SLASH_PERCENT: u64 = 20;
+
+/// Time in seconds an arbiter waits between requesting the unstake and withdrawing.
+/// It covers a whole dispute and one more voting period, so that an arbiter picked from the pool before the request,
+/// who never confirms the seat, is still replaced and slashed before withdrawing.
+pub const UNSTAKE_COOLDOWN: UnixTimestamp = EVIDENCE_PERIOD + 2 * VOTING_PERIOD;
+
+/// Current layout version of the arbiter pool, see [ArbiterPoolHeader].
+pub const ARBITER_POOL_VERSION: u8 = 1;
+
+/// Seed of the arbiter pool PDA, a single one for the program.
+pub const ARBITER_POOL_SEED: &[u8] = b"pool";
+
+/// Size in bytes of the [ArbiterPoolHeader].
+pub const ARBITER_POOL_HEADER_LEN: usize = size_of::<ArbiterPoolHeader>();
+
+/// Size in bytes of each arbiter in the pool.
+pub const ARBITER_POOL_ENTRY_LEN: usize = size_of::<Pubkey>();
+
+/// Current layout version of the milestones of an operation, see [MilestonesHeader].
+pub const MILESTONES_VERSION: u8 = 1;
+
+/// Seed of the milestones PDA, together with the operation account address.
+pub const MILESTONES_SEED: &[u8] = b"milestones";
+
+/// Size in bytes of the [MilestonesHeader].
+pub const MILESTONES_HEADER_LEN: usize = size_of::<MilestonesHeader>();
+
+/// Size in bytes of each [Milestone].
+pub const MILESTONE_LEN: usize = size_of::<Milestone>();
+
+/// Maximum number of milestones of an operation.
+pub const MAX_MILESTONES: usize = 10;
+
+/// Current layout version of the line items of an operation, see [LineItemsHeader].
+pub const LINE_ITEMS_VERSION: u8 = 1;
+
+/// Seed of the line items PDA, together with the operation account address.
+pub const LINE_ITEMS_SEED: &[u8] = b"items";
+
+/// Size in bytes of the [LineItemsHeader].
+pub const LINE_ITEMS_HEADER_LEN: usize = size_of::<LineItemsHeader>();
+
+/// Size in bytes of each [LineItem].
+pub const LINE_ITEM_LEN: usize = size_of::<LineItem>();
+
+/// Maximum number of line items of an operation.
+pub const MAX_LINE_ITEMS: usize = 10;
+
+/// Current layout version of the offer log, see [OfferLogHeader].
+pub const OFFER_LOG_VERSION: u8 = 1;
+
+/// Seed of the offer log PDA, together with the operation account address.
+pub const OFFER_LOG_SEED: &[u8] = b"offers";
+
+/// Size in bytes of the [OfferLogHeader].
+pub const OFFER_LOG_HEADER_LEN: usize = size_of::<OfferLogHeader>();
+
+/// Size in bytes of each [Offer].
+pub const OFFER_LEN: usize = size_of::<Offer>();
+
 /// The type of funding in use by the Operation. In principle, only SOL supported.
-#[derive(PartialEq, BorshSerialize, BorshDeserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, BorshSerialize, BorshDeserialize, Debug)]
 pub enum TokenVersion {
 	/// The native Solana token.
 	Sol,
 }
 
 /// The options for dispute voting in use by the Operation.
-#[derive(PartialEq, BorshSerialize, BorshDeserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, BorshSerialize, BorshDeserialize, Debug)]
 pub enum VotingOptions {
 	NoVote,
 	Buyer,
@@ -31,7 +203,7 @@ pub enum VotingOptions {
 }
 
 /// The status of the Operation account as the operation progresses.
-#[derive(PartialEq, BorshSerialize, BorshDeserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, BorshSerialize, BorshDeserialize, Debug)]
 pub enum OperationStatus {
 	/// Account just created, ready for initialization.
 	Closed,
@@ -57,6 +229,177 @@ pub enum OperationStatus {
 	BuyerClaim,
 	/// Dispute concluded
 	DisputeResolved, 
+	/// The losing party appealed, the appeal panel is voting
+	InAppeal,
+	/// The parties agreed on a split of the token value
+	Settled,
+	/// Seller shipped the item, with its tracking reference
+	Shipped,
+	/// Buyer received the item
+	Delivered,
+}
+
+impl OperationStatus {
+
+	/// True if the token value is escrowed and no dispute was opened, before or after the item shipped.
+	pub fn is_deposited(&self) -> bool {
+		matches!(self,
+			OperationStatus::BuyerDeposited |
+			OperationStatus::Shipped |
+			OperationStatus::Delivered)
+	}
+
+	/// True once the value was paid out, so no arbiter is needed anymore.
+	pub fn is_finished(&self) -> bool {
+		matches!(self,
+			OperationStatus::ReleaseRefund |
+			OperationStatus::DisputeResolved |
+			OperationStatus::Settled)
+	}
+}
+
+/// How the arbiters of the Operation are chosen.
+#[derive(Clone, Copy, PartialEq, BorshSerialize, BorshDeserialize, Debug)]
+pub enum ArbiterSelection {
+	/// The parties invite the arbiters, who register and get approved.
+	Manual,
+	/// The program picks the arbiters from the pool when a dispute starts.
+	Random,
+}
+
+/// Who submitted an entry of the evidence log.
+#[derive(Clone, Copy, PartialEq, BorshSerialize, BorshDeserialize, Debug)]
+pub enum EvidenceRole {
+	Seller,
+	Buyer,
+	/// Rulings and comments of the arbiters.
+	Arbiter,
+}
+
+impl TryFrom<u8> for ArbiterSelection {
+	type Error = ProgramError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		match value {
+			0 => Ok(ArbiterSelection::Manual),
+			1 => Ok(ArbiterSelection::Random),
+			_ => Err(ProgramError::InvalidAccountData),
+		}
+	}
+}
+
+impl TryFrom<u8> for TokenVersion {
+	type Error = ProgramError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		match value {
+			0 => Ok(TokenVersion::Sol),
+			_ => Err(ProgramError::InvalidAccountData),
+		}
+	}
+}
+
+impl TryFrom<u8> for VotingOptions {
+	type Error = ProgramError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		match value {
+			0 => Ok(VotingOptions::NoVote),
+			1 => Ok(VotingOptions::Buyer),
+			2 => Ok(VotingOptions::Seller),
+			_ => Err(ProgramError::InvalidAccountData),
+		}
+	}
+}
+
+impl TryFrom<u8> for OperationStatus {
+	type Error = ProgramError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		match value {
+			0 => Ok(OperationStatus::Closed),
+			1 => Ok(OperationStatus::Opened),
+			2 => Ok(OperationStatus::BuyerRegistered),
+			3 => Ok(OperationStatus::ArbitersRegistered),
+			4 => Ok(OperationStatus::ArbitersApproved),
+			5 => Ok(OperationStatus::BuyerDeposited),
+			6 => Ok(OperationStatus::ReleaseRefund),
+			7 => Ok(OperationStatus::InDispute),
+			8 => Ok(OperationStatus::InVoting),
+			9 => Ok(OperationStatus::SellerClaim),
+			10 => Ok(OperationStatus::BuyerClaim),
+			11 => Ok(OperationStatus::DisputeResolved),
+			12 => Ok(OperationStatus::InAppeal),
+			13 => Ok(OperationStatus::Settled),
+			14 => Ok(OperationStatus::Shipped),
+			15 => Ok(OperationStatus::Delivered),
+			_ => Err(ProgramError::InvalidAccountData),
+		}
+	}
+}
+
+impl TryFrom<u8> for EvidenceRole {
+	type Error = ProgramError;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		match value {
+			0 => Ok(EvidenceRole::Seller),
+			1 => Ok(EvidenceRole::Buyer),
+			2 => Ok(EvidenceRole::Arbiter),
+			_ => Err(ProgramError::InvalidAccountData),
+		}
+	}
+}
+
+/// A little endian u32 with an alignment of 1, so it can live inside zero-copy accounts.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Pod, Zeroable, Debug)]
+pub struct PodU32([u8;4]);
+
+impl From<u32> for PodU32 {
+	fn from(value: u32) -> Self {
+		PodU32(value.to_le_bytes())
+	}
+}
+
+impl From<PodU32> for u32 {
+	fn from(value: PodU32) -> Self {
+		u32::from_le_bytes(value.0)
+	}
+}
+
+/// A little endian u64 with an alignment of 1, so it can live inside zero-copy accounts.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Pod, Zeroable, Debug)]
+pub struct PodU64([u8;8]);
+
+impl From<u64> for PodU64 {
+	fn from(value: u64) -> Self {
+		PodU64(value.to_le_bytes())
+	}
+}
+
+impl From<PodU64> for u64 {
+	fn from(value: PodU64) -> Self {
+		u64::from_le_bytes(value.0)
+	}
+}
+
+/// A little endian i64 with an alignment of 1, so it can live inside zero-copy accounts.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Pod, Zeroable, Debug)]
+pub struct PodI64([u8;8]);
+
+impl From<i64> for PodI64 {
+	fn from(value: i64) -> Self {
+		PodI64(value.to_le_bytes())
+	}
+}
+
+impl From<PodI64> for i64 {
+	fn from(value: PodI64) -> Self {
+		i64::from_le_bytes(value.0)
+	}
 }
 
 /// Defines the type for the Operation state stored in an account.
@@ -64,19 +407,27 @@ pub enum OperationStatus {
 /// Every Operation will have 1 OperationAccount to hold its state.
 /// The important operation rules are:
 /// 1. Accounts cannot be reused, so they should have their rent withdrawn after an operation finishes.
-#[derive(PartialEq, BorshSerialize, BorshDeserialize, Debug)]
+///
+/// The layout is fixed and every field has an alignment of 1, so handlers map it directly
+/// over the account data and read or write single fields in place, without (de)serializing.
+/// Enums and bools are stored as bytes, use the typed accessors to read and write them.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
 pub struct OperationAccount {
-	/// Status of the operation.
-    pub status: OperationStatus,
+	/// Layout version, always the first byte. See [OPERATION_ACCOUNT_VERSION].
+	pub version: u8,
+
+	/// Status of the operation. See [OperationStatus].
+    status: u8,
 
 	/// Approximate time of creation.
-	pub created_at: UnixTimestamp, // Actually i64
+	created_at: PodI64,
 
-	/// Sol/Etc
-	pub token_version: TokenVersion,
+	/// Sol/Etc. See [TokenVersion].
+	token_version: u8,
 
 	/// The token amount for the purchase.
-	pub value: u64,
+	value: PodU64,
 
 	/// Public key of the seller.
 	pub seller: Pubkey,
@@ -84,8 +435,8 @@ pub struct OperationAccount {
 	/// Public key of the buyer.
 	pub buyer: Pubkey,
 
-	/// IPFS hash
-	pub ipfs: [u8;46],
+	/// Content reference of the item description
+	pub item_content: ContentRefData,
 
 	/// Public key of the arbiter.
 	pub arbiter1: Pubkey,
@@ -97,25 +448,939 @@ pub struct OperationAccount {
 	pub arbiter3: Pubkey,
 
 	/// Seller approves arbiters
-	pub seller_approved: bool,
+	seller_approved: u8,
 
 	/// Buyer approves arbiters
-	pub buyer_approved: bool,
+	buyer_approved: u8,
 
-	/// Seller additional IPFS hash
-	pub seller_ipfs_ext: [u8;46],
+	/// Vote of the arbiter. See [VotingOptions].
+	arbiter_vote_1: u8,
 
-	/// Buyer additional IPFS hash
-	pub buyer_ipfs_ext: [u8;46],
+	/// Vote of the arbiter. See [VotingOptions].
+	arbiter_vote_2: u8,
 
-	/// Public key of the arbiter.
-	pub arbiter_vote_1: VotingOptions,
+	/// Vote of the arbiter. See [VotingOptions].
+	arbiter_vote_3: u8,
+
+	/// One bit per seat whose arbiter has the stake locked for this operation. See [ArbiterStake::active_cases].
+	arbiter_locks: u8,
+
+	/// End of the evidence period, set when the dispute starts. See [EVIDENCE_PERIOD].
+	evidence_deadline: PodI64,
+
+	/// Public key of the participant who opened the dispute.
+	pub dispute_initiator: Pubkey,
+
+	/// Time the dispute was opened.
+	dispute_started_at: PodI64,
+
+	/// Content reference of the complaint, as given when the dispute was opened.
+	pub dispute_reason: ContentRefData,
+
+	/// Arbiters who did not vote by this time can be replaced. See [VOTING_PERIOD].
+	vote_deadline: PodI64,
+
+	/// Arbiters invited by the parties, the only ones who can register. Unused entries are zeroed.
+	pub arbiter_invites: [Pubkey; MAX_ARBITER_INVITES],
+
+	/// How the arbiters are chosen. See [ArbiterSelection].
+	arbiter_selection: u8,
+
+	/// End of the appeal window, set with the verdict of the first panel. See [APPEAL_PERIOD].
+	/// Zero once appealed, or if the operation was migrated with a verdict already given.
+	appeal_deadline: PodI64,
+
+	/// Public key of the participant who appealed, zeroed if there was no appeal.
+	pub appellant: Pubkey,
+
+	/// Lamports posted by the appellant. See [APPEAL_BOND_PERCENT].
+	appeal_bond: PodU64,
+
+	/// Arbiters of the appeal panel. Unused entries are zeroed.
+	pub appeal_arbiters: [Pubkey; APPEAL_PANEL_SIZE],
+
+	/// Votes of the appeal panel, in the order of the arbiters. See [VotingOptions].
+	appeal_votes: [u8; APPEAL_PANEL_SIZE],
+
+	/// Public key of the participant who proposed the latest settlement, zeroed if there is none.
+	pub settlement_proposer: Pubkey,
+
+	/// Lamports of the token value the buyer gets under the latest settlement, the seller gets the rest.
+	settlement_buyer_amount: PodU64,
+
+	/// Number of milestones the value is paid in, zero when it is paid at once. See [MilestonesHeader].
+	milestone_count: u8,
+
+	/// Number of line items of the order, zero for a single item. See [LineItemsHeader].
+	line_item_count: u8,
+
+	/// Only buyer who can register, zeroed if anyone can. Set by the seller.
+	pub intended_buyer: Pubkey,
+
+	/// Number of times the seller updated the price or item, so buyers can assert the listing they agreed to.
+	listing_revision: PodU32,
+
+	/// Time of the latest listing update, zero if never updated. Older offers cannot be accepted.
+	listing_updated_at: PodI64,
+
+	/// Time the seller marked the item shipped, zero if not shipped.
+	shipped_at: PodI64,
+
+	/// Content reference of the shipment tracking, as given by the seller.
+	pub tracking_content: ContentRefData,
+
+	/// Time the buyer confirmed receiving the item, or the attestor signed the delivery, zero if not confirmed.
+	delivered_at: PodI64,
+
+	/// Courier or oracle trusted to attest the delivery, zeroed if there is none. Set by the seller.
+	pub attestor: Pubkey,
+
+	/// Seconds the buyer has to inspect the item once delivered, zero if the release is only manual. Set by the seller.
+	inspection_period: PodI64,
+
+	/// End of the inspection period, set on delivery. Disputes cannot start after it, and anyone can release the value.
+	inspection_deadline: PodI64,
+}
+
+impl OperationAccount {
+
+	/// Maps an [OperationAccount] over the account data, dispatching on the layout version.
+	///
+	/// Accounts still in the legacy layout are rejected and must go through
+	/// [crate::migration::migrate_operation] first.
+	pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+
+		OperationAccount::check_version(data)?;
+
+		bytemuck::try_from_bytes(&data[..OPERATION_ACCOUNT_LEN])
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+
+	/// Mutable version of [OperationAccount::load]. Writes go straight into the account data.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		OperationAccount::check_version(data)?;
+
+		bytemuck::try_from_bytes_mut(&mut data[..OPERATION_ACCOUNT_LEN])
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+
+	/// Checks the size and leading version byte of the account data.
+	fn check_version(data: &[u8]) -> Result<(), ProgramError> {
+
+		if data.len() == LEGACY_OPERATION_ACCOUNT_LEN {
+			msg!("Operation account uses the legacy layout and must be migrated.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		match data.first() {
+			Some(0) | Some(&OPERATION_ACCOUNT_VERSION) => {},
+			_ => {
+				msg!("Unsupported operation account version.");
+				return Err(ProgramError::InvalidAccountData);
+			},
+		}
+
+		if data.len() < OPERATION_ACCOUNT_LEN {
+			return Err(ProgramError::AccountDataTooSmall);
+		}
+
+		Ok(())
+	}
+
+	pub fn status(&self) -> Result<OperationStatus, ProgramError> {
+		OperationStatus::try_from(self.status)
+	}
+
+	pub fn set_status(&mut self, status: OperationStatus) {
+		self.status = status as u8;
+	}
+
+	pub fn set_created_at(&mut self, created_at: UnixTimestamp) {
+		self.created_at = created_at.into();
+	}
+
+	pub fn token_version(&self) -> Result<TokenVersion, ProgramError> {
+		TokenVersion::try_from(self.token_version)
+	}
+
+	pub fn set_token_version(&mut self, token_version: TokenVersion) {
+		self.token_version = token_version as u8;
+	}
+
+	pub fn value(&self) -> u64 {
+		self.value.into()
+	}
+
+	pub fn set_value(&mut self, value: u64) {
+		self.value = value.into();
+	}
+
+	pub fn seller_approved(&self) -> bool {
+		self.seller_approved != 0
+	}
+
+	pub fn set_seller_approved(&mut self, approved: bool) {
+		self.seller_approved = approved as u8;
+	}
+
+	pub fn buyer_approved(&self) -> bool {
+		self.buyer_approved != 0
+	}
+
+	pub fn set_buyer_approved(&mut self, approved: bool) {
+		self.buyer_approved = approved as u8;
+	}
+
+	pub fn arbiter_vote_1(&self) -> Result<VotingOptions, ProgramError> {
+		VotingOptions::try_from(self.arbiter_vote_1)
+	}
+
+	pub fn set_arbiter_vote_1(&mut self, vote: VotingOptions) {
+		self.arbiter_vote_1 = vote as u8;
+	}
+
+	pub fn arbiter_vote_2(&self) -> Result<VotingOptions, ProgramError> {
+		VotingOptions::try_from(self.arbiter_vote_2)
+	}
+
+	pub fn set_arbiter_vote_2(&mut self, vote: VotingOptions) {
+		self.arbiter_vote_2 = vote as u8;
+	}
+
+	pub fn arbiter_vote_3(&self) -> Result<VotingOptions, ProgramError> {
+		VotingOptions::try_from(self.arbiter_vote_3)
+	}
+
+	pub fn set_arbiter_vote_3(&mut self, vote: VotingOptions) {
+		self.arbiter_vote_3 = vote as u8;
+	}
+
+	/// Seat held by the arbiter: 0 to 2 for the arbiter slots, then the appeal panel from [APPEAL_SEAT].
+	pub fn arbiter_seat(&self, arbiter: &Pubkey) -> Option<usize> {
+
+		if arbiter.to_bytes() == ZERO_ACCOUNT {
+			return None
+		}
+
+		[self.arbiter1, self.arbiter2, self.arbiter3].iter()
+			.chain(self.appeal_arbiters.iter())
+			.position(|seat| seat == arbiter)
+	}
+
+	/// True if the arbiter of the seat has the stake locked for this operation.
+	pub fn seat_locked(&self, seat: usize) -> bool {
+		self.arbiter_locks & (1 << seat) != 0
+	}
+
+	pub fn set_seat_locked(&mut self, seat: usize, locked: bool) {
+		if locked {
+			self.arbiter_locks |= 1 << seat;
+		} else {
+			self.arbiter_locks &= !(1 << seat);
+		}
+	}
+
+	pub fn evidence_deadline(&self) -> UnixTimestamp {
+		self.evidence_deadline.into()
+	}
+
+	pub fn set_evidence_deadline(&mut self, evidence_deadline: UnixTimestamp) {
+		self.evidence_deadline = evidence_deadline.into();
+	}
+
+	pub fn set_dispute_started_at(&mut self, dispute_started_at: UnixTimestamp) {
+		self.dispute_started_at = dispute_started_at.into();
+	}
+
+	pub fn vote_deadline(&self) -> UnixTimestamp {
+		self.vote_deadline.into()
+	}
+
+	pub fn set_vote_deadline(&mut self, vote_deadline: UnixTimestamp) {
+		self.vote_deadline = vote_deadline.into();
+	}
+
+	/// True if the arbiter was invited by one of the parties.
+	pub fn is_invited(&self, arbiter: &Pubkey) -> bool {
+		arbiter.to_bytes() != ZERO_ACCOUNT && self.arbiter_invites.contains(arbiter)
+	}
+
+	/// Adds the arbiter to the first unused invitation.
+	pub fn add_invite(&mut self, arbiter: Pubkey) -> Result<(), ProgramError> {
+
+		match self.arbiter_invites.iter_mut().find(|invite| invite.to_bytes() == ZERO_ACCOUNT) {
+			Some(invite) => {
+				*invite = arbiter;
+				Ok(())
+			},
+			None => {
+				msg!("Arbiter invitations already filled.");
+				Err(ProgramError::AccountAlreadyInitialized)
+			},
+		}
+	}
+
+	/// Revokes the invitation of the arbiter, returning false if there was none.
+	pub fn remove_invite(&mut self, arbiter: &Pubkey) -> bool {
+
+		match self.arbiter_invites.iter_mut().find(|invite| *invite == arbiter) {
+			Some(invite) if arbiter.to_bytes() != ZERO_ACCOUNT => {
+				*invite = Default::default();
+				true
+			},
+			_ => false,
+		}
+	}
+
+	/// True if the account holds one of the arbiter slots.
+	pub fn is_arbiter(&self, account: &Pubkey) -> bool {
+		account.to_bytes() != ZERO_ACCOUNT &&
+			(self.arbiter1 == *account || self.arbiter2 == *account || self.arbiter3 == *account)
+	}
+
+	/// True once the three arbiter slots are taken.
+	pub fn arbiters_filled(&self) -> bool {
+		self.arbiter1.to_bytes() != ZERO_ACCOUNT &&
+			self.arbiter2.to_bytes() != ZERO_ACCOUNT &&
+			self.arbiter3.to_bytes() != ZERO_ACCOUNT
+	}
+
+	pub fn arbiter_selection(&self) -> Result<ArbiterSelection, ProgramError> {
+		ArbiterSelection::try_from(self.arbiter_selection)
+	}
+
+	pub fn set_arbiter_selection(&mut self, arbiter_selection: ArbiterSelection) {
+		self.arbiter_selection = arbiter_selection as u8;
+	}
+
+	pub fn appeal_deadline(&self) -> UnixTimestamp {
+		self.appeal_deadline.into()
+	}
+
+	pub fn set_appeal_deadline(&mut self, appeal_deadline: UnixTimestamp) {
+		self.appeal_deadline = appeal_deadline.into();
+	}
+
+	pub fn appeal_bond(&self) -> u64 {
+		self.appeal_bond.into()
+	}
+
+	pub fn set_appeal_bond(&mut self, appeal_bond: u64) {
+		self.appeal_bond = appeal_bond.into();
+	}
+
+	pub fn appeal_vote(&self, index: usize) -> Result<VotingOptions, ProgramError> {
+		VotingOptions::try_from(self.appeal_votes[index])
+	}
+
+	pub fn set_appeal_vote(&mut self, index: usize, vote: VotingOptions) {
+		self.appeal_votes[index] = vote as u8;
+	}
+
+	pub fn settlement_buyer_amount(&self) -> u64 {
+		self.settlement_buyer_amount.into()
+	}
+
+	pub fn set_settlement_buyer_amount(&mut self, settlement_buyer_amount: u64) {
+		self.settlement_buyer_amount = settlement_buyer_amount.into();
+	}
+
+	pub fn milestone_count(&self) -> u8 {
+		self.milestone_count
+	}
+
+	pub fn set_milestone_count(&mut self, milestone_count: u8) {
+		self.milestone_count = milestone_count;
+	}
+
+	pub fn line_item_count(&self) -> u8 {
+		self.line_item_count
+	}
+
+	pub fn set_line_item_count(&mut self, line_item_count: u8) {
+		self.line_item_count = line_item_count;
+	}
+
+	pub fn listing_revision(&self) -> u32 {
+		self.listing_revision.into()
+	}
+
+	pub fn set_listing_revision(&mut self, listing_revision: u32) {
+		self.listing_revision = listing_revision.into();
+	}
+
+	pub fn listing_updated_at(&self) -> UnixTimestamp {
+		self.listing_updated_at.into()
+	}
+
+	pub fn set_listing_updated_at(&mut self, listing_updated_at: UnixTimestamp) {
+		self.listing_updated_at = listing_updated_at.into();
+	}
+
+	pub fn shipped_at(&self) -> UnixTimestamp {
+		self.shipped_at.into()
+	}
+
+	pub fn set_shipped_at(&mut self, shipped_at: UnixTimestamp) {
+		self.shipped_at = shipped_at.into();
+	}
+
+	pub fn delivered_at(&self) -> UnixTimestamp {
+		self.delivered_at.into()
+	}
+
+	pub fn set_delivered_at(&mut self, delivered_at: UnixTimestamp) {
+		self.delivered_at = delivered_at.into();
+	}
+
+	pub fn inspection_period(&self) -> UnixTimestamp {
+		self.inspection_period.into()
+	}
+
+	pub fn set_inspection_period(&mut self, inspection_period: UnixTimestamp) {
+		self.inspection_period = inspection_period.into();
+	}
+
+	pub fn inspection_deadline(&self) -> UnixTimestamp {
+		self.inspection_deadline.into()
+	}
+
+	pub fn set_inspection_deadline(&mut self, inspection_deadline: UnixTimestamp) {
+		self.inspection_deadline = inspection_deadline.into();
+	}
+
+	/// Marks the item delivered at the given time, starting the inspection period if there is one.
+	/// The period runs from `now`, when the delivery is recorded, since a signed delivery time can be backdated.
+	pub fn set_delivered(&mut self, delivered_at: UnixTimestamp, now: UnixTimestamp) {
+		self.set_status(OperationStatus::Delivered);
+		self.set_delivered_at(delivered_at);
+
+		if self.inspection_period() != 0 {
+			self.set_inspection_deadline(now.saturating_add(self.inspection_period()));
+		}
+	}
+}
+
+/// Defines the header of the evidence log of a dispute.
+///
+/// The log is a PDA of the program, derived from [EVIDENCE_LOG_SEED] and the operation account address,
+/// created when the dispute starts. The header is followed by `count` [EvidenceEntry] records.
+/// Entries are only ever appended, the account grows by one entry each time.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct EvidenceLogHeader {
+	/// Layout version, always the first byte. See [EVIDENCE_LOG_VERSION].
+	pub version: u8,
+
+	/// Bump seed of the PDA.
+	pub bump: u8,
+
+	/// Operation account under dispute.
+	pub operation: Pubkey,
+
+	/// Number of entries that follow the header.
+	count: PodU32,
+}
+
+impl EvidenceLogHeader {
+
+	/// Maps an [EvidenceLogHeader] over the account data, checking its version.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		if data.len() < EVIDENCE_LOG_HEADER_LEN || data[0] != EVIDENCE_LOG_VERSION {
+			msg!("Unsupported evidence log version.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		bytemuck::try_from_bytes_mut(&mut data[..EVIDENCE_LOG_HEADER_LEN])
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+
+	pub fn count(&self) -> u32 {
+		self.count.into()
+	}
+
+	pub fn set_count(&mut self, count: u32) {
+		self.count = count.into();
+	}
+}
+
+/// Defines a single submission to the evidence log.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct EvidenceEntry {
+	/// Public key of the submitter.
+	pub submitter: Pubkey,
+
+	/// Seller/Buyer/Arbiter. See [EvidenceRole].
+	role: u8,
+
+	/// Time of submission. Zero for evidence carried over from older layouts, whose time is unknown.
+	submitted_at: PodI64,
+
+	/// Content reference of the evidence, ruling or comment.
+	pub content: ContentRefData,
+}
+
+impl EvidenceEntry {
+
+	pub fn new(submitter: Pubkey, role: EvidenceRole, submitted_at: UnixTimestamp, content: ContentRefData) -> Self {
+		EvidenceEntry {
+			submitter,
+			role: role as u8,
+			submitted_at: submitted_at.into(),
+			content,
+		}
+	}
+}
+
+/// Defines the profile of an arbiter, shared by every operation they take part in.
+///
+/// The profile is a PDA of the program, derived from [ARBITER_PROFILE_SEED] and the arbiter address.
+/// Parties can review it before approving an arbiter. Only active arbiters can register to operations.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct ArbiterProfile {
+	/// Layout version, always the first byte. See [ARBITER_PROFILE_VERSION].
+	pub version: u8,
+
+	/// Bump seed of the PDA.
+	pub bump: u8,
 
 	/// Public key of the arbiter.
-	pub arbiter_vote_2: VotingOptions,
+	pub arbiter: Pubkey,
+
+	/// Arbiter is taking new cases.
+	active: u8,
+
+	/// Content reference of the arbiter bio.
+	pub bio: ContentRefData,
+
+	/// Number of operations the arbiter registered to.
+	cases_accepted: PodU32,
+
+	/// Number of disputes the arbiter voted in.
+	votes_cast: PodU32,
+
+	/// Number of disputes the arbiter was replaced in, for not voting in time.
+	votes_missed: PodU32,
+}
+
+impl ArbiterProfile {
+
+	/// Maps an [ArbiterProfile] over the account data, checking its version.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		if data.len() != ARBITER_PROFILE_LEN || data[0] != ARBITER_PROFILE_VERSION {
+			msg!("Unsupported arbiter profile version.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		bytemuck::try_from_bytes_mut(data)
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+
+	pub fn active(&self) -> bool {
+		self.active != 0
+	}
+
+	pub fn set_active(&mut self, active: bool) {
+		self.active = active as u8;
+	}
+
+	pub fn add_case_accepted(&mut self) {
+		self.cases_accepted = u32::from(self.cases_accepted).saturating_add(1).into();
+	}
+
+	pub fn add_vote_cast(&mut self) {
+		self.votes_cast = u32::from(self.votes_cast).saturating_add(1).into();
+	}
+
+	pub fn add_vote_missed(&mut self) {
+		self.votes_missed = u32::from(self.votes_missed).saturating_add(1).into();
+	}
+}
+
+/// Defines the stake an arbiter locks to be eligible for operations.
+///
+/// The stake is a PDA of the program, derived from [ARBITER_STAKE_SEED] and the arbiter address.
+/// It holds the staked lamports on top of its own rent; see [MIN_ARBITER_STAKE] and [STAKE_SLASH_PERCENT].
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct ArbiterStake {
+	/// Layout version, always the first byte. See [ARBITER_STAKE_VERSION].
+	pub version: u8,
+
+	/// Bump seed of the PDA.
+	pub bump: u8,
 
 	/// Public key of the arbiter.
-	pub arbiter_vote_3: VotingOptions,
+	pub arbiter: Pubkey,
+
+	/// Lamports staked, excluding the rent of the account.
+	amount: PodU64,
+
+	/// Time from which the stake can be withdrawn, 0 if no unstake was requested. See [UNSTAKE_COOLDOWN].
+	unstake_at: PodI64,
+
+	/// Lamports slashed so far.
+	slashed: PodU64,
+
+	/// Operations where the arbiter holds a locked seat. The stake cannot be withdrawn until it is zero.
+	active_cases: PodU32,
+}
+
+impl ArbiterStake {
+
+	/// Maps an [ArbiterStake] over the account data, checking its version.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		if data.len() != ARBITER_STAKE_LEN || data[0] != ARBITER_STAKE_VERSION {
+			msg!("Unsupported arbiter stake version.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		bytemuck::try_from_bytes_mut(data)
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+
+	pub fn amount(&self) -> u64 {
+		self.amount.into()
+	}
+
+	pub fn set_amount(&mut self, amount: u64) {
+		self.amount = amount.into();
+	}
+
+	pub fn unstake_at(&self) -> UnixTimestamp {
+		self.unstake_at.into()
+	}
+
+	pub fn set_unstake_at(&mut self, unstake_at: UnixTimestamp) {
+		self.unstake_at = unstake_at.into();
+	}
+
+	pub fn active_cases(&self) -> u32 {
+		self.active_cases.into()
+	}
+
+	/// Locks the stake for one more operation, see [OperationAccount::seat_locked].
+	pub fn add_active_case(&mut self) {
+		self.active_cases = u32::from(self.active_cases).saturating_add(1).into();
+	}
+
+	/// Unlocks the stake of an operation where the seat was voted, freed or finished.
+	pub fn remove_active_case(&mut self) {
+		self.active_cases = u32::from(self.active_cases).saturating_sub(1).into();
+	}
+
+	/// The arbiter can take new cases: enough stake, and no unstake requested.
+	pub fn is_eligible(&self) -> bool {
+		self.amount() >= MIN_ARBITER_STAKE && self.unstake_at() == 0
+	}
+
+	/// Takes [STAKE_SLASH_PERCENT] of the stake away, returning the lamports taken.
+	pub fn slash(&mut self) -> u64 {
+		let slashed = (self.amount() as u128 * STAKE_SLASH_PERCENT as u128 / 100) as u64;
+
+		self.set_amount(self.amount() - slashed);
+		self.slashed = u64::from(self.slashed).saturating_add(slashed).into();
+
+		slashed
+	}
+}
+
+/// Defines the header of the pool of arbiters available for random selection.
+///
+/// The pool is a single PDA of the program, derived from [ARBITER_POOL_SEED].
+/// The header is followed by `count` arbiter public keys, and possibly unused space
+/// left by arbiters who left. Only active arbiters with an eligible stake can join.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct ArbiterPoolHeader {
+	/// Layout version, always the first byte. See [ARBITER_POOL_VERSION].
+	pub version: u8,
+
+	/// Bump seed of the PDA.
+	pub bump: u8,
+
+	/// Number of arbiters that follow the header.
+	count: PodU32,
+}
+
+impl ArbiterPoolHeader {
+
+	/// Maps an [ArbiterPoolHeader] over the account data, checking its version.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		if data.len() < ARBITER_POOL_HEADER_LEN || data[0] != ARBITER_POOL_VERSION {
+			msg!("Unsupported arbiter pool version.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		bytemuck::try_from_bytes_mut(&mut data[..ARBITER_POOL_HEADER_LEN])
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+
+	pub fn count(&self) -> u32 {
+		self.count.into()
+	}
+
+	pub fn set_count(&mut self, count: u32) {
+		self.count = count.into();
+	}
+}
+
+/// Defines the header of the milestones of an operation.
+///
+/// The milestones are a PDA of the program, derived from [MILESTONES_SEED] and the operation account address,
+/// created by the seller before a buyer registers. The header is followed by `count` [Milestone] records,
+/// whose amounts add up to the value of the operation.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct MilestonesHeader {
+	/// Layout version, always the first byte. See [MILESTONES_VERSION].
+	pub version: u8,
+
+	/// Bump seed of the PDA.
+	pub bump: u8,
+
+	/// Operation account paid in these milestones.
+	pub operation: Pubkey,
+
+	/// Number of milestones that follow the header.
+	pub count: u8,
+}
+
+impl MilestonesHeader {
+
+	/// Maps a [MilestonesHeader] over the account data, checking its version and length.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		if data.len() < MILESTONES_HEADER_LEN || data[0] != MILESTONES_VERSION {
+			msg!("Unsupported milestones version.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		if data.len() != MILESTONES_HEADER_LEN + data[MILESTONES_HEADER_LEN - 1] as usize * MILESTONE_LEN {
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		bytemuck::try_from_bytes_mut(&mut data[..MILESTONES_HEADER_LEN])
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+}
+
+/// Defines a single stage of the payment of an operation.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct Milestone {
+	/// Lamports paid to the seller when the milestone is released.
+	amount: PodU64,
+
+	/// The buyer released the milestone.
+	released: u8,
+
+	/// Content reference of the deliverable of the milestone.
+	pub content: ContentRefData,
+}
+
+impl Milestone {
+
+	pub fn new(amount: u64, content: ContentRefData) -> Self {
+		Milestone {
+			amount: amount.into(),
+			released: 0,
+			content,
+		}
+	}
+
+	pub fn amount(&self) -> u64 {
+		self.amount.into()
+	}
+
+	pub fn released(&self) -> bool {
+		self.released != 0
+	}
+
+	pub fn set_released(&mut self, released: bool) {
+		self.released = released as u8;
+	}
+}
+
+/// Defines the header of the line items of an order.
+///
+/// The line items are a PDA of the program, derived from [LINE_ITEMS_SEED] and the operation account address,
+/// created by the seller before a buyer registers. The header is followed by `count` [LineItem] records,
+/// whose totals add up to the value of the operation.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct LineItemsHeader {
+	/// Layout version, always the first byte. See [LINE_ITEMS_VERSION].
+	pub version: u8,
+
+	/// Bump seed of the PDA.
+	pub bump: u8,
+
+	/// Operation account of the order.
+	pub operation: Pubkey,
+
+	/// Number of line items that follow the header.
+	pub count: u8,
+}
+
+impl LineItemsHeader {
+
+	/// Maps a [LineItemsHeader] over the account data, checking its version and length.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		if data.len() < LINE_ITEMS_HEADER_LEN || data[0] != LINE_ITEMS_VERSION {
+			msg!("Unsupported line items version.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		if data.len() != LINE_ITEMS_HEADER_LEN + data[LINE_ITEMS_HEADER_LEN - 1] as usize * LINE_ITEM_LEN {
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		bytemuck::try_from_bytes_mut(&mut data[..LINE_ITEMS_HEADER_LEN])
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+}
+
+/// Defines a single line of an order: an item, its unit price and the quantity bought.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct LineItem {
+	/// Lamports paid for each unit.
+	unit_price: PodU64,
+
+	/// Units ordered.
+	quantity: PodU32,
+
+	/// Units the seller refunded so far.
+	refunded: PodU32,
+
+	/// Content reference of the item description.
+	pub content: ContentRefData,
+}
+
+impl LineItem {
+
+	pub fn new(unit_price: u64, quantity: u32, content: ContentRefData) -> Self {
+		LineItem {
+			unit_price: unit_price.into(),
+			quantity: quantity.into(),
+			refunded: 0.into(),
+			content,
+		}
+	}
+
+	pub fn unit_price(&self) -> u64 {
+		self.unit_price.into()
+	}
+
+	pub fn quantity(&self) -> u32 {
+		self.quantity.into()
+	}
+
+	pub fn refunded(&self) -> u32 {
+		self.refunded.into()
+	}
+
+	pub fn set_refunded(&mut self, refunded: u32) {
+		self.refunded = refunded.into();
+	}
+}
+
+/// Defines the header of the offer log of an operation.
+///
+/// The log is a PDA of the program, derived from [OFFER_LOG_SEED] and the operation account address,
+/// created with the first offer. The header is followed by `count` [Offer] records.
+/// Offers are only ever appended, the account grows by one offer each time.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct OfferLogHeader {
+	/// Layout version, always the first byte. See [OFFER_LOG_VERSION].
+	pub version: u8,
+
+	/// Bump seed of the PDA.
+	pub bump: u8,
+
+	/// Operation account the offers are made to.
+	pub operation: Pubkey,
+
+	/// Number of offers that follow the header.
+	count: PodU32,
+}
+
+impl OfferLogHeader {
+
+	/// Maps an [OfferLogHeader] over the account data, checking its version.
+	pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+
+		if data.len() < OFFER_LOG_HEADER_LEN || data[0] != OFFER_LOG_VERSION {
+			msg!("Unsupported offer log version.");
+			return Err(ProgramError::InvalidAccountData);
+		}
+
+		bytemuck::try_from_bytes_mut(&mut data[..OFFER_LOG_HEADER_LEN])
+			.map_err(|_e| ProgramError::InvalidAccountData)
+	}
+
+	pub fn count(&self) -> u32 {
+		self.count.into()
+	}
+
+	pub fn set_count(&mut self, count: u32) {
+		self.count = count.into();
+	}
+}
+
+/// Defines a single price offer of a prospective buyer.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Pod, Zeroable, Debug)]
+pub struct Offer {
+	/// Public key of the prospective buyer.
+	pub buyer: Pubkey,
+
+	/// Lamports offered for the operation.
+	amount: PodU64,
+
+	/// Time of the offer.
+	offered_at: PodI64,
+
+	/// The seller accepted the offer.
+	accepted: u8,
+}
+
+impl Offer {
+
+	pub fn new(buyer: Pubkey, amount: u64, offered_at: UnixTimestamp) -> Self {
+		Offer {
+			buyer,
+			amount: amount.into(),
+			offered_at: offered_at.into(),
+			accepted: 0,
+		}
+	}
+
+	pub fn amount(&self) -> u64 {
+		self.amount.into()
+	}
+
+	pub fn offered_at(&self) -> UnixTimestamp {
+		self.offered_at.into()
+	}
+
+	pub fn accepted(&self) -> bool {
+		self.accepted != 0
+	}
+
+	pub fn set_accepted(&mut self, accepted: bool) {
+		self.accepted = accepted as u8;
+	}
 }
 
 /// List of errors specific to the SCA operation 
@@ -124,5 +1389,38 @@ pub enum SCAError {
 
 	/// Failure to retrieve a Rent object
 	RentError = 1,
+
+	/// Arbiters cannot vote until the evidence period is over
+	EvidencePeriodOpen = 2,
+
+	/// Parties cannot add evidence once the evidence period is over
+	EvidencePeriodOver = 3,
+
+	/// Arbiters cannot be replaced until the voting period is over
+	VotingPeriodOpen = 4,
+
+	/// Arbiters need an eligible stake to register
+	InsufficientStake = 5,
+
+	/// Arbiters cannot withdraw the stake until the cooldown is over, nor while they hold a case
+	UnstakeCooldown = 6,
+
+	/// The arbiter pool does not have enough arbiters to pick from
+	NotEnoughArbiters = 7,
+
+	/// The verdict cannot be claimed until the appeal period is over
+	AppealPeriodOpen = 8,
+
+	/// The verdict cannot be appealed once the appeal period is over
+	AppealPeriodOver = 9,
+
+	/// The delivery attestation was not signed by the attestor of the operation
+	InvalidAttestation = 10,
+
+	/// The value cannot be released until the inspection period is over
+	InspectionPeriodOpen = 11,
+
+	/// Disputes cannot start once the inspection period is over
+	InspectionPeriodOver = 12,
 }
 
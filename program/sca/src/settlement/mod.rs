@@ -0,0 +1,325 @@
+//! # settlement
+//! Functionality related to settling an operation by agreement of the parties
+//!
+//! List of supported instructions
+//!
+//! 1. Propose Settlement -> [propose_settlement]
+//! 2. Accept Settlement -> [accept_settlement]
+//!
+//! Either party proposes how the token value is split, and the other co-signs it.
+//! The split is paid right away and ends the operation, arbiters are not involved.
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::scatype::{
+	OperationAccount, OperationStatus, ZERO_ACCOUNT,
+};
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+};
+
+/// True if the token value is still escrowed and the parties can settle.
+fn can_settle(status: OperationStatus) -> bool {
+	matches!(status,
+		OperationStatus::BuyerDeposited |
+		OperationStatus::Shipped |
+		OperationStatus::Delivered |
+		OperationStatus::InDispute |
+		OperationStatus::InVoting |
+		OperationStatus::InAppeal)
+}
+
+/// Allows a Buyer/Seller to propose a split of the token value, replacing any previous proposal.
+/// Note that this function expects a BuyerDeposited/Shipped/Delivered/InDispute/InVoting/InAppeal [OperationAccount].
+///
+/// buyer_amount: Lamports the buyer gets, the seller gets the rest of the value.
+///
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn propose_settlement(program_id: &Pubkey, accounts: &[AccountInfo], buyer_amount: u64) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get PARTICIPANT account
+	let participant_account_info = next_account_info(accounts_iter)?;
+
+	// Check PARTICIPANT account validity
+	is_signed_by_writable_account(participant_account_info, "PARTICIPANT account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if !can_settle(operation_account_data.status()?) {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if *participant_account_info.key != operation_account_data.seller &&
+		*participant_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer/Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if buyer_amount > operation_account_data.value() {
+		msg!("Settlement exceeds the token value.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ========================= Change state section
+
+	// Set externally
+	operation_account_data.settlement_proposer = *participant_account_info.key;
+	operation_account_data.set_settlement_buyer_amount(buyer_amount);
+
+	msg!("Settlement proposed, buyer amount: {}", buyer_amount);
+
+	Ok(())
+}
+
+/// Allows the other Buyer/Seller to accept the proposed settlement, paying it out and ending the operation.
+/// The amount must match the proposal, so that it cannot be changed right before accepting.
+/// Any appeal bond is returned to the appellant.
+/// Note that this function expects a BuyerDeposited/Shipped/Delivered/InDispute/InVoting/InAppeal [OperationAccount].
+///
+/// buyer_amount: Lamports the buyer gets, as in the accepted proposal.
+///
+/// Accounts:
+/// 1. PARTICIPANT - Account of the item seller/buyer who did not propose, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 3. PROPOSER - Account of the other party, who proposed the settlement.
+#[inline(never)]
+pub fn accept_settlement(program_id: &Pubkey, accounts: &[AccountInfo], buyer_amount: u64) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get PARTICIPANT account
+	let participant_account_info = next_account_info(accounts_iter)?;
+
+	// Check PARTICIPANT account validity
+	is_signed_by_writable_account(participant_account_info, "PARTICIPANT account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the PROPOSER account, validated below
+	let proposer_account_info = next_account_info(accounts_iter)?;
+
+
+	// ================ Enforce configuration rules section
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if !can_settle(operation_account_data.status()?) {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// CHECK: Is there no proposal to accept??? -> Reject
+	if operation_account_data.settlement_proposer.to_bytes() == ZERO_ACCOUNT {
+		msg!("No settlement proposed.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if *participant_account_info.key != operation_account_data.seller &&
+		*participant_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer/Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *participant_account_info.key == operation_account_data.settlement_proposer {
+		msg!("The settlement must be accepted by the other party.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *proposer_account_info.key != operation_account_data.settlement_proposer || !proposer_account_info.is_writable {
+		msg!("Invalid PROPOSER account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if buyer_amount != operation_account_data.settlement_buyer_amount() {
+		msg!("Settlement terms changed.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// ========================= Change state section
+
+	let (seller_account_info, buyer_account_info) = if *participant_account_info.key == operation_account_data.seller {
+		(participant_account_info, proposer_account_info)
+	} else {
+		(proposer_account_info, participant_account_info)
+	};
+
+	let seller_amount = operation_account_data.value() - buyer_amount;
+	let appeal_bond = operation_account_data.appeal_bond();
+
+	// Debit from_account and credit to_account
+	**operation_account_info.try_borrow_mut_lamports()? -= operation_account_data.value() + appeal_bond;
+	**buyer_account_info.try_borrow_mut_lamports()? += buyer_amount;
+	**seller_account_info.try_borrow_mut_lamports()? += seller_amount;
+
+	if operation_account_data.appellant == *seller_account_info.key {
+		**seller_account_info.try_borrow_mut_lamports()? += appeal_bond;
+	} else {
+		**buyer_account_info.try_borrow_mut_lamports()? += appeal_bond;
+	}
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_status(OperationStatus::Settled);
+
+	msg!("Settlement accepted, buyer amount: {}, seller amount: {}", buyer_amount, seller_amount);
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bytemuck::{bytes_of, Zeroable};
+
+	use crate::scatype::OPERATION_ACCOUNT_VERSION;
+
+	const SELLER: Pubkey = Pubkey::new_from_array([1; 32]);
+	const BUYER: Pubkey = Pubkey::new_from_array([2; 32]);
+
+	const VALUE: u64 = 1_000;
+	const BALANCE: u64 = 5_000;
+
+	fn operation(status: OperationStatus, proposer: Pubkey, buyer_amount: u64) -> OperationAccount {
+		let mut operation_account_data = OperationAccount::zeroed();
+		operation_account_data.version = OPERATION_ACCOUNT_VERSION;
+		operation_account_data.set_status(status);
+		operation_account_data.set_value(VALUE);
+		operation_account_data.seller = SELLER;
+		operation_account_data.buyer = BUYER;
+		operation_account_data.settlement_proposer = proposer;
+		operation_account_data.set_settlement_buyer_amount(buyer_amount);
+
+		operation_account_data
+	}
+
+	/// Runs [accept_settlement] by the party who did not propose, returning the result, the operation
+	/// and the lamports of the operation, the seller and the buyer afterwards.
+	fn accept(operation_account_data: &OperationAccount, buyer_amount: u64) -> (ProgramResult, OperationAccount, [u64; 3]) {
+		let program_id = crate::id();
+		let system_program = Pubkey::default();
+		let operation_key = Pubkey::new_from_array([9; 32]);
+
+		let mut operation_data = bytes_of(operation_account_data).to_vec();
+		let mut lamports = [BALANCE + VALUE + operation_account_data.appeal_bond(), BALANCE, BALANCE];
+		let [operation_lamports, seller_lamports, buyer_lamports] = &mut lamports;
+		let (mut seller_data, mut buyer_data) = (vec![], vec![]);
+
+		let acceptor_is_seller = operation_account_data.settlement_proposer == BUYER;
+
+		let operation_account_info = AccountInfo::new(&operation_key, false, true, operation_lamports, &mut operation_data,
+			&program_id, false, 0);
+		let seller_account_info = AccountInfo::new(&SELLER, acceptor_is_seller, true, seller_lamports, &mut seller_data,
+			&system_program, false, 0);
+		let buyer_account_info = AccountInfo::new(&BUYER, !acceptor_is_seller, true, buyer_lamports, &mut buyer_data,
+			&system_program, false, 0);
+
+		let accounts = if acceptor_is_seller {
+			[seller_account_info, operation_account_info.clone(), buyer_account_info]
+		} else {
+			[buyer_account_info, operation_account_info.clone(), seller_account_info]
+		};
+
+		let result = accept_settlement(&program_id, &accounts, buyer_amount);
+		drop(accounts);
+		drop(operation_account_info);
+
+		(result, *OperationAccount::load(&operation_data).unwrap(), lamports)
+	}
+
+	#[test]
+	fn pays_the_agreed_split() {
+		let (result, settled, lamports) = accept(&operation(OperationStatus::Delivered, BUYER, 300), 300);
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(settled.status(), Ok(OperationStatus::Settled));
+		assert_eq!(lamports, [BALANCE, BALANCE + 700, BALANCE + 300]);
+	}
+
+	#[test]
+	fn pays_splits_that_give_everything_to_either_party() {
+		let (_result, _settled, lamports) = accept(&operation(OperationStatus::InDispute, SELLER, VALUE), VALUE);
+		assert_eq!(lamports, [BALANCE, BALANCE, BALANCE + VALUE]);
+
+		let (_result, _settled, lamports) = accept(&operation(OperationStatus::InDispute, SELLER, 0), 0);
+		assert_eq!(lamports, [BALANCE, BALANCE + VALUE, BALANCE]);
+	}
+
+	#[test]
+	fn returns_the_appeal_bond_to_the_appellant() {
+		for (appellant, bonus) in [(SELLER, [100, 0]), (BUYER, [0, 100])] {
+			let mut appealed = operation(OperationStatus::InAppeal, BUYER, 400);
+			appealed.set_appeal_bond(100);
+			appealed.appellant = appellant;
+
+			let (result, _settled, lamports) = accept(&appealed, 400);
+
+			assert_eq!(result, Ok(()));
+			assert_eq!(lamports, [BALANCE, BALANCE + 600 + bonus[0], BALANCE + 400 + bonus[1]]);
+		}
+	}
+
+	#[test]
+	fn rejects_changed_terms_and_closed_operations() {
+		let (result, unchanged, lamports) = accept(&operation(OperationStatus::Delivered, BUYER, 300), 301);
+
+		assert_eq!(result, Err(ProgramError::InvalidArgument));
+		assert_eq!(unchanged.status(), Ok(OperationStatus::Delivered));
+		assert_eq!(lamports, [BALANCE + VALUE, BALANCE, BALANCE]);
+
+		let (result, _unchanged, _lamports) = accept(&operation(OperationStatus::DisputeResolved, BUYER, 300), 300);
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+
+		let (result, _unchanged, _lamports) = accept(&operation(OperationStatus::Delivered, Pubkey::default(), 0), 0);
+		assert_eq!(result, Err(ProgramError::InvalidAccountData));
+	}
+}
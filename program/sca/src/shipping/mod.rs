@@ -0,0 +1,542 @@
+//! # shipping
+//! Functionality related to the shipment and delivery of the item
+//!
+//! List of supported instructions
+//!
+//! 1. Mark Shipped -> [mark_shipped]
+//! 2. Confirm Received -> [confirm_received]
+//! 3. Attest Delivery -> [attest_delivery]
+//! 4. Finalize Release -> [finalize_release]
+//!
+//! Once the buyer deposits, the seller records the shipment with a tracking reference,
+//! and the buyer confirms receiving the item. Both are timestamped on-chain, so a dispute
+//! can refer to when the item left and arrived. The token value stays escrowed until released as usual.
+//! Operations with milestones are released in stages instead, so they are not shipped nor delivered at once.
+//!
+//! Operations with an attestor, such as a courier or an oracle, can have the delivery confirmed
+//! by a signature of the attestor instead, checked by the Ed25519 program in the same transaction.
+//!
+//! Operations with an inspection period give the buyer that long to dispute once the delivery is recorded on-chain.
+//! After it, disputes are closed and anyone can release the token value to the seller.
+//!
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+	clock::UnixTimestamp, ed25519_program,
+	sysvar::{
+		self,
+		instructions::{load_current_index_checked, load_instruction_at_checked},
+	}
+};
+
+use crate::content::{ContentRef, ContentRefData};
+
+use crate::scatype::{OperationAccount, OperationStatus, SCAError, ZERO_ACCOUNT};
+
+use crate::account::{
+	is_owned_and_writable,
+	is_signed_by_writable_account,
+};
+
+use crate::dispute::current_timestamp;
+
+/// Allows a Seller to record that the item was shipped, along with its tracking reference.
+/// Note that this function expects a BuyerDeposited [OperationAccount] without milestones.
+///
+/// tracking_content: Content reference of the shipment tracking, conformant to [crate::instruction::OperationInstruction::MarkShipped]
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn mark_shipped(program_id: &Pubkey, accounts: &[AccountInfo], tracking_content: ContentRef) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	is_signed_by_writable_account(seller_account_info, "SELLER account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::BuyerDeposited {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// Operations with milestones are released in stages, not delivered at once
+	if operation_account_data.milestone_count() != 0 {
+		msg!("Operation is released by milestones.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	// The content reference was already validated when the instruction was unpacked
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_status(OperationStatus::Shipped);
+	operation_account_data.set_shipped_at(unix_timestamp);
+
+	// Set externally
+	operation_account_data.tracking_content = ContentRefData::from(&tracking_content);
+
+	msg!("Item shipped at: {}", unix_timestamp);
+
+	Ok(())
+}
+
+/// Allows a Buyer to confirm receiving the item. The token value stays escrowed until released.
+/// Items handed over without a recorded shipment can be confirmed too.
+/// Note that this function expects a BuyerDeposited/Shipped [OperationAccount] without milestones.
+///
+/// Accounts:
+/// 1. BUYER - Account of the item buyer, who also pays for this transaction.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn confirm_received(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get BUYER account
+	let buyer_account_info = next_account_info(accounts_iter)?;
+
+	// Check BUYER account validity
+	is_signed_by_writable_account(buyer_account_info, "BUYER account is not a valid account.")?;
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	let status = operation_account_data.status()?;
+
+	if status != OperationStatus::BuyerDeposited && status != OperationStatus::Shipped {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// Operations with milestones are released in stages, not delivered at once
+	if operation_account_data.milestone_count() != 0 {
+		msg!("Operation is released by milestones.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *buyer_account_info.key != operation_account_data.buyer {
+		msg!("Invalid Buyer account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_delivered(unix_timestamp, unix_timestamp);
+
+	msg!("Item received at: {}", unix_timestamp);
+
+	Ok(())
+}
+
+/// Records the delivery of the item as signed by the attestor of the operation, on behalf of the buyer.
+/// The attestor signs the operation address, the delivered flag and the time of delivery, see [delivery_attestation_message],
+/// and the signature is checked by an Ed25519 program instruction right before this one. Anyone can submit it.
+/// Note that this function expects a BuyerDeposited/Shipped [OperationAccount] with an attestor and without milestones.
+///
+/// attestation: Delivered flag and time of delivery as signed, conformant to [crate::instruction::OperationInstruction::AttestDelivery]
+///
+/// Accounts:
+/// 1. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+/// 2. INSTRUCTIONS - The Instructions sysvar, holding the Ed25519 program instruction.
+#[inline(never)]
+pub fn attest_delivery(program_id: &Pubkey, accounts: &[AccountInfo], attestation: (bool, UnixTimestamp)) -> ProgramResult {
+
+	// Destructure attestation tuple
+	let (delivered, delivered_at) = attestation;
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+	// Get the INSTRUCTIONS account
+	let instructions_account_info = next_account_info(accounts_iter)?;
+
+	// Check INSTRUCTIONS account validity
+	if !sysvar::instructions::check_id(instructions_account_info.key) {
+		msg!("Invalid Instructions sysvar");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	let status = operation_account_data.status()?;
+
+	if status != OperationStatus::BuyerDeposited && status != OperationStatus::Shipped {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// Operations with milestones are released in stages, not delivered at once
+	if operation_account_data.milestone_count() != 0 {
+		msg!("Operation is released by milestones.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if operation_account_data.attestor.to_bytes() == ZERO_ACCOUNT {
+		msg!("Operation has no attestor.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	if !delivered {
+		msg!("Attestation does not confirm the delivery.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// The item cannot arrive before it left, nor in the future
+	if delivered_at > unix_timestamp || delivered_at < operation_account_data.shipped_at() {
+		msg!("Invalid delivery time.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	let message = delivery_attestation_message(operation_account_info.key, delivered, delivered_at);
+
+	check_ed25519_signature(instructions_account_info, &operation_account_data.attestor, &message)?;
+
+	// ========================= Change state section
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	// The inspection period starts now, so that a backdated delivery time cannot shorten it.
+	operation_account_data.set_delivered(delivered_at, unix_timestamp);
+
+	msg!("Item delivery attested at: {}", delivered_at);
+
+	Ok(())
+}
+
+/// Releases the token deposit to the seller once the inspection period after the delivery is over.
+/// Anyone can submit it, so the seller does not depend on the buyer to get paid.
+/// Note that this function expects a Delivered [OperationAccount] with an inspection period.
+///
+/// Accounts:
+/// 1. SELLER - Account of the item seller, who receives the token value.
+/// 2. OPERATIONACCOUNT - Represents the ongoing operation. Comformant to [OperationAccount]
+#[inline(never)]
+pub fn finalize_release(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+	// Iterating accounts is safer than indexing
+	let accounts_iter = &mut accounts.iter();
+
+	// ================ Validate accounts section
+
+	//	Get SELLER account
+	let seller_account_info = next_account_info(accounts_iter)?;
+
+	// Check SELLER account validity
+	// Seller is not a signer here, and the pubkey is already stored.
+	if !seller_account_info.is_writable {
+		msg!("SELLER account is not a valid account.");
+		return Err(ProgramError::InvalidArgument)
+	}
+
+	// Get the OPERATIONACCOUNT account
+	let operation_account_info = next_account_info(accounts_iter)?;
+
+	// Check OPERATIONACCOUNT account validity
+	is_owned_and_writable(program_id, operation_account_info, "OPERATIONACCOUNT account is not a valid account.")?;
+
+
+	// ================ Enforce configuration rules section
+
+	let unix_timestamp = current_timestamp();
+
+	// Map the account data so that we can read it and/or modify it in place.
+	let mut operation_account_bytes = operation_account_info.try_borrow_mut_data()?;
+	let operation_account_data = OperationAccount::load_mut(&mut operation_account_bytes)?;
+
+	// ================ Enforce previous state section
+
+
+	// CHECK: Is this DATA account in an incorrect state??? -> Reject
+	if operation_account_data.status()? != OperationStatus::Delivered {
+		msg!("Operation account incorrect state.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	if *seller_account_info.key != operation_account_data.seller {
+		msg!("Invalid Seller account.");
+		return Err(ProgramError::InvalidAccountData)
+	}
+
+	// ======================= Enforce data validity using accounts data section
+
+	let inspection_deadline = operation_account_data.inspection_deadline();
+
+	if inspection_deadline == 0 || unix_timestamp < inspection_deadline {
+		msg!("Inspection period is not over.");
+		return Err(ProgramError::Custom(SCAError::InspectionPeriodOpen as u32))
+	}
+
+	// ========================= Change state section
+
+	// Debit from_account and credit to_account
+	**operation_account_info.try_borrow_mut_lamports()? -= operation_account_data.value();
+	**seller_account_info.try_borrow_mut_lamports()? += operation_account_data.value();
+
+	// Set internally; make sure everything not used is zeroed out anyway.
+	operation_account_data.set_status(OperationStatus::ReleaseRefund);
+
+	msg!("Inspection period over at: {}, token value released.", inspection_deadline);
+
+	Ok(())
+}
+
+/// Returns the message the attestor signs: the operation address, the delivered flag as a byte and the time as little endian.
+pub fn delivery_attestation_message(operation: &Pubkey, delivered: bool, delivered_at: UnixTimestamp) -> Vec<u8> {
+
+	let mut message = Vec::with_capacity(32 + 1 + 8);
+	message.extend_from_slice(operation.as_ref());
+	message.push(delivered as u8);
+	message.extend_from_slice(&delivered_at.to_le_bytes());
+
+	message
+}
+
+/// Size in bytes of the signature count and padding that start an Ed25519 program instruction.
+const ED25519_HEADER_LEN: usize = 2;
+
+/// Size in bytes of the offsets of each signature in an Ed25519 program instruction.
+const ED25519_OFFSETS_LEN: usize = 14;
+
+/// Checks that the instruction right before the current one is an Ed25519 program instruction
+/// verifying a single signature of the signer over the message, all held in its own data.
+/// The signature itself was already verified by the runtime, or the transaction would have failed.
+fn check_ed25519_signature(instructions_account_info: &AccountInfo, signer: &Pubkey, message: &[u8]) -> ProgramResult {
+
+	let current_index = load_current_index_checked(instructions_account_info)?;
+
+	if current_index == 0 {
+		msg!("Ed25519 program instruction not found.");
+		return Err(ProgramError::Custom(SCAError::InvalidAttestation as u32))
+	}
+
+	let instruction = load_instruction_at_checked(current_index as usize - 1, instructions_account_info)?;
+
+	if instruction.program_id != ed25519_program::id() {
+		msg!("Ed25519 program instruction not found.");
+		return Err(ProgramError::Custom(SCAError::InvalidAttestation as u32))
+	}
+
+	let data = &instruction.data;
+
+	if data.len() < ED25519_HEADER_LEN + ED25519_OFFSETS_LEN || data[0] != 1 {
+		msg!("Ed25519 program instruction must verify a single signature.");
+		return Err(ProgramError::Custom(SCAError::InvalidAttestation as u32))
+	}
+
+	// Offsets of the signature, public key and message, and the instructions holding them
+	let offsets: Vec<u16> = data[ED25519_HEADER_LEN..ED25519_HEADER_LEN + ED25519_OFFSETS_LEN]
+		.chunks_exact(2)
+		.map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+		.collect();
+
+	let (public_key_offset, message_offset, message_len) = (offsets[2] as usize, offsets[4] as usize, offsets[5] as usize);
+
+	// Data held in other instructions could be anything, so it must be in the Ed25519 instruction itself
+	if offsets[1] != u16::MAX || offsets[3] != u16::MAX || offsets[6] != u16::MAX {
+		msg!("Ed25519 program instruction must hold its own data.");
+		return Err(ProgramError::Custom(SCAError::InvalidAttestation as u32))
+	}
+
+	let signed_public_key = data.get(public_key_offset..public_key_offset + 32);
+	let signed_message = data.get(message_offset..message_offset + message_len);
+
+	if signed_public_key != Some(signer.as_ref()) || signed_message != Some(message) {
+		msg!("Attestation not signed by the attestor of the operation.");
+		return Err(ProgramError::Custom(SCAError::InvalidAttestation as u32))
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use solana_program::sysvar::instructions::{construct_instructions_data, store_current_index, BorrowedInstruction};
+
+	const OPERATION: Pubkey = Pubkey::new_from_array([1; 32]);
+	const ATTESTOR: Pubkey = Pubkey::new_from_array([9; 32]);
+
+	const DELIVERED_AT: UnixTimestamp = 1_700_000_000;
+
+	/// Offsets of the public key, signature and message in [ed25519_data].
+	const PUBLIC_KEY_OFFSET: u16 = (ED25519_HEADER_LEN + ED25519_OFFSETS_LEN) as u16;
+	const SIGNATURE_OFFSET: u16 = PUBLIC_KEY_OFFSET + 32;
+	const MESSAGE_OFFSET: u16 = SIGNATURE_OFFSET + 64;
+
+	/// Builds the data of an Ed25519 program instruction verifying a single signature held in the instruction itself.
+	/// The signature is not checked here, the runtime does it before the program runs.
+	fn ed25519_data(signer: &Pubkey, message: &[u8]) -> Vec<u8> {
+		let offsets = [SIGNATURE_OFFSET, u16::MAX, PUBLIC_KEY_OFFSET, u16::MAX, MESSAGE_OFFSET, message.len() as u16, u16::MAX];
+
+		[&[1, 0][..], &offsets.map(u16::to_le_bytes).concat(), signer.as_ref(), &[0x5a; 64], message].concat()
+	}
+
+	/// Runs [check_ed25519_signature] as the instruction at `current_index` of a transaction made of `instructions`.
+	fn check(instructions: &[(Pubkey, Vec<u8>)], current_index: u16, signer: &Pubkey, message: &[u8]) -> ProgramResult {
+		let borrowed: Vec<BorrowedInstruction> = instructions.iter()
+			.map(|(program_id, data)| BorrowedInstruction { program_id, accounts: vec![], data })
+			.collect();
+
+		let mut data = construct_instructions_data(&borrowed);
+		store_current_index(&mut data, current_index);
+
+		let mut lamports = 0;
+		let key = sysvar::instructions::id();
+		let owner = sysvar::id();
+		let instructions_account_info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+
+		check_ed25519_signature(&instructions_account_info, signer, message)
+	}
+
+	fn attestation_transaction(ed25519_data: Vec<u8>) -> Vec<(Pubkey, Vec<u8>)> {
+		vec![(ed25519_program::id(), ed25519_data), (crate::id(), vec![37])]
+	}
+
+	fn invalid_attestation() -> ProgramResult {
+		Err(ProgramError::Custom(SCAError::InvalidAttestation as u32))
+	}
+
+	#[test]
+	fn delivery_attestation_message_is_the_operation_flag_and_time() {
+		let message = delivery_attestation_message(&OPERATION, true, DELIVERED_AT);
+
+		assert_eq!(message.len(), 32 + 1 + 8);
+		assert_eq!(&message[..32], OPERATION.as_ref());
+		assert_eq!(message[32], 1);
+		assert_eq!(&message[33..], &DELIVERED_AT.to_le_bytes());
+
+		assert_eq!(delivery_attestation_message(&OPERATION, false, DELIVERED_AT)[32], 0);
+	}
+
+	#[test]
+	fn accepts_the_attestor_signature_over_the_message() {
+		let message = delivery_attestation_message(&OPERATION, true, DELIVERED_AT);
+
+		assert_eq!(check(&attestation_transaction(ed25519_data(&ATTESTOR, &message)), 1, &ATTESTOR, &message), Ok(()));
+	}
+
+	#[test]
+	fn rejects_a_missing_ed25519_instruction() {
+		let message = delivery_attestation_message(&OPERATION, true, DELIVERED_AT);
+
+		// Nothing runs before the current instruction
+		assert_eq!(check(&[(crate::id(), vec![37])], 0, &ATTESTOR, &message), invalid_attestation());
+
+		// Another program runs right before it
+		let transaction = [(crate::id(), ed25519_data(&ATTESTOR, &message)), (crate::id(), vec![37])];
+		assert_eq!(check(&transaction, 1, &ATTESTOR, &message), invalid_attestation());
+	}
+
+	#[test]
+	fn rejects_other_signers_and_messages() {
+		let message = delivery_attestation_message(&OPERATION, true, DELIVERED_AT);
+		let backdated = delivery_attestation_message(&OPERATION, true, DELIVERED_AT - 1);
+		let transaction = attestation_transaction(ed25519_data(&ATTESTOR, &message));
+
+		assert_eq!(check(&transaction, 1, &Pubkey::new_from_array([8; 32]), &message), invalid_attestation());
+		assert_eq!(check(&transaction, 1, &ATTESTOR, &backdated), invalid_attestation());
+	}
+
+	#[test]
+	fn rejects_malformed_ed25519_data() {
+		let message = delivery_attestation_message(&OPERATION, true, DELIVERED_AT);
+		let valid = ed25519_data(&ATTESTOR, &message);
+
+		let mut two_signatures = valid.clone();
+		two_signatures[0] = 2;
+
+		// The public key held in the instruction of index 0, instead of the Ed25519 instruction itself
+		let mut data_elsewhere = valid.clone();
+		data_elsewhere[ED25519_HEADER_LEN + 6..ED25519_HEADER_LEN + 8].copy_from_slice(&0u16.to_le_bytes());
+
+		// The message runs past the end of the data
+		let mut truncated_message = valid.clone();
+		truncated_message.pop();
+
+		for data in [valid[..ED25519_HEADER_LEN + ED25519_OFFSETS_LEN - 1].to_vec(), two_signatures, data_elsewhere, truncated_message] {
+			assert_eq!(check(&attestation_transaction(data), 1, &ATTESTOR, &message), invalid_attestation());
+		}
+	}
+}
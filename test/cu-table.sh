@@ -0,0 +1,23 @@
+#!/bin/sh
+# Builds the compute units table of test/testinfo.md from two runs of the test app.
+# Usage: sh cu-table.sh <log of the baseline build> <log of the new build>
+# Each log is the output of `npm start`, holding "CU <instruction>: <units>" lines.
+# Instructions run more than once keep their first measure.
+
+awk '
+	function units(line) { sub(/^CU /, "", line); sub(/: [0-9]+$/, "", line); return line }
+	/^CU .*: [0-9]+$/ {
+		label = units($0); value = $NF
+		if (FILENAME == ARGV[1]) { if (!(label in before)) before[label] = value }
+		else if (!(label in after)) { after[label] = value; order[++count] = label }
+	}
+	END {
+		print "| Instruction | Before | After | Delta |"
+		print "|---|---:|---:|---:|"
+		for (i = 1; i <= count; i++) {
+			label = order[i]
+			if (label in before) printf "| %s | %d | %d | %+d |\n", label, before[label], after[label], after[label] - before[label]
+			else printf "| %s | - | %d | - |\n", label, after[label]
+		}
+	}
+' "$1" "$2"
@@ -9,7 +9,7 @@ import * as SCA from "./sca";
 
 import * as fs from 'fs';
 import * as path from 'path';
-import { AccountTypes, OperationAccountData, getAccountData } from "./type";
+import { AccountTypes, ArbiterProfileData, ArbiterStakeLayout, EvidenceLogData, MIN_ARBITER_STAKE, decodeArbiterPool, OperationAccountData, contentRefToString, getAccountData, ipfsContent, LineItemInput, LineItemsData, OfferLogData, MilestoneInput, MilestonesData } from "./type";
 import { download, upload } from "./pinata"
 
 const connection = new Connection("http://localhost:8899","finalized");
@@ -18,18 +18,17 @@ var buyer: Keypair;
 
 var arbiters: Keypair[] = [];
 
-async function initOp(ipfsCID:string):Promise<PublicKey> {
+async function initOp(ipfsCID:string, milestones: MilestoneInput[] = [], lineItems: LineItemInput[] = [],
+	intendedBuyer?: PublicKey, attestor?: PublicKey, inspectionPeriod?: bigint):Promise<PublicKey> {
     seller = Util.readKey("seller");
     console.log("Using seller " + seller.publicKey.toBase58());
 
-    let result = await SCA.initializeOperation(connection, seller, ipfsCID);
+    let result = await SCA.initializeOperation(connection, seller, ipfsContent(ipfsCID), undefined, milestones, lineItems, intendedBuyer, attestor, inspectionPeriod);
     console.log(JSON.stringify(result));
 
     let operationAccountInfo = await getAccountData(connection, result, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
-	let arr = new Uint8Array(operationAccountInfo.ipfsCid);
-	let str = Buffer.from(arr.buffer).toString();
-	console.log(str)
+	console.log(contentRefToString(operationAccountInfo.itemContent))
 
 	return result;
 }
@@ -44,6 +43,27 @@ async function recordItemInfo(): Promise<PublicKey> {
     };
     let ipfsCID = await upload(obj);
 	let opInfo = await initOp(ipfsCID);
+
+	// Milestone flow: pay the value in two stages instead
+	// let opInfo = await initOp(ipfsCID, [
+	// 	{ amount: BigInt(400), content: ipfsContent(ipfsCID) },
+	// 	{ amount: BigInt(600), content: ipfsContent(ipfsCID) },
+	// ]);
+
+	// Reserved flow: only the agreed buyer can register
+	// let opInfo = await initOp(ipfsCID, [], [], Util.readKey("buyer").publicKey);
+
+	// Attested flow: a local keypair stands in for the courier attesting the delivery
+	// let opInfo = await initOp(ipfsCID, [], [], undefined, Util.readKey("attestor").publicKey);
+
+	// Inspection flow: the buyer has 30 seconds to dispute once the item is delivered
+	// let opInfo = await initOp(ipfsCID, [], [], undefined, undefined, BigInt(30));
+
+	// Order flow: two line items, the total becomes the value
+	// let opInfo = await initOp(ipfsCID, [], [
+	// 	{ unitPrice: BigInt(300), quantity: 2, content: ipfsContent(ipfsCID) },
+	// 	{ unitPrice: BigInt(100), quantity: 4, content: ipfsContent(ipfsCID) },
+	// ]);
 	return opInfo;
 }
 
@@ -53,10 +73,54 @@ async function assignBuyer(opInfo: PublicKey) {
 	console.log("Using buyer " + buyer.publicKey.toBase58());
 	let operationAccountPubkey = opInfo;
 
-	let result = await SCA.registerBuyer(connection, buyer, operationAccountPubkey);
+	// Register only on the terms and listing revision reviewed here
+	let operationAccountInfo = await getAccountData(connection, operationAccountPubkey, AccountTypes.Operation) as OperationAccountData
+
+	let result = await SCA.registerBuyer(connection, buyer, operationAccountPubkey, operationAccountInfo);
 	console.log(JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "RegisterBuyer");
 
-	let operationAccountInfo = await getAccountData(connection, operationAccountPubkey, AccountTypes.Operation) as OperationAccountData
+	operationAccountInfo = await getAccountData(connection, operationAccountPubkey, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+// Seller lowers the price and updates the item description before anyone registers
+async function updateListing(opInfo: PublicKey) {
+
+    let obj = {
+        name: "Used Macbook Air 13",
+        desc: "bla bla bla, battery replaced",
+        price: "950",
+        token: "Sol",
+    };
+    let ipfsCID = await upload(obj);
+
+	let result = await SCA.updateListing(connection, seller, BigInt(obj.price), ipfsContent(ipfsCID), opInfo);
+	console.log("Seller updates listing " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "UpdateListing");
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+// Buyer offers less than the asking value, and the seller accepts, registering the buyer
+async function negotiate(opInfo: PublicKey) {
+
+	buyer = Util.readKey("buyer");
+	console.log("Using buyer " + buyer.publicKey.toBase58());
+
+	let result = await SCA.proposePrice(connection, buyer, BigInt(900), opInfo);
+	console.log("Buyer offers " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ProposePrice");
+
+	let offerLogInfo = await getAccountData(connection, SCA.offerLogAddress(opInfo), AccountTypes.OfferLog) as OfferLogData
+	console.log(Util.toJSONString(offerLogInfo));
+
+	result = await SCA.acceptOffer(connection, seller, offerLogInfo.count - 1, opInfo);
+	console.log("Seller accepts offer " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "AcceptOffer");
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
 }
 
@@ -72,8 +136,42 @@ async function assignArbiters(opInfo: PublicKey) {
 
     let operationAccountPubkey = opInfo;
 
+	// Only arbiters with an active profile can register
+	for (const arbiter of arbiters) {
+		let bio = await upload({ name: "Arbiter " + arbiter.publicKey.toBase58(), desc: "bla bla bla" });
+		let result = await SCA.registerArbiterProfile(connection, arbiter, true, ipfsContent(bio));
+		console.log("Arbiter profile " + JSON.stringify(result));
+	}
+
+	// And enough stake locked, which is kept between runs
+	for (const arbiter of arbiters) {
+		let stakeInfo = await connection.getAccountInfo(SCA.arbiterStakeAddress(arbiter.publicKey));
+		if (stakeInfo !== null && ArbiterStakeLayout.decode(stakeInfo.data).amount >= MIN_ARBITER_STAKE) continue;
+
+		let result = await SCA.stakeArbiter(connection, arbiter, MIN_ARBITER_STAKE);
+		console.log("Arbiter stake " + JSON.stringify(result));
+	}
+
+	// Pooled arbiters can be picked for operations with random selection
+	let poolInfo = await connection.getAccountInfo(SCA.arbiterPoolAddress());
+	let pooled = poolInfo === null ? [] : decodeArbiterPool(poolInfo.data).arbiters;
+
+	for (const arbiter of arbiters) {
+		if (pooled.some((member) => member.equals(arbiter.publicKey))) continue;
+
+		let result = await SCA.joinArbiterPool(connection, arbiter);
+		console.log("Arbiter joins pool " + JSON.stringify(result));
+	}
+
+	// Only invited arbiters can register
+	for (const arbiter of arbiters) {
+		let result = await SCA.inviteArbiter(connection, seller, arbiter.publicKey, operationAccountPubkey);
+		console.log("Seller invites arbiter " + JSON.stringify(result));
+	}
+
 	let result = await SCA.registerArbiter(connection, arbiters[0], operationAccountPubkey);
 	console.log(JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "RegisterArbiter");
 
 	result = await SCA.registerArbiter(connection, arbiters[1], operationAccountPubkey);
 	console.log(JSON.stringify(result));
@@ -88,6 +186,7 @@ async function assignArbiters(opInfo: PublicKey) {
 async function approvals(opInfo: PublicKey) {
 	let result = await SCA.buyerApproves(connection, buyer, opInfo);
 	console.log("Buyer approves " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ParticipantApprovesArbiters");
 	
     result = await SCA.sellerApproves(connection, seller, opInfo);
 	console.log("Seller approves " + JSON.stringify(result));
@@ -96,24 +195,111 @@ async function approvals(opInfo: PublicKey) {
     console.log(Util.toJSONString(operationAccountInfo));
 }
 
+// The seller designates and approves the arbiters before anyone registers, see assignArbiters,
+// then the buyer registers, approves them and deposits in a single instruction
+async function preAgreedDeal(opInfo: PublicKey) {
+	let result = await SCA.sellerApproves(connection, seller, opInfo);
+	console.log("Seller approves " + JSON.stringify(result));
+
+	buyer = Util.readKey("buyer");
+	console.log("Using buyer " + buyer.publicKey.toBase58());
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+
+	result = await SCA.registerBuyerAndDeposit(connection, buyer, opInfo, operationAccountInfo);
+	console.log("Buyer registered and completed deposit " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "RegisterBuyerAndDeposit");
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
 async function buyerPays(opInfo: PublicKey) {
 	let result = await SCA.buyerDeposit(connection, buyer, opInfo);
 	console.log("Buyer completed deposit " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "BuyerDeposit");
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
 
+	for (const arbiter of arbiters) {
+		let arbiterProfile = await getAccountData(connection, SCA.arbiterProfileAddress(arbiter.publicKey), AccountTypes.ArbiterProfile) as ArbiterProfileData
+		console.log(Util.toJSONString(arbiterProfile));
+	}
+
 	let lamports = await connection.getBalance(buyer.publicKey);
 	console.log("Buyer balance " + lamports.toString());
 }
 
+// Seller ships the item with its tracking reference, and the buyer confirms receiving it
+async function shipAndReceive(opInfo: PublicKey) {
+
+    let tracking = await upload({ carrier: "Fedex", tracking: "123456789012" });
+
+	let result = await SCA.markShipped(connection, seller, ipfsContent(tracking), opInfo);
+	console.log("Seller ships " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "MarkShipped");
+
+	result = await SCA.confirmReceived(connection, buyer, opInfo);
+	console.log("Buyer receives " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ConfirmReceived");
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+// Seller ships the item, and the attestor signs its delivery instead of the buyer, see recordItemInfo
+async function shipAndAttest(opInfo: PublicKey) {
+
+    let tracking = await upload({ carrier: "Fedex", tracking: "123456789012" });
+
+	let result = await SCA.markShipped(connection, seller, ipfsContent(tracking), opInfo);
+	console.log("Seller ships " + JSON.stringify(result));
+
+	// The delivery cannot predate the shipment, nor the clock of the cluster
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+
+	// Anyone can submit the attestation, the seller pays for it here
+	let attestor = Util.readKey("attestor");
+
+	result = await SCA.attestDelivery(connection, seller, attestor, operationAccountInfo.shippedAt, opInfo);
+	console.log("Attestor confirms delivery " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "AttestDelivery");
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+// Once the inspection period is over anyone can release the value, see recordItemInfo
+async function waitAndFinalize(opInfo: PublicKey) {
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+	console.log("Inspection period over at " + operationAccountInfo.inspectionDeadline.toString());
+
+	console.log("Waiting for the inspection period to end...");
+	await Util.sleep((Number(operationAccountInfo.inspectionPeriod) + 5) * 1000);
+
+	// Anyone can finalize, the buyer pays for it here
+	let result = await SCA.finalizeRelease(connection, buyer, seller.publicKey, opInfo);
+	console.log("Finalize release " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "FinalizeRelease");
+
+	let lamports = await connection.getBalance(seller.publicKey);
+	console.log("Seller balance " + lamports.toString());
+}
+
 async function buyerClose(opInfo: PublicKey) {
 	let result = await SCA.buyerRelease(connection, buyer, seller.publicKey, opInfo);
 	console.log("Buyer release " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "BuyerRelease");
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
 
+	for (const arbiter of arbiters) {
+		let arbiterProfile = await getAccountData(connection, SCA.arbiterProfileAddress(arbiter.publicKey), AccountTypes.ArbiterProfile) as ArbiterProfileData
+		console.log(Util.toJSONString(arbiterProfile));
+	}
+
 	let lamports = await connection.getBalance(buyer.publicKey);
 	console.log("Buyer balance " + lamports.toString());
 
@@ -124,10 +310,16 @@ async function buyerClose(opInfo: PublicKey) {
 async function sellerRefund(opInfo: PublicKey) {
 	let result = await SCA.sellerRefund(connection, seller, buyer.publicKey, opInfo);
 	console.log("Seller refund " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "SellerRefund");
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
 
+	for (const arbiter of arbiters) {
+		let arbiterProfile = await getAccountData(connection, SCA.arbiterProfileAddress(arbiter.publicKey), AccountTypes.ArbiterProfile) as ArbiterProfileData
+		console.log(Util.toJSONString(arbiterProfile));
+	}
+
 	let lamports = await connection.getBalance(buyer.publicKey);
 	console.log("Buyer balance " + lamports.toString());
 
@@ -137,7 +329,13 @@ async function sellerRefund(opInfo: PublicKey) {
 }
 
 async function buyerDispute(opInfo: PublicKey) {
-	let result = await SCA.startDispute(connection, buyer, opInfo);
+    let obj = {
+        complaint: "Item not as described",
+        desc: "bla bla bla",
+    };
+    let reasonCID = await upload(obj);
+
+	let result = await SCA.startDispute(connection, buyer, ipfsContent(reasonCID), opInfo);
 	console.log("Buyer dispute " + JSON.stringify(result));
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
@@ -145,8 +343,15 @@ async function buyerDispute(opInfo: PublicKey) {
 }
 
 async function sellerDispute(opInfo: PublicKey) {
-	let result = await SCA.startDispute(connection, seller, opInfo);
+    let obj = {
+        complaint: "Buyer received the item but did not release the deposit",
+        desc: "bla bla bla",
+    };
+    let reasonCID = await upload(obj);
+
+	let result = await SCA.startDispute(connection, seller, ipfsContent(reasonCID), opInfo);
 	console.log("Seller dispute " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "StartDispute");
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
@@ -161,11 +366,15 @@ async function sellerInfo(opInfo: PublicKey) {
     };
     let ipfsCID = await upload(obj);
 
-	let result = await SCA.sellerAddInfo(connection, seller, ipfsCID, opInfo);
+	let result = await SCA.sellerAddInfo(connection, seller, ipfsContent(ipfsCID), opInfo);
 	console.log("Seller added info " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "SellerAddInfo");
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
+
+	let evidenceLog = await getAccountData(connection, SCA.evidenceLogAddress(opInfo), AccountTypes.EvidenceLog) as EvidenceLogData
+    console.log(Util.toJSONString(evidenceLog));
 }
 
 async function buyerInfo(opInfo: PublicKey) {
@@ -177,11 +386,21 @@ async function buyerInfo(opInfo: PublicKey) {
     };
     let ipfsCID = await upload(obj);
 
-	let result = await SCA.buyerAddInfo(connection, buyer, ipfsCID, opInfo);
+	let result = await SCA.buyerAddInfo(connection, buyer, ipfsContent(ipfsCID), opInfo);
 	console.log("Buyer added info " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "BuyerAddInfo");
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
+
+	let evidenceLog = await getAccountData(connection, SCA.evidenceLogAddress(opInfo), AccountTypes.EvidenceLog) as EvidenceLogData
+    console.log(Util.toJSONString(evidenceLog));
+}
+
+// Votes are rejected until the evidence period is over; 30 seconds when built with short-periods
+async function waitEvidencePeriod() {
+	console.log("Waiting for the evidence period to end...");
+	await Util.sleep(35 * 1000);
 }
 
 async function arbiterVotes(opInfo: PublicKey) {
@@ -190,6 +409,7 @@ async function arbiterVotes(opInfo: PublicKey) {
 
 	let result = await SCA.arbiterVote(connection, arbiter1, true, opInfo);
 	console.log("Arbiter1 votes " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ArbiterVote");
 
 	let arbiter2 = Util.readKey("arbiter2");
 	console.log("Using arbiter2 " + arbiter2.publicKey.toBase58());
@@ -202,6 +422,46 @@ async function arbiterVotes(opInfo: PublicKey) {
 
 	result = await SCA.arbiterVote(connection, arbiter3, false, opInfo);
 	console.log("Arbiter3 votes " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ArbiterVote (final tally)");
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+}
+
+async function arbiterRuling(opInfo: PublicKey) {
+	let arbiter1 = Util.readKey("arbiter1");
+
+    let obj = {
+        ruling: "Seller",
+        reason: "bla bla bla",
+    };
+    let ipfsCID = await upload(obj);
+
+	let result = await SCA.arbiterAddInfo(connection, arbiter1, ipfsContent(ipfsCID), opInfo);
+	console.log("Arbiter1 added ruling " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ArbiterAddInfo");
+
+	let evidenceLog = await getAccountData(connection, SCA.evidenceLogAddress(opInfo), AccountTypes.EvidenceLog) as EvidenceLogData
+    console.log(Util.toJSONString(evidenceLog));
+}
+
+// Claims are rejected until the appeal period is over; 30 seconds when built with short-periods
+async function waitAppealPeriod() {
+	console.log("Waiting for the appeal period to end...");
+	await Util.sleep(35 * 1000);
+}
+
+// Needs five more arbiters in the pool, besides the first panel
+async function buyerAppeal(opInfo: PublicKey) {
+    let obj = {
+        grounds: "The arbiters ignored the evidence",
+        desc: "bla bla bla",
+    };
+    let groundsCID = await upload(obj);
+
+	let result = await SCA.appealVerdict(connection, buyer, ipfsContent(groundsCID), opInfo);
+	console.log("Buyer appeal " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "AppealVerdict");
 
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
@@ -210,8 +470,37 @@ async function arbiterVotes(opInfo: PublicKey) {
 async function claim(opInfo: PublicKey) {
 	let result = await SCA.participantClaim(connection, seller, opInfo);
 	console.log("Seller claim " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ParticipantClaim");
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+
+	for (const arbiter of arbiters) {
+		let arbiterProfile = await getAccountData(connection, SCA.arbiterProfileAddress(arbiter.publicKey), AccountTypes.ArbiterProfile) as ArbiterProfileData
+		console.log(Util.toJSONString(arbiterProfile));
+	}
+
+	let lamports = await connection.getBalance(buyer.publicKey);
+	console.log("Buyer balance " + lamports.toString());
+
+	lamports = await connection.getBalance(seller.publicKey);
+	console.log("Seller balance " + lamports.toString());
+}
 
+// Seller proposes to refund half of the value, and the buyer accepts, ending the dispute
+async function settle(opInfo: PublicKey) {
 	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+	let buyerAmount = operationAccountInfo.value / BigInt(2);
+
+	let result = await SCA.proposeSettlement(connection, seller, buyerAmount, opInfo);
+	console.log("Seller proposes settlement " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "ProposeSettlement");
+
+	result = await SCA.acceptSettlement(connection, buyer, buyerAmount, opInfo);
+	console.log("Buyer accepts settlement " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "AcceptSettlement");
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
     console.log(Util.toJSONString(operationAccountInfo));
 
 	let lamports = await connection.getBalance(buyer.publicKey);
@@ -221,17 +510,84 @@ async function claim(opInfo: PublicKey) {
 	console.log("Seller balance " + lamports.toString());
 }
 
+// Seller refunds a tenth of the value, the rest stays in escrow
+async function sellerPartialRefund(opInfo: PublicKey) {
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+
+	let result = await SCA.sellerPartialRefund(connection, seller, buyer.publicKey, operationAccountInfo.value / BigInt(10), opInfo);
+	console.log("Seller partial refund " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "SellerPartialRefund");
+
+	operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+
+	let lamports = await connection.getBalance(buyer.publicKey);
+	console.log("Buyer balance " + lamports.toString());
+}
+
+// Buyer releases every milestone, one transaction each
+async function buyerReleasesMilestones(opInfo: PublicKey) {
+	let milestonesInfo = await getAccountData(connection, SCA.milestonesAddress(opInfo), AccountTypes.Milestones) as MilestonesData
+	console.log(Util.toJSONString(milestonesInfo));
+
+	for (let i = 0; i < milestonesInfo.count; i++) {
+		let result = await SCA.releaseMilestone(connection, buyer, seller.publicKey, i, opInfo);
+		console.log("Release milestone " + i + " " + JSON.stringify(result));
+		await Util.logComputeUnits(connection, result, "ReleaseMilestone");
+
+		let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+		console.log(Util.toJSONString(operationAccountInfo));
+	}
+
+	let lamports = await connection.getBalance(seller.publicKey);
+	console.log("Seller balance " + lamports.toString());
+}
+
+// Seller refunds a unit of the first line item
+async function sellerRefundsLineItem(opInfo: PublicKey) {
+	let result = await SCA.refundLineItem(connection, seller, buyer.publicKey, 0, 1, opInfo);
+	console.log("Seller refunds line item " + JSON.stringify(result));
+	await Util.logComputeUnits(connection, result, "RefundLineItem");
+
+	let lineItemsInfo = await getAccountData(connection, SCA.lineItemsAddress(opInfo), AccountTypes.LineItems) as LineItemsData
+	console.log(Util.toJSONString(lineItemsInfo));
+
+	let operationAccountInfo = await getAccountData(connection, opInfo, AccountTypes.Operation) as OperationAccountData
+    console.log(Util.toJSONString(operationAccountInfo));
+
+	let lamports = await connection.getBalance(buyer.publicKey);
+	console.log("Buyer balance " + lamports.toString());
+}
+
 (async () => {
 
      let opInfo = await recordItemInfo();
+     // await updateListing(opInfo);
      await assignBuyer(opInfo);
+     // Negotiation flow, instead of assignBuyer
+     // await negotiate(opInfo);
      await assignArbiters(opInfo);
      await approvals(opInfo);
      await buyerPays(opInfo);
+     // Pre-agreed flow, instead of the four steps above
+     // await assignArbiters(opInfo);
+     // await preAgreedDeal(opInfo);
 
 	 // Normal flow
+	 // await shipAndReceive(opInfo);
+	 // Attested flow, see recordItemInfo
+	 // await shipAndAttest(opInfo);
+	 // Inspection flow, after either of the two above, see recordItemInfo
+	 // await waitAndFinalize(opInfo);
+	 // await sellerPartialRefund(opInfo);
 	 // await buyerClose(opInfo);
 
+	 // Milestone flow, see recordItemInfo
+	 // await buyerReleasesMilestones(opInfo);
+
+	 // Order flow, see recordItemInfo
+	 // await sellerRefundsLineItem(opInfo);
+
 	 // Cancel flow
 	 // await sellerRefund(opInfo);
 
@@ -239,7 +595,18 @@ async function claim(opInfo: PublicKey) {
 	 await sellerDispute(opInfo);
 	 await sellerInfo(opInfo);
 	 await buyerInfo(opInfo);
+
+	 // Settlement flow
+	 // await settle(opInfo);
+
+	 await waitEvidencePeriod();
 	 await arbiterVotes(opInfo);
+	 await arbiterRuling(opInfo);
+
+	 // Appeal flow
+	 // await buyerAppeal(opInfo);
+
+	 await waitAppealPeriod();
 	 await claim(opInfo);
 	 /*
      let operationAccountInfo = await getAccountData(connection, 
@@ -26,4 +26,14 @@ export function toJSONString(obj: Object) {
             ? value.toString()
             : value // return everything else unchanged
     );
-}
\ No newline at end of file
+}
+
+export function sleep(ms: number): Promise<void> {
+    return new Promise(resolve => setTimeout(resolve, ms));
+}
+
+// Prints the compute units consumed by a confirmed transaction, to benchmark each instruction.
+export async function logComputeUnits(connection: Connection, sig: string, label: string) {
+    let tx = await connection.getTransaction(sig, {commitment: "confirmed", maxSupportedTransactionVersion: 0});
+    console.log("CU " + label + ": " + tx?.meta?.computeUnitsConsumed);
+}